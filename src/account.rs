@@ -1,10 +1,46 @@
 use crate::transaction::PublicKey;
+use crate::vm::{Contract, Storage};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Account {
   accounts: HashSet<PublicKey>,
   balances: HashMap<PublicKey, i64>,
+  nonces: HashMap<PublicKey, u64>,
+  multisigs: HashMap<PublicKey, Multisig>,
+  assets: HashMap<String, Asset>,
+  asset_balances: HashMap<PublicKey, HashMap<String, i64>>,
+  contracts: HashMap<PublicKey, Contract>,
+  /// Coins validators bonded, they can't be spent until unstaked.
+  stakes: HashMap<PublicKey, i64>,
+  /// Validators caught double signing, they can't forge or vote anymore.
+  jailed: HashSet<PublicKey>,
+  /// Unstaked coins that can still be slashed until they are released.
+  unbonding: HashMap<PublicKey, Vec<Unbond>>,
+}
+
+/// Coins on their way from a validator's stake back to its balance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unbond {
+  pub amount: i64,
+  /// Block count of the block that credits them to the balance.
+  pub release: u128,
+}
+
+/// A token issued by an account, identified by its symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+  pub symbol: String,
+  pub decimals: u8,
+  pub issuer: PublicKey,
+  pub supply: i64,
+}
+
+/// Keys allowed to spend from a multisig account and how many of them must sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Multisig {
+  pub keys: Vec<PublicKey>,
+  pub threshold: usize,
 }
 
 #[derive(Debug, PartialEq)]
@@ -12,11 +48,25 @@ pub enum AccountError {
   AccountNotFound(PublicKey),
 }
 
+impl Default for Account {
+  fn default() -> Self {
+    Account::new()
+  }
+}
+
 impl Account {
   pub fn new() -> Self {
     Self {
       accounts: HashSet::new(),
       balances: HashMap::new(),
+      nonces: HashMap::new(),
+      multisigs: HashMap::new(),
+      assets: HashMap::new(),
+      asset_balances: HashMap::new(),
+      contracts: HashMap::new(),
+      stakes: HashMap::new(),
+      jailed: HashSet::new(),
+      unbonding: HashMap::new(),
     }
   }
 
@@ -26,10 +76,172 @@ impl Account {
     }
   }
 
+  pub fn add_multisig(&mut self, account: PublicKey, multisig: Multisig) {
+    self.add_account(account.clone());
+    self.multisigs.insert(account, multisig);
+  }
+
+  pub fn multisig(&self, account: &str) -> Option<&Multisig> {
+    self.multisigs.get(account)
+  }
+
+  pub fn stake(&self, account: &str) -> i64 {
+    self.stakes.get(account).cloned().unwrap_or(0)
+  }
+
+  /// Stake of every validator that isn't jailed.
+  pub fn total_stake(&self) -> i64 {
+    self.validators().map(|(_, stake)| stake).sum()
+  }
+
+  pub fn update_stake(&mut self, account: &str, amount: i64) {
+    *self.stakes.entry(account.to_owned()).or_insert(0) += amount;
+    if self.stakes[account] == 0 {
+      self.stakes.remove(account);
+    }
+  }
+
+  /// Accounts with stake that aren't jailed and how much they hold.
+  pub fn validators(&self) -> impl Iterator<Item = (&PublicKey, i64)> {
+    self
+      .stakes
+      .iter()
+      .filter(move |(account, _)| !self.jailed.contains(*account))
+      .map(|(account, stake)| (account, *stake))
+  }
+
+  pub fn unbond(&mut self, account: PublicKey, unbond: Unbond) {
+    self.unbonding.entry(account).or_default().push(unbond);
+  }
+
+  pub fn unbonding(&self, account: &str) -> &[Unbond] {
+    self
+      .unbonding
+      .get(account)
+      .map(Vec::as_slice)
+      .unwrap_or_default()
+  }
+
+  /// Removes the unbonds released at or before `block_count`,
+  /// returning how much each account gets back.
+  pub fn release_unbonded(&mut self, block_count: u128) -> Vec<(PublicKey, i64)> {
+    let mut released = Vec::new();
+
+    for (account, unbonds) in &mut self.unbonding {
+      let amount: i64 = unbonds
+        .iter()
+        .filter(|unbond| unbond.release <= block_count)
+        .map(|unbond| unbond.amount)
+        .sum();
+
+      unbonds.retain(|unbond| unbond.release > block_count);
+
+      if amount > 0 {
+        released.push((account.clone(), amount));
+      }
+    }
+
+    self.unbonding.retain(|_, unbonds| !unbonds.is_empty());
+
+    released.sort();
+
+    released
+  }
+
+  /// Takes `percent` of every pending unbond, returning the total taken.
+  pub fn slash_unbonding(&mut self, account: &str, percent: i64) -> i64 {
+    let mut slashed = 0;
+
+    for unbond in self.unbonding.get_mut(account).into_iter().flatten() {
+      let amount = unbond.amount * percent / 100;
+      unbond.amount -= amount;
+      slashed += amount;
+    }
+
+    slashed
+  }
+
+  pub fn jail(&mut self, account: PublicKey) {
+    self.jailed.insert(account);
+  }
+
+  pub fn is_jailed(&self, account: &str) -> bool {
+    self.jailed.contains(account)
+  }
+
+  pub fn add_contract(&mut self, account: PublicKey, contract: Contract) {
+    self.add_account(account.clone());
+    self.contracts.insert(account, contract);
+  }
+
+  pub fn contract(&self, account: &str) -> Option<&Contract> {
+    self.contracts.get(account)
+  }
+
+  /// Replaces the contract's storage with what a successful call left.
+  pub fn set_contract_storage(&mut self, account: &str, storage: Storage) {
+    if let Some(contract) = self.contracts.get_mut(account) {
+      contract.storage = storage;
+    }
+  }
+
+  /// Registers the asset and credits its whole supply to the issuer.
+  pub fn issue_asset(&mut self, asset: Asset) {
+    self.update_asset_balance(&asset.issuer, &asset.symbol, asset.supply);
+    self.assets.insert(asset.symbol.clone(), asset);
+  }
+
+  pub fn asset(&self, symbol: &str) -> Option<&Asset> {
+    self.assets.get(symbol)
+  }
+
+  pub fn assets(&self) -> impl Iterator<Item = &Asset> {
+    self.assets.values()
+  }
+
+  pub fn asset_balance(&self, account: &str, symbol: &str) -> i64 {
+    self
+      .asset_balances
+      .get(account)
+      .and_then(|balances| balances.get(symbol))
+      .cloned()
+      .unwrap_or(0)
+  }
+
+  /// Balance of every asset the account ever held, by symbol.
+  pub fn asset_balances(&self, account: &str) -> HashMap<String, i64> {
+    self
+      .asset_balances
+      .get(account)
+      .cloned()
+      .unwrap_or_default()
+  }
+
+  pub fn update_asset_balance(&mut self, account: &str, symbol: &str, amount: i64) {
+    *self
+      .asset_balances
+      .entry(account.to_owned())
+      .or_default()
+      .entry(symbol.to_owned())
+      .or_insert(0) += amount;
+  }
+
   pub fn balance(&self, account: &String) -> Option<i64> {
     self.balances.get(account).cloned()
   }
 
+  /// How many transactions sent by the account were executed.
+  ///
+  /// Only a count, transactions don't carry a nonce and nothing checks it.
+  /// Replays are rejected by transaction id instead.
+  pub fn nonce(&self, account: &str) -> u64 {
+    self.nonces.get(account).cloned().unwrap_or(0)
+  }
+
+  pub fn increment_nonce(&mut self, account: &str) {
+    *self.nonces.entry(account.to_owned()).or_insert(0) += 1;
+  }
+
   // TODO: this is not atomic, will this be a problem?
   pub fn update_balance(&mut self, account: &String, amount: i64) -> Result<(), AccountError> {
     match self.balances.get(account) {
@@ -124,4 +336,99 @@ mod tests {
 
     assert_eq!(account.balance(&public_key), Some(2));
   }
+
+  #[test]
+  fn nonce_is_zero_for_accounts_that_never_sent_a_transaction() {
+    let account = Account::new();
+
+    assert_eq!(0, account.nonce("public_key"));
+  }
+
+  #[test]
+  fn increments_nonce() {
+    let mut account = Account::new();
+
+    account.increment_nonce("public_key");
+    account.increment_nonce("public_key");
+
+    assert_eq!(2, account.nonce("public_key"));
+  }
+
+  #[test]
+  fn multisig_accounts_start_with_zero_balance() {
+    let mut account = Account::new();
+
+    let multisig = Multisig {
+      keys: vec![String::from("a"), String::from("b")],
+      threshold: 2,
+    };
+
+    account.add_multisig(String::from("multisig"), multisig.clone());
+
+    assert_eq!(Some(0), account.balance(&String::from("multisig")));
+    assert_eq!(Some(&multisig), account.multisig("multisig"));
+    assert_eq!(None, account.multisig("a"));
+  }
+
+  #[test]
+  fn issuing_an_asset_credits_its_supply_to_the_issuer() {
+    let mut account = Account::new();
+
+    account.issue_asset(Asset {
+      symbol: String::from("GOLD"),
+      decimals: 2,
+      issuer: String::from("issuer"),
+      supply: 100,
+    });
+
+    account.update_asset_balance("issuer", "GOLD", -30);
+    account.update_asset_balance("receiver", "GOLD", 30);
+
+    assert_eq!(70, account.asset_balance("issuer", "GOLD"));
+    assert_eq!(30, account.asset_balance("receiver", "GOLD"));
+    assert_eq!(0, account.asset_balance("receiver", "SILVER"));
+    assert_eq!(1, account.assets().count());
+  }
+
+  #[test]
+  fn accounts_without_stake_are_not_validators() {
+    let mut account = Account::new();
+
+    account.update_stake("a", 10);
+    account.update_stake("b", 5);
+    account.update_stake("b", -5);
+
+    assert_eq!(10, account.stake("a"));
+    assert_eq!(10, account.total_stake());
+    assert_eq!(
+      vec![(&String::from("a"), 10)],
+      account.validators().collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn unbonded_coins_are_released_at_their_block_count() {
+    let mut account = Account::new();
+
+    account.unbond(
+      String::from("a"),
+      Unbond {
+        amount: 10,
+        release: 5,
+      },
+    );
+    account.unbond(
+      String::from("a"),
+      Unbond {
+        amount: 20,
+        release: 6,
+      },
+    );
+
+    assert_eq!(15, account.slash_unbonding("a", 50));
+    assert_eq!(Vec::<(PublicKey, i64)>::new(), account.release_unbonded(4));
+    assert_eq!(vec![(String::from("a"), 5)], account.release_unbonded(5));
+    assert_eq!(vec![(String::from("a"), 10)], account.release_unbonded(6));
+    assert!(account.unbonding("a").is_empty());
+  }
 }
@@ -0,0 +1,435 @@
+//! Error bodies returned by the http api.
+
+use crate::account::AccountError;
+use crate::chain::ChainError;
+use crate::node::NodeError;
+use actix_web::{
+  error::{JsonPayloadError, QueryPayloadError},
+  http::StatusCode,
+  HttpRequest, HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// `{"code": "invalid_signature", "message": "...", "details": {...}}`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ApiError {
+  #[serde(skip)]
+  pub status: StatusCode,
+  pub code: &'static str,
+  pub message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub details: Option<Value>,
+}
+
+impl ApiError {
+  pub fn new(status: StatusCode, code: &'static str, message: &str) -> Self {
+    Self {
+      status,
+      code,
+      message: message.to_owned(),
+      details: None,
+    }
+  }
+
+  pub fn with_details(mut self, details: Value) -> Self {
+    self.details = Some(details);
+    self
+  }
+}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.code, self.message)
+  }
+}
+
+impl ResponseError for ApiError {
+  fn status_code(&self) -> StatusCode {
+    self.status
+  }
+
+  fn error_response(&self) -> HttpResponse {
+    HttpResponse::build(self.status).json(self)
+  }
+}
+
+impl From<AccountError> for ApiError {
+  fn from(error: AccountError) -> Self {
+    match error {
+      AccountError::AccountNotFound(account) => ApiError::new(
+        StatusCode::NOT_FOUND,
+        "account_not_found",
+        "account not found",
+      )
+      .with_details(json!({ "account": account })),
+    }
+  }
+}
+
+impl From<ChainError> for ApiError {
+  fn from(error: ChainError) -> Self {
+    match error {
+      ChainError::InvalidBlockHash(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_block_hash",
+        "block does not point to the tip of the chain",
+      )
+      .with_details(json!({ "hash": block.hash(), "last_hash": block.last_hash() })),
+      ChainError::InvalidBlockCount(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_block_count",
+        "block does not follow the tip of the chain",
+      )
+      .with_details(json!({ "hash": block.hash(), "block_count": block.block_count() })),
+      ChainError::AccountNotFound(account) => ApiError::new(
+        StatusCode::NOT_FOUND,
+        "account_not_found",
+        "account not found",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::TransactionsFailed(errors) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "transactions_failed",
+        "some transactions in the block failed",
+      )
+      .with_details(json!({
+        "errors": errors.into_iter().map(ApiError::from).collect::<Vec<_>>()
+      })),
+      ChainError::BlockTooLarge(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "block_too_large",
+        "block is larger than allowed",
+      )
+      .with_details(json!({ "hash": block.hash(), "size": block.size() })),
+      ChainError::TooManyTransactions(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "too_many_transactions",
+        "block has more transactions than allowed",
+      )
+      .with_details(json!({
+        "hash": block.hash(),
+        "transactions": block.block.transactions.len(),
+      })),
+      ChainError::TimestampBeforeParent(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "timestamp_before_parent",
+        "block is older than its parent",
+      )
+      .with_details(json!({ "hash": block.hash(), "timestamp": block.block.timestamp() })),
+      ChainError::TimestampInTheFuture(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "timestamp_in_the_future",
+        "block timestamp is too far in the future",
+      )
+      .with_details(json!({ "hash": block.hash(), "timestamp": block.block.timestamp() })),
+      ChainError::DuplicateTransaction(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "duplicate_transaction",
+        "transaction was already included",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::InvalidTransactionSignature(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_transaction_signature",
+        "transaction isn't signed by the account it acts for",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::InvalidMultisig(account) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_multisig",
+        "threshold must be between one and the number of keys",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::AccountAlreadyExists(account) => ApiError::new(
+        StatusCode::CONFLICT,
+        "account_already_exists",
+        "account already exists",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::NotMultisig(account) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "not_multisig",
+        "account is not a multisig account",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::MultisigRequired(account) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "multisig_required",
+        "transfers from multisig accounts need cosignatures",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::NotEnoughSignatures {
+        account,
+        required,
+        valid,
+      } => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "not_enough_signatures",
+        "not enough valid cosignatures",
+      )
+      .with_details(json!({ "account": account, "required": required, "valid": valid })),
+      ChainError::MemoTooLarge(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "memo_too_large",
+        "memo is longer than allowed",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::InvalidContract(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_contract",
+        "invalid contract code, address or gas limit",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::ContractNotFound(contract) => ApiError::new(
+        StatusCode::NOT_FOUND,
+        "contract_not_found",
+        "contract not found",
+      )
+      .with_details(json!({ "contract": contract })),
+      ChainError::ContractFailed {
+        id,
+        error,
+        gas_used,
+      } => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "contract_failed",
+        "contract call failed",
+      )
+      .with_details(json!({ "id": id, "error": format!("{:?}", error), "gas_used": gas_used })),
+      ChainError::InvalidBatch(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_batch",
+        "batch must pay between one and the maximum number of outputs a positive amount",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::InsufficientBalance(account) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "insufficient_balance",
+        "sender's balance doesn't cover the transfer",
+      )
+      .with_details(json!({ "account": account })),
+      ChainError::InvalidAsset(symbol) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_asset",
+        "invalid asset symbol, decimals or supply",
+      )
+      .with_details(json!({ "symbol": symbol })),
+      ChainError::AssetAlreadyExists(symbol) => ApiError::new(
+        StatusCode::CONFLICT,
+        "asset_already_exists",
+        "asset already exists",
+      )
+      .with_details(json!({ "symbol": symbol })),
+      ChainError::AssetNotFound(symbol) => {
+        ApiError::new(StatusCode::NOT_FOUND, "asset_not_found", "asset not found")
+          .with_details(json!({ "symbol": symbol }))
+      }
+      ChainError::InsufficientAssetBalance { account, symbol } => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "insufficient_asset_balance",
+        "sender doesn't hold enough of the asset",
+      )
+      .with_details(json!({ "account": account, "symbol": symbol })),
+      ChainError::InsufficientStake(validator) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "insufficient_stake",
+        "validator's stake doesn't cover the amount",
+      )
+      .with_details(json!({ "validator": validator })),
+      ChainError::InvalidVote(signed_vote) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_vote",
+        "vote isn't signed by a validator or isn't for a checkpoint height",
+      )
+      .with_details(json!({ "vote": signed_vote.vote })),
+      ChainError::InvalidEvidence(id) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_evidence",
+        "blocks don't prove the forger double signed",
+      )
+      .with_details(json!({ "id": id })),
+      ChainError::ForgerJailed(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "forger_jailed",
+        "block was forged by a jailed validator",
+      )
+      .with_details(json!({ "hash": block.hash(), "forger": block.block.forger() })),
+      ChainError::ValidatorJailed(validator) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "validator_jailed",
+        "jailed validators can't unstake",
+      )
+      .with_details(json!({ "validator": validator })),
+      ChainError::UnexpectedForger(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "unexpected_forger",
+        "block wasn't forged by the validator picked for it",
+      )
+      .with_details(json!({ "hash": block.hash(), "forger": block.block.forger() })),
+      ChainError::ReorgPastFinalized(block) => ApiError::new(
+        StatusCode::CONFLICT,
+        "reorg_past_finalized",
+        "the fork would drop a final block",
+      )
+      .with_details(json!({ "hash": block.hash(), "block_count": block.block_count() })),
+      ChainError::TransactionLocked { id, unlock } => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "transaction_locked",
+        "transaction can't be included yet",
+      )
+      .with_details(json!({ "id": id, "unlock": unlock })),
+      ChainError::ForkNotPreferred(block) => ApiError::new(
+        StatusCode::CONFLICT,
+        "fork_not_preferred",
+        "the current chain is preferred over the fork",
+      )
+      .with_details(json!({ "hash": block.hash(), "block_count": block.block_count() })),
+    }
+  }
+}
+
+impl From<NodeError> for ApiError {
+  fn from(error: NodeError) -> Self {
+    match error {
+      NodeError::InvalidSignature {
+        public_key,
+        signed_transaction,
+      } => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_signature",
+        "invalid signature",
+      )
+      .with_details(json!({
+        "public_key": public_key,
+        "transaction": signed_transaction.transaction.id(),
+      })),
+      NodeError::InvalidBlockSignature(block) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_block_signature",
+        "invalid block signature",
+      )
+      .with_details(json!({ "hash": block.hash() })),
+      NodeError::Chain(error) => error.into(),
+      NodeError::PeerBanned(peer) => {
+        ApiError::new(StatusCode::FORBIDDEN, "peer_banned", "peer is banned")
+          .with_details(json!({ "peer": peer }))
+      }
+      NodeError::RateLimited(peer) => ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        "too many messages",
+      )
+      .with_details(json!({ "peer": peer })),
+      NodeError::NotForger(forger) => ApiError::new(
+        StatusCode::CONFLICT,
+        "not_forger",
+        "another validator forges the next block",
+      )
+      .with_details(json!({ "forger": forger })),
+      NodeError::NotExecutable(error) => ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "not_executable",
+        "transaction can't be executed",
+      )
+      .with_details(json!({ "error": ApiError::from(error) })),
+    }
+  }
+}
+
+impl From<JsonPayloadError> for ApiError {
+  fn from(error: JsonPayloadError) -> Self {
+    let (status, code) = match &error {
+      JsonPayloadError::ContentType => {
+        (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type")
+      }
+      JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+        (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large")
+      }
+      _ => (StatusCode::BAD_REQUEST, "invalid_json"),
+    };
+
+    ApiError::new(status, code, &error.to_string())
+  }
+}
+
+impl From<QueryPayloadError> for ApiError {
+  fn from(error: QueryPayloadError) -> Self {
+    ApiError::new(StatusCode::BAD_REQUEST, "invalid_query", &error.to_string())
+  }
+}
+
+/// Used as the `QueryConfig` error handler so bad query strings get an `ApiError` too.
+pub fn query_error_handler(error: QueryPayloadError, _request: &HttpRequest) -> actix_web::Error {
+  ApiError::from(error).into()
+}
+
+/// Used as the `JsonConfig` error handler so malformed bodies get an `ApiError` too.
+pub fn json_error_handler(error: JsonPayloadError, _request: &HttpRequest) -> actix_web::Error {
+  ApiError::from(error).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn maps_account_not_found_to_not_found() {
+    let error = ApiError::from(ChainError::AccountNotFound(String::from("a")));
+
+    assert_eq!(StatusCode::NOT_FOUND, error.status_code());
+    assert_eq!("account_not_found", error.code);
+    assert_eq!(Some(json!({ "account": "a" })), error.details);
+  }
+
+  #[test]
+  fn chain_errors_wrapped_by_the_node_keep_their_mapping() {
+    let error = ApiError::from(NodeError::Chain(ChainError::AccountNotFound(String::from(
+      "a",
+    ))));
+
+    assert_eq!(
+      ApiError::from(AccountError::AccountNotFound(String::from("a"))),
+      error
+    );
+  }
+
+  #[test]
+  fn includes_every_failed_transaction_in_the_details() {
+    let error = ApiError::from(ChainError::TransactionsFailed(vec![
+      ChainError::AccountNotFound(String::from("a")),
+      ChainError::AccountNotFound(String::from("b")),
+    ]));
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, error.status_code());
+    assert_eq!(
+      2,
+      error.details.unwrap()["errors"].as_array().unwrap().len()
+    );
+  }
+
+  #[test]
+  fn maps_rate_limits_to_too_many_requests() {
+    let error = ApiError::from(NodeError::RateLimited(String::from("peer")));
+
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, error.status_code());
+  }
+
+  #[test]
+  fn serializes_without_the_status() {
+    let error = ApiError::new(StatusCode::BAD_REQUEST, "invalid_json", "expected value");
+
+    assert_eq!(
+      r#"{"code":"invalid_json","message":"expected value"}"#,
+      serde_json::to_string(&error).unwrap()
+    );
+  }
+
+  #[test]
+  fn maps_wrong_content_type_to_unsupported_media_type() {
+    let error = ApiError::from(JsonPayloadError::ContentType);
+
+    assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, error.status_code());
+  }
+}
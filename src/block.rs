@@ -1,9 +1,10 @@
 use crate::transaction::PublicKey;
 use crate::wallet::SignedTransaction;
+use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use std::time::SystemTime;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Block {
   pub transactions: Vec<SignedTransaction>,
   last_hash: String,
@@ -19,15 +20,31 @@ impl Block {
     forger: PublicKey,
     block_count: u128,
   ) -> Self {
-    Self {
+    Block::with_timestamp(
       transactions,
       last_hash,
       forger,
       block_count,
-      timestamp: SystemTime::now()
+      SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_micros(),
+    )
+  }
+
+  pub fn with_timestamp(
+    transactions: Vec<SignedTransaction>,
+    last_hash: String,
+    forger: PublicKey,
+    block_count: u128,
+    timestamp: u128,
+  ) -> Self {
+    Self {
+      transactions,
+      last_hash,
+      forger,
+      block_count,
+      timestamp,
     }
   }
 
@@ -38,16 +55,13 @@ impl Block {
   }
 
   pub fn genesis() -> Block {
-    let mut block = Block::new(
+    Block::with_timestamp(
       Vec::new(),
       String::from("genesis_hash"),
       String::from("genesis_forger"),
       0,
-    );
-
-    block.timestamp = 0;
-
-    block
+      0,
+    )
   }
 
   pub fn last_hash(&self) -> String {
@@ -57,6 +71,14 @@ impl Block {
   pub fn block_count(&self) -> u128 {
     self.block_count
   }
+
+  pub fn forger(&self) -> &PublicKey {
+    &self.forger
+  }
+
+  pub fn timestamp(&self) -> u128 {
+    self.timestamp
+  }
 }
 
 #[cfg(test)]
@@ -91,4 +113,17 @@ mod tests {
 
     assert_eq!(0, block.block_count());
   }
+
+  #[test]
+  fn creates_block_with_the_given_timestamp() {
+    let block = Block::with_timestamp(
+      Vec::new(),
+      String::from("last_hash"),
+      String::from("forger_public_key"),
+      1,
+      42,
+    );
+
+    assert_eq!(42, block.timestamp());
+  }
 }
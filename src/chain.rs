@@ -1,11 +1,44 @@
-use crate::account::{Account, AccountError};
-use crate::transaction::{PublicKey, Transaction};
-use crate::wallet::{SignedBlock, SignedTransaction};
+use crate::account::{Account, AccountError, Asset, Multisig, Unbond};
+use crate::clock::{Clock, SystemClock};
+use crate::consensus::ConsensusParams;
+use crate::epoch::Epoch;
+use crate::events::{Event, TransactionStatus};
+use crate::finality::{Checkpoint, Finality, SignedVote};
+use crate::index::{Location, TransactionIndex};
+use crate::metrics::METRICS;
+use crate::receipt::{ExecutionStatus, Receipt};
+use crate::transaction::{Lock, PublicKey, Transaction};
+use crate::vm::{self, Contract, ContractReceipt, VmError, VmFailure};
+use crate::wallet::{SignedBlock, SignedTransaction, Wallet};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, info, info_span};
+
+/// Asset symbols are 1 to this many uppercase letters or digits.
+pub const MAX_ASSET_SYMBOL_LENGTH: usize = 12;
+
+pub const MAX_ASSET_DECIMALS: u8 = 18;
 
 #[derive(Debug)]
 pub struct Chain {
   blocks: Vec<SignedBlock>,
   account: Account,
+  allocations: Vec<(PublicKey, i64)>,
+  events: Vec<Event>,
+  index: TransactionIndex,
+  /// Height of every block by hash.
+  heights: HashMap<String, usize>,
+  params: ConsensusParams,
+  /// Coins in existence, genesis allocations plus block rewards.
+  supply: i64,
+  /// Only used to reject blocks from the future.
+  clock: Arc<dyn Clock>,
+  /// One receipt per transaction of each block, same indices as `blocks`.
+  receipts: Vec<Vec<Receipt>>,
+  finality: Finality,
+  /// Validator set of every epoch so far, the last one is the current one.
+  epochs: Vec<Epoch>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -14,255 +47,3146 @@ pub enum ChainError {
   InvalidBlockCount(SignedBlock),
   AccountNotFound(PublicKey),
   TransactionsFailed(Vec<ChainError>),
+  ForkNotPreferred(SignedBlock),
+  BlockTooLarge(SignedBlock),
+  TooManyTransactions(SignedBlock),
+  TimestampBeforeParent(SignedBlock),
+  TimestampInTheFuture(SignedBlock),
+  DuplicateTransaction(String),
+  /// The transaction isn't signed by its sender, or one of its cosignatures is forged.
+  InvalidTransactionSignature(String),
+  /// The threshold is zero or above the number of distinct keys,
+  /// or the address wasn't derived from the transaction.
+  InvalidMultisig(PublicKey),
+  AccountAlreadyExists(PublicKey),
+  /// A multisig transfer from an account that isn't multisig.
+  NotMultisig(PublicKey),
+  /// A plain transfer from a multisig account.
+  MultisigRequired(PublicKey),
+  NotEnoughSignatures {
+    account: PublicKey,
+    required: usize,
+    valid: usize,
+  },
+  MemoTooLarge(String),
+  /// Empty or oversized code, a gas limit above the maximum,
+  /// or an address that wasn't derived from the transaction.
+  InvalidContract(String),
+  ContractNotFound(PublicKey),
+  /// The caller still pays for `gas_used`.
+  ContractFailed {
+    id: String,
+    error: VmError,
+    gas_used: u64,
+  },
+  /// A batch transfer with no outputs, too many or a non positive amount.
+  InvalidBatch(String),
+  InsufficientBalance(PublicKey),
+  /// Bad symbol, too many decimals or a supply that isn't positive.
+  InvalidAsset(String),
+  AssetAlreadyExists(String),
+  AssetNotFound(String),
+  /// The amount isn't positive or is more than the sender holds.
+  InsufficientAssetBalance {
+    account: PublicKey,
+    symbol: String,
+  },
+  /// Unstaking more than the validator's stake, or a non positive amount.
+  InsufficientStake(PublicKey),
+  /// Bad signature, a voter that isn't a validator or a height that isn't a checkpoint.
+  InvalidVote(SignedVote),
+  /// The blocks weren't both signed by the same forger at the same height,
+  /// are the same block, or the forger was already jailed.
+  InvalidEvidence(String),
+  ForgerJailed(SignedBlock),
+  /// Jailed validators can't unstake, their stake stays slashable.
+  ValidatorJailed(PublicKey),
+  /// The epoch's validator set picked someone else to forge the block.
+  UnexpectedForger(SignedBlock),
+  /// The fork would drop a final block.
+  ReorgPastFinalized(SignedBlock),
+  /// A locked transfer included before it unlocked.
+  TransactionLocked {
+    id: String,
+    unlock: Lock,
+  },
+}
+
+impl Default for Chain {
+  fn default() -> Self {
+    Chain::new()
+  }
 }
 
 impl Chain {
   pub fn new() -> Self {
+    Chain::with_allocations(Vec::new())
+  }
+
+  /// Creates a chain whose genesis state credits `allocations`.
+  pub fn with_allocations(allocations: Vec<(PublicKey, i64)>) -> Self {
+    Chain::with_params(
+      allocations,
+      ConsensusParams::default(),
+      Arc::new(SystemClock),
+    )
+  }
+
+  pub fn with_params(
+    allocations: Vec<(PublicKey, i64)>,
+    params: ConsensusParams,
+    clock: Arc<dyn Clock>,
+  ) -> Self {
+    params.check();
+
+    let mut account = Account::new();
+
+    for (public_key, amount) in &allocations {
+      account.add_account(public_key.clone());
+      account.update_balance(public_key, *amount).unwrap();
+    }
+
+    let supply = allocations.iter().map(|(_, amount)| amount).sum();
+
+    let epoch = Epoch {
+      number: 0,
+      start: 1,
+      validators: BTreeMap::new(),
+    };
+
     Self {
       blocks: vec![SignedBlock::genesis()],
-      account: Account::new(),
+      heights: vec![(SignedBlock::genesis().hash(), 0)]
+        .into_iter()
+        .collect(),
+      account,
+      allocations,
+      events: Vec::new(),
+      index: TransactionIndex::new(),
+      supply,
+      params,
+      clock,
+      receipts: vec![Vec::new()],
+      finality: Finality::new(),
+      epochs: vec![epoch],
+    }
+  }
+
+  /// Adds blocks that were already accepted, e.g. before a restart.
+  pub fn restore(&mut self, blocks: Vec<SignedBlock>) -> Result<(), ChainError> {
+    for signed_block in blocks {
+      match self.add(signed_block) {
+        Ok(()) | Err(ChainError::TransactionsFailed(_)) => {}
+        Err(error) => return Err(error),
+      }
+    }
+
+    self.events.clear();
+
+    Ok(())
+  }
+
+  pub fn params(&self) -> &ConsensusParams {
+    &self.params
+  }
+
+  pub fn supply(&self) -> i64 {
+    self.supply
+  }
+
+  /// What the forger of the next block will earn.
+  pub fn next_block_reward(&self) -> i64 {
+    self
+      .params
+      .block_reward(self.tip().block_count() + 1, self.supply)
+  }
+
+  /// Credits the forger with the block reward.
+  fn reward(&mut self, forger: &PublicKey, block_count: u128) {
+    let reward = self.params.block_reward(block_count, self.supply);
+
+    if reward == 0 {
+      return;
+    }
+
+    self.account.add_account(forger.clone());
+    self.account.update_balance(forger, reward).unwrap();
+    self.supply += reward;
+
+    self.events.push(Event::Balance {
+      account: forger.clone(),
+      balance: self.account.balance(forger).unwrap(),
+    });
+  }
+
+  /// Takes the events produced since the last call.
+  pub fn events(&mut self) -> Vec<Event> {
+    std::mem::take(&mut self.events)
+  }
+
+  /// Identifies the chain, nodes only talk to peers with the same genesis block.
+  pub fn id(&self) -> String {
+    self.blocks[0].hash()
+  }
+
+  pub fn tip(&self) -> &SignedBlock {
+    self.blocks.last().unwrap()
+  }
+
+  pub fn blocks(&self) -> &[SignedBlock] {
+    &self.blocks
+  }
+
+  pub fn account(&self) -> &Account {
+    &self.account
+  }
+
+  pub fn block_by_height(&self, block_count: u128) -> Option<&SignedBlock> {
+    self.blocks.get(block_count as usize)
+  }
+
+  pub fn block_by_hash(&self, hash: &str) -> Option<&SignedBlock> {
+    self.heights.get(hash).map(|height| &self.blocks[*height])
+  }
+
+  pub fn contains_block(&self, signed_block: &SignedBlock) -> bool {
+    self
+      .blocks
+      .get(signed_block.block_count() as usize)
+      .map(|block| block.hash() == signed_block.hash())
+      .unwrap_or(false)
+  }
+
+  pub fn contains_transaction(&self, signed_transaction: &SignedTransaction) -> bool {
+    self
+      .transaction(signed_transaction.transaction.id())
+      .map(|(_, included)| included == signed_transaction)
+      .unwrap_or(false)
+  }
+
+  fn contains_transaction_id(&self, id: &str) -> bool {
+    self.index.location(id).is_some()
+  }
+
+  pub fn transaction(&self, id: &str) -> Option<(Location, &SignedTransaction)> {
+    let location = self.index.location(id)?;
+
+    let signed_block = &self.blocks[location.block_count as usize];
+
+    Some((
+      location,
+      &signed_block.block.transactions[location.position],
+    ))
+  }
+
+  /// Transactions the account sent or received, oldest first.
+  pub fn account_transactions(
+    &self,
+    account: &str,
+  ) -> impl DoubleEndedIterator<Item = (Location, &SignedTransaction)> + ExactSizeIterator {
+    self.located(self.index.account(account))
+  }
+
+  /// Transactions carrying exactly this memo, oldest first.
+  pub fn memo_transactions(
+    &self,
+    memo: &str,
+  ) -> impl DoubleEndedIterator<Item = (Location, &SignedTransaction)> + ExactSizeIterator {
+    self.located(self.index.memo(memo))
+  }
+
+  fn located<'a>(
+    &'a self,
+    locations: &'a [Location],
+  ) -> impl DoubleEndedIterator<Item = (Location, &'a SignedTransaction)> + ExactSizeIterator {
+    locations.iter().map(move |location| {
+      let signed_block = &self.blocks[location.block_count as usize];
+
+      (
+        *location,
+        &signed_block.block.transactions[location.position],
+      )
+    })
+  }
+
+  /// Checks the consensus rules that don't depend on executing the transactions.
+  fn validate(&self, signed_block: &SignedBlock) -> Result<(), ChainError> {
+    let transactions = &signed_block.block.transactions;
+
+    if transactions.len() > self.params.max_transactions {
+      return Err(ChainError::TooManyTransactions(signed_block.clone()));
+    }
+
+    if signed_block.size() > self.params.max_block_size {
+      return Err(ChainError::BlockTooLarge(signed_block.clone()));
+    }
+
+    if self.account.is_jailed(signed_block.block.forger()) {
+      return Err(ChainError::ForgerJailed(signed_block.clone()));
+    }
+
+    if let Some(forger) = self.next_forger(signed_block.block.timestamp()) {
+      // A later round only starts once our own clock says the earlier forgers
+      // timed out, otherwise anyone could move its timestamp ahead to a round it forges in.
+      let round_started = self.round_start(signed_block.block.timestamp()) <= self.clock.now();
+
+      if forger != signed_block.block.forger() || !round_started {
+        return Err(ChainError::UnexpectedForger(signed_block.clone()));
+      }
+    }
+
+    let timestamp = signed_block.block.timestamp();
+
+    if timestamp < self.tip().block.timestamp() {
+      return Err(ChainError::TimestampBeforeParent(signed_block.clone()));
+    }
+
+    if timestamp > self.clock.now() + self.params.max_timestamp_drift.as_micros() {
+      return Err(ChainError::TimestampInTheFuture(signed_block.clone()));
+    }
+
+    let mut ids = HashSet::new();
+
+    for signed_transaction in transactions {
+      let id = signed_transaction.transaction.id();
+
+      if !ids.insert(id) || self.contains_transaction_id(id) {
+        return Err(ChainError::DuplicateTransaction(id.clone()));
+      }
+
+      if !Wallet::verify_signer(signed_transaction) {
+        return Err(ChainError::InvalidTransactionSignature(id.clone()));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Fork choice: the chain with more blocks wins and ties are broken
+  /// by the smallest tip hash so every node picks the same fork.
+  pub fn prefers(&self, tip: &SignedBlock) -> bool {
+    let our_tip = self.tip();
+
+    match tip.block_count().cmp(&our_tip.block_count()) {
+      Ordering::Greater => true,
+      Ordering::Less => false,
+      Ordering::Equal => tip.hash() < our_tip.hash(),
+    }
+  }
+
+  /// Replaces the chain with `blocks` (every block after genesis) if they
+  /// form a valid chain that is preferred over ours.
+  ///
+  /// Returns the blocks that were dropped from our chain.
+  pub fn reorganize(&mut self, blocks: Vec<SignedBlock>) -> Result<Vec<SignedBlock>, ChainError> {
+    let mut candidate = Chain::with_params(
+      self.allocations.clone(),
+      self.params.clone(),
+      Arc::clone(&self.clock),
+    );
+
+    let mut blocks = blocks.into_iter().peekable();
+
+    // Blocks we already have were already reported.
+    while let Some(signed_block) = blocks.next_if(|block| self.contains_block(block)) {
+      match candidate.add(signed_block) {
+        Ok(()) | Err(ChainError::TransactionsFailed(_)) => {}
+        Err(error) => return Err(error),
+      }
+    }
+
+    candidate.events.clear();
+
+    for signed_block in blocks {
+      match candidate.add(signed_block) {
+        Ok(()) | Err(ChainError::TransactionsFailed(_)) => {}
+        Err(error) => return Err(error),
+      }
+    }
+
+    if let Some(checkpoint) = self.finality.finalized() {
+      if !candidate.contains_checkpoint(checkpoint) {
+        return Err(ChainError::ReorgPastFinalized(candidate.tip().clone()));
+      }
+    }
+
+    if !self.prefers(candidate.tip()) {
+      return Err(ChainError::ForkNotPreferred(candidate.tip().clone()));
+    }
+
+    let orphaned: Vec<SignedBlock> = self
+      .blocks
+      .iter()
+      .filter(|block| !candidate.contains_block(block))
+      .cloned()
+      .collect();
+
+    // Votes only count once their checkpoint is in the chain, so keeping
+    // the ones for orphaned blocks is harmless, and the ones for the
+    // fork's checkpoints may finalize them now.
+    candidate.finality = std::mem::take(&mut self.finality);
+
+    *self = candidate;
+
+    for checkpoint in self.finality.checkpoints() {
+      self.try_finalize(&checkpoint);
+    }
+
+    // Balances touched only by the orphaned blocks were reverted.
+    let mut accounts: Vec<&PublicKey> = orphaned
+      .iter()
+      .flat_map(|block| {
+        block
+          .block
+          .transactions
+          .iter()
+          .flat_map(|signed_transaction| signed_transaction.transaction.accounts())
+          .chain(std::iter::once(block.block.forger()))
+      })
+      .collect();
+
+    accounts.sort();
+    accounts.dedup();
+
+    for account in accounts {
+      if let Some(balance) = self.account.balance(account) {
+        self.events.push(Event::Balance {
+          account: account.clone(),
+          balance,
+        });
+      }
+    }
+
+    Ok(orphaned)
+  }
+
+  pub fn add(&mut self, signed_block: SignedBlock) -> Result<(), ChainError> {
+    let _span = info_span!(
+      "apply_block",
+      block_count = %signed_block.block_count(),
+      hash = %signed_block.hash(),
+    )
+    .entered();
+
+    let last_block = self.tip();
+
+    // TODO: make hash a property of the block to avoid computing it every time?
+    // NOTE: is it safe to make the hash a property of the block?
+    if last_block.hash() != signed_block.last_hash() {
+      return Err(ChainError::InvalidBlockHash(signed_block));
+    }
+
+    if last_block.block_count() + 1 != signed_block.block_count() {
+      return Err(ChainError::InvalidBlockCount(signed_block));
+    }
+
+    self.validate(&signed_block)?;
+
+    let _timer = METRICS.block_apply_seconds.start_timer();
+
+    let mut failed_transactions = Vec::new();
+
+    let block_count = signed_block.block_count();
+
+    let mut receipts = Vec::with_capacity(signed_block.block.transactions.len());
+
+    for (position, transaction) in signed_block.block.transactions.iter().enumerate() {
+      let (status, execution_status, contract) = match self.run(transaction) {
+        Ok(contract) => (
+          TransactionStatus::Included { block_count },
+          ExecutionStatus::Success,
+          contract,
+        ),
+        Err(error) => {
+          let reason = format!("{:?}", error);
+          let contract = match &error {
+            ChainError::ContractFailed { gas_used, .. } => Some(ContractReceipt {
+              gas_used: *gas_used,
+              logs: Vec::new(),
+            }),
+            _ => None,
+          };
+          failed_transactions.push(error);
+          (
+            TransactionStatus::Failed {
+              block_count,
+              reason: reason.clone(),
+            },
+            ExecutionStatus::Failed { reason },
+            contract,
+          )
+        }
+      };
+
+      let fee = contract
+        .as_ref()
+        .map_or(0, |contract| self.params.fee(contract.gas_used));
+
+      receipts.push(Receipt {
+        id: transaction.transaction.id().clone(),
+        block_count,
+        position,
+        status: execution_status,
+        fee,
+        balances: transaction
+          .transaction
+          .accounts()
+          .into_iter()
+          .filter_map(|account| {
+            self
+              .account
+              .balance(account)
+              .map(|balance| (account.clone(), balance))
+          })
+          .collect::<BTreeMap<_, _>>(),
+        contract,
+      });
+
+      self.events.push(Event::TransactionStatus {
+        id: transaction.transaction.id().clone(),
+        status,
+      });
+
+      for account in transaction.transaction.accounts() {
+        if let Some(balance) = self.account.balance(account) {
+          self.events.push(Event::Balance {
+            account: account.clone(),
+            balance,
+          });
+        }
+      }
+    }
+
+    for (validator, amount) in self.account.release_unbonded(block_count) {
+      // Unbonding accounts always exist, they had to exist to stake.
+      let _ = self.account.update_balance(&validator, amount);
+      if let Some(balance) = self.account.balance(&validator) {
+        self.events.push(Event::Balance {
+          account: validator,
+          balance,
+        });
+      }
+    }
+
+    self.reward(signed_block.block.forger(), block_count);
+
+    self.events.push(Event::Block {
+      block_count,
+      hash: signed_block.hash(),
+      transactions: signed_block.block.transactions.len(),
+    });
+
+    debug!(
+      transactions = signed_block.block.transactions.len(),
+      failed = failed_transactions.len(),
+      "block applied"
+    );
+
+    self.index.add(&signed_block);
+
+    self.heights.insert(signed_block.hash(), self.blocks.len());
+    self.blocks.push(signed_block);
+    self.receipts.push(receipts);
+
+    if block_count.is_multiple_of(self.params.epoch_length.max(1)) {
+      self.start_epoch();
+    }
+
+    // Votes may have arrived before the block.
+    if let Some(checkpoint) = self.checkpoint(block_count) {
+      self.try_finalize(&checkpoint);
+    }
+
+    if !failed_transactions.is_empty() {
+      return Err(ChainError::TransactionsFailed(failed_transactions));
+    }
+
+    Ok(())
+  }
+
+  /// Checks that the transaction could be executed on top of the tip without executing it.
+  pub fn can_execute(&self, signed_transaction: &SignedTransaction) -> Result<(), ChainError> {
+    self.check_memo(&signed_transaction.transaction)?;
+
+    match &signed_transaction.transaction {
+      Transaction::LockedTransfer { id, unlock, .. } if !self.is_unlocked(unlock) => {
+        Err(ChainError::TransactionLocked {
+          id: id.clone(),
+          unlock: *unlock,
+        })
+      }
+      Transaction::Transfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      }
+      | Transaction::LockedTransfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[sender, receiver])?;
+        if self.account.multisig(sender).is_some() {
+          return Err(ChainError::MultisigRequired(sender.clone()));
+        }
+        self.ensure_can_pay(sender, *amount)
+      }
+      Transaction::BatchTransfer {
+        id,
+        sender,
+        outputs,
+        ..
+      } => {
+        self.ensure_accounts_exist(&signed_transaction.transaction.accounts())?;
+        if self.account.multisig(sender).is_some() {
+          return Err(ChainError::MultisigRequired(sender.clone()));
+        }
+        if outputs.is_empty()
+          || outputs.len() > self.params.max_batch_outputs
+          || outputs.iter().any(|output| output.amount <= 0)
+        {
+          return Err(ChainError::InvalidBatch(id.clone()));
+        }
+        let total = outputs
+          .iter()
+          .try_fold(0i64, |total, output| total.checked_add(output.amount));
+        match (total, self.account.balance(sender)) {
+          (Some(total), Some(balance)) if total <= balance => Ok(()),
+          _ => Err(ChainError::InsufficientBalance(sender.clone())),
+        }
+      }
+      Transaction::IssueAsset {
+        issuer,
+        symbol,
+        decimals,
+        supply,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[issuer])?;
+        let valid_symbol = !symbol.is_empty()
+          && symbol.len() <= MAX_ASSET_SYMBOL_LENGTH
+          && symbol
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        if !valid_symbol || *decimals > MAX_ASSET_DECIMALS || *supply <= 0 {
+          return Err(ChainError::InvalidAsset(symbol.clone()));
+        }
+        if self.account.asset(symbol).is_some() {
+          return Err(ChainError::AssetAlreadyExists(symbol.clone()));
+        }
+        Ok(())
+      }
+      Transaction::AssetTransfer {
+        sender,
+        receiver,
+        symbol,
+        amount,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[sender, receiver])?;
+        if self.account.multisig(sender).is_some() {
+          return Err(ChainError::MultisigRequired(sender.clone()));
+        }
+        if self.account.asset(symbol).is_none() {
+          return Err(ChainError::AssetNotFound(symbol.clone()));
+        }
+        if *amount <= 0 || self.account.asset_balance(sender, symbol) < *amount {
+          return Err(ChainError::InsufficientAssetBalance {
+            account: sender.clone(),
+            symbol: symbol.clone(),
+          });
+        }
+        Ok(())
+      }
+      Transaction::DeployContract {
+        id,
+        deployer,
+        contract,
+        code,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[deployer])?;
+        if code.is_empty()
+          || code.len() > self.params.max_contract_size
+          || *contract != Transaction::contract_account(id)
+        {
+          return Err(ChainError::InvalidContract(id.clone()));
+        }
+        if self.account.balance(contract).is_some() {
+          return Err(ChainError::AccountAlreadyExists(contract.clone()));
+        }
+        Ok(())
+      }
+      Transaction::CallContract {
+        id,
+        caller,
+        contract,
+        gas_limit,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[caller])?;
+        if self.account.contract(contract).is_none() {
+          return Err(ChainError::ContractNotFound(contract.clone()));
+        }
+        if *gas_limit > self.params.max_gas_per_call {
+          return Err(ChainError::InvalidContract(id.clone()));
+        }
+        // Enough to pay for the call even if it uses all its gas.
+        match self.account.balance(caller) {
+          Some(balance) if balance >= self.params.fee(*gas_limit) => Ok(()),
+          _ => Err(ChainError::InsufficientBalance(caller.clone())),
+        }
+      }
+      Transaction::Stake {
+        validator, amount, ..
+      } => {
+        self.ensure_accounts_exist(&[validator])?;
+        self.ensure_signed_by(validator, signed_transaction)?;
+        if self.account.multisig(validator).is_some() {
+          return Err(ChainError::MultisigRequired(validator.clone()));
+        }
+        match self.account.balance(validator) {
+          Some(balance) if *amount > 0 && *amount <= balance => Ok(()),
+          _ => Err(ChainError::InsufficientBalance(validator.clone())),
+        }
+      }
+      Transaction::Unstake {
+        validator, amount, ..
+      } => {
+        self.ensure_accounts_exist(&[validator])?;
+        self.ensure_signed_by(validator, signed_transaction)?;
+        if self.account.is_jailed(validator) {
+          return Err(ChainError::ValidatorJailed(validator.clone()));
+        }
+        if *amount <= 0 || *amount > self.account.stake(validator) {
+          return Err(ChainError::InsufficientStake(validator.clone()));
+        }
+        Ok(())
+      }
+      Transaction::Evidence {
+        id, first, second, ..
+      } => {
+        let equivocation = first.block.forger() == second.block.forger()
+          && first.block_count() == second.block_count()
+          && first.hash() != second.hash()
+          && Wallet::verify_block_forger(first)
+          && Wallet::verify_block_forger(second);
+        if !equivocation || self.account.is_jailed(first.block.forger()) {
+          return Err(ChainError::InvalidEvidence(id.clone()));
+        }
+        Ok(())
+      }
+      Transaction::CreateMultisig {
+        id,
+        account,
+        keys,
+        threshold,
+        ..
+      } => {
+        let distinct_keys = keys.iter().collect::<HashSet<_>>().len();
+
+        if *threshold == 0
+          || *threshold > distinct_keys
+          || *account != Transaction::multisig_account(id, keys, *threshold)
+        {
+          return Err(ChainError::InvalidMultisig(account.clone()));
+        }
+        if self.account.balance(account).is_some() {
+          return Err(ChainError::AccountAlreadyExists(account.clone()));
+        }
+        Ok(())
+      }
+      Transaction::MultisigTransfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      } => {
+        self.ensure_accounts_exist(&[sender, receiver])?;
+        self.verify_signatures(signed_transaction)?;
+        self.ensure_can_pay(sender, *amount)
+      }
+    }
+  }
+
+  /// The amount must be positive and covered by the sender's balance.
+  fn ensure_can_pay(&self, sender: &PublicKey, amount: i64) -> Result<(), ChainError> {
+    match self.account.balance(sender) {
+      Some(balance) if amount > 0 && amount <= balance => Ok(()),
+      _ => Err(ChainError::InsufficientBalance(sender.clone())),
+    }
+  }
+
+  /// Our block at the same height, if it was signed by the same
+  /// forger as `signed_block` but is a different block.
+  pub fn equivocation(&self, signed_block: &SignedBlock) -> Option<&SignedBlock> {
+    self
+      .blocks
+      .get(signed_block.block_count() as usize)
+      .filter(|ours| {
+        ours.block.forger() == signed_block.block.forger() && ours.hash() != signed_block.hash()
+      })
+  }
+
+  /// Snapshots the validator set, it forges the blocks after the tip.
+  fn start_epoch(&mut self) {
+    let epoch = Epoch {
+      number: self.epoch().number + 1,
+      start: self.tip().block_count() + 1,
+      validators: self
+        .account
+        .validators()
+        .map(|(validator, stake)| (validator.clone(), stake))
+        .collect(),
+    };
+
+    info!(
+      number = %epoch.number,
+      validators = epoch.validators.len(),
+      "epoch started"
+    );
+
+    self.epochs.push(epoch);
+  }
+
+  /// The epoch the next block belongs to.
+  pub fn epoch(&self) -> &Epoch {
+    self.epochs.last().unwrap()
+  }
+
+  pub fn epochs(&self) -> &[Epoch] {
+    &self.epochs
+  }
+
+  /// Who must forge a block with `timestamp` on top of the tip, a new
+  /// forger is picked every `forger_timeout` after the tip's timestamp.
+  /// Jailed validators are skipped.
+  ///
+  /// None while the epoch has no validators, anyone may forge then.
+  pub fn next_forger(&self, timestamp: u128) -> Option<&PublicKey> {
+    self
+      .epoch()
+      .forger(&self.tip().hash(), self.round(timestamp), |validator| {
+        !self.account.is_jailed(validator)
+      })
+  }
+
+  /// How many forgers timed out before a block with `timestamp` on top of the tip.
+  fn round(&self, timestamp: u128) -> u128 {
+    timestamp.saturating_sub(self.tip().block.timestamp())
+      / self.params.forger_timeout.as_micros().max(1)
+  }
+
+  /// When the round a block with `timestamp` falls in started, in microseconds.
+  fn round_start(&self, timestamp: u128) -> u128 {
+    self.tip().block.timestamp() + self.round(timestamp) * self.params.forger_timeout.as_micros()
+  }
+
+  pub fn is_checkpoint(&self, block_count: u128) -> bool {
+    block_count > 0 && block_count.is_multiple_of(self.params.checkpoint_interval.max(1))
+  }
+
+  /// The highest checkpoint in the chain, if it's past genesis.
+  pub fn last_checkpoint(&self) -> Option<Checkpoint> {
+    let interval = self.params.checkpoint_interval.max(1);
+
+    let block_count = self.tip().block_count() / interval * interval;
+
+    self.checkpoint(block_count)
+  }
+
+  fn checkpoint(&self, block_count: u128) -> Option<Checkpoint> {
+    if !self.is_checkpoint(block_count) {
+      return None;
+    }
+
+    self
+      .blocks
+      .get(block_count as usize)
+      .map(|signed_block| Checkpoint {
+        block_count,
+        hash: signed_block.hash(),
+      })
+  }
+
+  fn contains_checkpoint(&self, checkpoint: &Checkpoint) -> bool {
+    self
+      .blocks
+      .get(checkpoint.block_count as usize)
+      .map(|signed_block| signed_block.hash() == checkpoint.hash)
+      .unwrap_or(false)
+  }
+
+  pub fn finality(&self) -> &Finality {
+    &self.finality
+  }
+
+  /// Puts back votes and the final checkpoint saved before a restart.
+  pub fn restore_finality(&mut self, finality: Finality) {
+    self.finality = finality;
+
+    for checkpoint in self.finality.checkpoints() {
+      self.try_finalize(&checkpoint);
+    }
+  }
+
+  /// Counts a validator's vote for a checkpoint, the checkpoint becomes
+  /// final once validators with 2/3 of the stake voted for it.
+  ///
+  /// Votes for checkpoints we don't have yet, because we're behind or on
+  /// another fork, are kept until the block arrives. Returns false if the
+  /// vote was already counted or is too far ahead of our tip to keep.
+  pub fn add_vote(&mut self, signed_vote: &SignedVote) -> Result<bool, ChainError> {
+    let vote = &signed_vote.vote;
+
+    let block_count = vote.checkpoint.block_count;
+
+    if !Wallet::verify_vote(signed_vote) || !self.is_checkpoint(block_count) {
+      return Err(ChainError::InvalidVote(signed_vote.clone()));
+    }
+
+    let is_validator = self.voting_stake(block_count).contains_key(&vote.validator);
+
+    let ahead = block_count > self.tip().block_count();
+
+    // Past our tip the validator may have joined in an epoch we haven't seen yet.
+    if !is_validator && !ahead {
+      return Err(ChainError::InvalidVote(signed_vote.clone()));
     }
+
+    if !is_validator
+      || block_count <= self.finality.finalized_height()
+      || block_count > self.tip().block_count() + self.params.epoch_length.max(1)
+    {
+      return Ok(false);
+    }
+
+    if !self.finality.add(vote) {
+      return Ok(false);
+    }
+
+    self.try_finalize(&vote.checkpoint);
+
+    Ok(true)
+  }
+
+  /// Stake each validator votes with on checkpoint `block_count`, the
+  /// snapshot of its epoch. Epochs without validators, e.g. before the
+  /// first snapshot with stake, use the current stakes instead.
+  pub fn voting_stake(&self, block_count: u128) -> BTreeMap<PublicKey, i64> {
+    let epoch = self
+      .epochs
+      .iter()
+      .rev()
+      .find(|epoch| epoch.start <= block_count)
+      .unwrap_or_else(|| self.epoch());
+
+    let stakes: BTreeMap<PublicKey, i64> = if epoch.validators.is_empty() {
+      self
+        .account
+        .validators()
+        .map(|(validator, stake)| (validator.clone(), stake))
+        .collect()
+    } else {
+      epoch.validators.clone()
+    };
+
+    stakes
+      .into_iter()
+      .filter(|(validator, stake)| *stake > 0 && !self.account.is_jailed(validator))
+      .collect()
+  }
+
+  fn try_finalize(&mut self, checkpoint: &Checkpoint) {
+    if checkpoint.block_count <= self.finality.finalized_height()
+      || !self.contains_checkpoint(checkpoint)
+    {
+      return;
+    }
+
+    let stakes = self.voting_stake(checkpoint.block_count);
+
+    let voted: i64 = self
+      .finality
+      .voters(checkpoint)
+      .filter_map(|validator| stakes.get(validator))
+      .sum();
+
+    let total: i64 = stakes.values().sum();
+
+    if total > 0 && 3 * voted >= 2 * total {
+      info!(block_count = %checkpoint.block_count, "checkpoint finalized");
+      self.finality.finalize(checkpoint.clone());
+    }
+  }
+
+  /// What happened when a transaction that made it into the chain was executed.
+  pub fn receipt(&self, id: &str) -> Option<&Receipt> {
+    let location = self.index.location(id)?;
+
+    self.receipts[location.block_count as usize].get(location.position)
+  }
+
+  pub fn check_memo(&self, transaction: &Transaction) -> Result<(), ChainError> {
+    match transaction.memo() {
+      Some(memo) if memo.len() > self.params.max_memo_size => {
+        Err(ChainError::MemoTooLarge(transaction.id().clone()))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Whether a transfer locked until `unlock` could be included in the next block.
+  pub fn is_unlocked(&self, unlock: &Lock) -> bool {
+    let tip = self.tip();
+
+    match unlock {
+      Lock::Height(block_count) => tip.block_count() + 1 >= *block_count,
+      Lock::Time(timestamp) => tip.block.timestamp() >= *timestamp,
+    }
+  }
+
+  /// Checks that transfers out of a multisig account were cosigned
+  /// by at least `threshold` of the account's keys.
+  ///
+  /// Other transactions are always accepted.
+  pub fn verify_signatures(
+    &self,
+    signed_transaction: &SignedTransaction,
+  ) -> Result<(), ChainError> {
+    let sender = match &signed_transaction.transaction {
+      Transaction::MultisigTransfer { sender, .. } => sender,
+      _ => return Ok(()),
+    };
+
+    let Multisig { keys, threshold } = self
+      .account
+      .multisig(sender)
+      .ok_or_else(|| ChainError::NotMultisig(sender.clone()))?;
+
+    let valid = signed_transaction
+      .cosignatures
+      .iter()
+      .filter(|cosignature| keys.contains(&cosignature.public_key))
+      .filter(|cosignature| {
+        Wallet::verify_cosignature(cosignature, &signed_transaction.transaction)
+      })
+      .map(|cosignature| &cosignature.public_key)
+      .collect::<HashSet<_>>()
+      .len();
+
+    if valid < *threshold {
+      return Err(ChainError::NotEnoughSignatures {
+        account: sender.clone(),
+        required: *threshold,
+        valid,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Checks that `account` signed the transaction, blocks were already
+  /// checked when they arrived but `execute` can be called with anything.
+  fn ensure_signed_by(
+    &self,
+    account: &PublicKey,
+    signed_transaction: &SignedTransaction,
+  ) -> Result<(), ChainError> {
+    if Wallet::verify_transaction(account, signed_transaction) {
+      Ok(())
+    } else {
+      Err(ChainError::InvalidTransactionSignature(
+        signed_transaction.transaction.id().clone(),
+      ))
+    }
+  }
+
+  fn ensure_accounts_exist(&self, accounts: &[&PublicKey]) -> Result<(), ChainError> {
+    match accounts
+      .iter()
+      .find(|account| self.account.balance(account).is_none())
+    {
+      None => Ok(()),
+      Some(account) => Err(ChainError::AccountNotFound((*account).clone())),
+    }
+  }
+
+  pub fn execute(&mut self, signed_transaction: &SignedTransaction) -> Result<(), ChainError> {
+    self.run(signed_transaction).map(|_| ())
+  }
+
+  /// Executes the transaction, returning what the contract call left behind if it was one.
+  fn run(
+    &mut self,
+    signed_transaction: &SignedTransaction,
+  ) -> Result<Option<ContractReceipt>, ChainError> {
+    self.can_execute(signed_transaction)?;
+
+    match &signed_transaction.transaction {
+      Transaction::Transfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      }
+      | Transaction::LockedTransfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      }
+      | Transaction::MultisigTransfer {
+        sender,
+        receiver,
+        amount,
+        ..
+      } => {
+        // TODO: not atomic, is this a problem?
+        self
+          .account
+          .update_balance(sender, -amount)
+          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))?;
+        self
+          .account
+          .update_balance(receiver, *amount)
+          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))?;
+        self.account.increment_nonce(sender);
+        Ok(None)
+      }
+      Transaction::BatchTransfer {
+        sender, outputs, ..
+      } => {
+        // Every account was checked to exist, so none of these can fail halfway.
+        for output in outputs {
+          self
+            .account
+            .update_balance(sender, -output.amount)
+            .map_err(|AccountError::AccountNotFound(account)| {
+              ChainError::AccountNotFound(account)
+            })?;
+          self
+            .account
+            .update_balance(&output.receiver, output.amount)
+            .map_err(|AccountError::AccountNotFound(account)| {
+              ChainError::AccountNotFound(account)
+            })?;
+        }
+        self.account.increment_nonce(sender);
+        Ok(None)
+      }
+      Transaction::IssueAsset {
+        issuer,
+        symbol,
+        decimals,
+        supply,
+        ..
+      } => {
+        self.account.issue_asset(Asset {
+          symbol: symbol.clone(),
+          decimals: *decimals,
+          issuer: issuer.clone(),
+          supply: *supply,
+        });
+        Ok(None)
+      }
+      Transaction::AssetTransfer {
+        sender,
+        receiver,
+        symbol,
+        amount,
+        ..
+      } => {
+        self.account.update_asset_balance(sender, symbol, -amount);
+        self.account.update_asset_balance(receiver, symbol, *amount);
+        self.account.increment_nonce(sender);
+        Ok(None)
+      }
+      Transaction::DeployContract { contract, code, .. } => {
+        self.account.add_contract(
+          contract.clone(),
+          Contract {
+            code: code.clone(),
+            storage: vm::Storage::new(),
+          },
+        );
+        Ok(None)
+      }
+      Transaction::CallContract {
+        id,
+        caller,
+        contract,
+        args,
+        gas_limit,
+        ..
+      } => {
+        let Contract { code, storage } = self
+          .account
+          .contract(contract)
+          .ok_or_else(|| ChainError::ContractNotFound(contract.clone()))?;
+        let result = vm::execute(code, args, storage, *gas_limit);
+        let gas_used = match &result {
+          Ok((_, receipt)) => receipt.gas_used,
+          Err(failure) => failure.gas_used,
+        };
+        // Fees are burned, failed calls pay for the gas they used too.
+        let fee = self.params.fee(gas_used);
+        self
+          .account
+          .update_balance(caller, -fee)
+          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))?;
+        self.supply -= fee;
+        let (storage, receipt) =
+          result.map_err(|VmFailure { error, gas_used }| ChainError::ContractFailed {
+            id: id.clone(),
+            error,
+            gas_used,
+          })?;
+        self.account.set_contract_storage(contract, storage);
+        self.account.increment_nonce(caller);
+        Ok(Some(receipt))
+      }
+      Transaction::Stake {
+        validator, amount, ..
+      } => {
+        self
+          .account
+          .update_balance(validator, -amount)
+          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))?;
+        self.account.update_stake(validator, *amount);
+        self.account.increment_nonce(validator);
+        Ok(None)
+      }
+      Transaction::Unstake {
+        validator, amount, ..
+      } => {
+        self.account.update_stake(validator, -amount);
+        // Released an epoch later so evidence of double signing can still slash it.
+        let release = self.tip().block_count() + 1 + self.params.epoch_length.max(1);
+        self.account.unbond(
+          validator.clone(),
+          Unbond {
+            amount: *amount,
+            release,
+          },
+        );
+        self.account.increment_nonce(validator);
+        Ok(None)
+      }
+      Transaction::Evidence { first, .. } => {
+        let forger = first.block.forger();
+        let percent = self.params.slash_percent.clamp(0, 100);
+        let staked = self.account.stake(forger) * percent / 100;
+        self.account.update_stake(forger, -staked);
+        let slashed = staked + self.account.slash_unbonding(forger, percent);
+        // Slashed coins are burned.
+        self.supply -= slashed;
+        self.account.jail(forger.clone());
+        info!(%forger, slashed, "forger slashed for double signing");
+        Ok(None)
+      }
+      Transaction::CreateMultisig {
+        account,
+        keys,
+        threshold,
+        ..
+      } => {
+        self.account.add_multisig(
+          account.clone(),
+          Multisig {
+            keys: keys.clone(),
+            threshold: *threshold,
+          },
+        );
+        Ok(None)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::Block;
+  use crate::clock::ManualClock;
+  use crate::transaction::Output;
+  use crate::wallet::Wallet;
+  use std::time::Duration;
+
+  #[test]
+  fn chain_starts_with_the_genesis_block() {
+    let chain = Chain::new();
+
+    assert_eq!(chain.blocks, vec![SignedBlock::genesis()]);
+  }
+
+  #[test]
+  fn does_not_add_block_to_the_chain_if_last_block_hash_does_not_match() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      String::from("not_the_last_block_hash"),
+      String::from("forger_public_key"),
+      0,
+    ));
+
+    let expected = Err(ChainError::InvalidBlockHash(block.clone()));
+
+    let actual = chain.add(block);
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn does_not_add_block_to_the_chain_if_block_count_does_not_match() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      chain.blocks.last().unwrap().hash(),
+      String::from("forger_public_key"),
+      10,
+    ));
+
+    let expected = Err(ChainError::InvalidBlockCount(block.clone()));
+
+    let actual = chain.add(block);
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn after_adding_block_to_the_chain_returns_transactions_that_failed() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let block = wallet.sign_block(Block::new(
+      vec![transaction],
+      chain.blocks.last().unwrap().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    let expected = Err(ChainError::TransactionsFailed(vec![
+      ChainError::AccountNotFound(sender),
+    ]));
+
+    let actual = chain.add(block);
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn block_is_added_to_the_chain_even_if_one_of_its_transactions_fail() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let block = wallet.sign_block(Block::new(
+      vec![transaction],
+      chain.blocks.last().unwrap().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    assert_eq!(
+      Err(ChainError::TransactionsFailed(vec![
+        ChainError::AccountNotFound(sender)
+      ])),
+      chain.add(block.clone())
+    );
+
+    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), block]);
+  }
+
+  #[test]
+  fn adds_blocks_to_the_chain() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      chain.blocks.last().unwrap().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    assert_eq!(Ok(()), chain.add(block.clone()));
+
+    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), block]);
+  }
+
+  #[test]
+  fn executes_block_transactions_before_adding_it_to_the_chain() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    chain.account.add_account(sender.clone());
+
+    chain.account.update_balance(&sender, 10).unwrap();
+
+    let receiver = String::from("receiver_public_key");
+
+    chain.account.add_account(receiver.clone());
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let block = wallet.sign_block(Block::new(
+      vec![transaction],
+      chain.blocks.last().unwrap().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    chain.add(block).unwrap();
+
+    assert_eq!(chain.account.balance(&sender), Some(0));
+    assert_eq!(chain.account.balance(&receiver), Some(10));
+  }
+
+  #[test]
+  fn chain_starts_with_the_allocated_balances() {
+    let public_key = String::from("public_key");
+
+    let chain = Chain::with_allocations(vec![(public_key.clone(), 100)]);
+
+    assert_eq!(chain.account.balance(&public_key), Some(100));
+  }
+
+  #[test]
+  fn prefers_longer_chains() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    assert!(chain.prefers(&block));
+
+    chain.add(block.clone()).unwrap();
+
+    assert!(!chain.prefers(&SignedBlock::genesis()));
+    assert!(!chain.prefers(&block));
+  }
+
+  #[test]
+  fn reorganizes_to_a_preferred_fork_and_returns_the_orphaned_blocks() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let orphan = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+      1,
+    ));
+
+    chain.add(orphan.clone()).unwrap();
+
+    let fork_a = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      SignedBlock::genesis().hash(),
+      String::from("forger_public_key"),
+      1,
+      2,
+    ));
+
+    let fork_b = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      fork_a.hash(),
+      String::from("forger_public_key"),
+      2,
+      3,
+    ));
+
+    assert_eq!(Some(&orphan), chain.block_by_hash(&orphan.hash()));
+
+    let actual = chain.reorganize(vec![fork_a.clone(), fork_b.clone()]);
+
+    assert_eq!(Ok(vec![orphan.clone()]), actual);
+
+    assert_eq!(None, chain.block_by_hash(&orphan.hash()));
+    assert_eq!(Some(&fork_b), chain.block_by_hash(&fork_b.hash()));
+    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), fork_a, fork_b]);
+  }
+
+  #[test]
+  fn reorganizes_over_a_shared_prefix_with_failed_transactions() {
+    let mut chain = Chain::new();
+
+    let shared = block_with(&chain, vec![transfer(10)], 1);
+
+    assert!(matches!(
+      chain.add(shared.clone()),
+      Err(ChainError::TransactionsFailed(_))
+    ));
+
+    let mut fork = chain_with(chain.params().clone());
+
+    let _ = fork.add(shared);
+
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
+
+    fork.add(block_with(&fork, Vec::new(), 3)).unwrap();
+    fork.add(block_with(&fork, Vec::new(), 4)).unwrap();
+
+    assert_eq!(
+      1,
+      chain.reorganize(fork.blocks()[1..].to_vec()).unwrap().len()
+    );
+    assert_eq!(fork.tip(), chain.tip());
+  }
+
+  #[test]
+  fn does_not_reorganize_to_a_fork_that_is_not_preferred() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    chain.add(block.clone()).unwrap();
+
+    let expected = Err(ChainError::ForkNotPreferred(SignedBlock::genesis()));
+
+    let actual = chain.reorganize(Vec::new());
+
+    assert_eq!(expected, actual);
+
+    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), block]);
+  }
+
+  #[test]
+  fn reorganizing_replays_the_transactions_of_the_fork() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let allocations = vec![(sender.clone(), 10), (receiver.clone(), 0)];
+
+    let mut chain = Chain::with_allocations(allocations);
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let block = wallet.sign_block(Block::new(
+      vec![transaction],
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    chain.reorganize(vec![block]).unwrap();
+
+    assert_eq!(chain.account.balance(&sender), Some(0));
+    assert_eq!(chain.account.balance(&receiver), Some(10));
+  }
+
+  #[test]
+  fn adding_a_block_produces_events() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let block = wallet.sign_block(Block::new(
+      vec![transaction.clone()],
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    chain.add(block.clone()).unwrap();
+
+    let expected = vec![
+      Event::TransactionStatus {
+        id: transaction.transaction.id().clone(),
+        status: TransactionStatus::Included { block_count: 1 },
+      },
+      Event::Balance {
+        account: sender,
+        balance: 0,
+      },
+      Event::Balance {
+        account: receiver,
+        balance: 10,
+      },
+      Event::Balance {
+        account: String::from("forger_public_key"),
+        balance: 50,
+      },
+      Event::Block {
+        block_count: 1,
+        hash: block.hash(),
+        transactions: 1,
+      },
+    ];
+
+    assert_eq!(expected, chain.events());
+    assert_eq!(Vec::<Event>::new(), chain.events());
+  }
+
+  #[test]
+  fn reorganizing_reports_reverted_balances_and_only_the_new_blocks() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    let shared = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+      1,
+    ));
+
+    chain.add(shared.clone()).unwrap();
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    let orphan = wallet.sign_block(Block::with_timestamp(
+      vec![transaction],
+      shared.hash(),
+      String::from("forger_public_key"),
+      2,
+      2,
+    ));
+
+    chain.add(orphan).unwrap();
+
+    chain.events();
+
+    let fork_a = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      shared.hash(),
+      String::from("forger_public_key"),
+      2,
+      3,
+    ));
+
+    let fork_b = wallet.sign_block(Block::with_timestamp(
+      Vec::new(),
+      fork_a.hash(),
+      String::from("forger_public_key"),
+      3,
+      4,
+    ));
+
+    chain
+      .reorganize(vec![shared, fork_a.clone(), fork_b.clone()])
+      .unwrap();
+
+    let forger = String::from("forger_public_key");
+
+    let expected = vec![
+      Event::Balance {
+        account: forger.clone(),
+        balance: 100,
+      },
+      Event::Block {
+        block_count: 2,
+        hash: fork_a.hash(),
+        transactions: 0,
+      },
+      Event::Balance {
+        account: forger.clone(),
+        balance: 150,
+      },
+      Event::Block {
+        block_count: 3,
+        hash: fork_b.hash(),
+        transactions: 0,
+      },
+      Event::Balance {
+        account: sender,
+        balance: 10,
+      },
+      Event::Balance {
+        account: forger,
+        balance: 150,
+      },
+      Event::Balance {
+        account: receiver,
+        balance: 0,
+      },
+    ];
+
+    assert_eq!(expected, chain.events());
+  }
+
+  #[test]
+  fn executes_transfer_transaction() {
+    let mut chain = Chain::new();
+
+    let sender = String::from("sender_public_key");
+
+    chain.account.add_account(sender.clone());
+
+    chain.account.update_balance(&sender, 10).unwrap();
+
+    let receiver = String::from("receiver_public_key");
+
+    chain.account.add_account(receiver.clone());
+
+    let wallet = Wallet::new();
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    chain.execute(&transaction).unwrap();
+
+    assert_eq!(chain.account.balance(&sender), Some(0));
+    assert_eq!(chain.account.balance(&receiver), Some(10));
+    assert_eq!(chain.account.nonce(&sender), 1);
+  }
+
+  #[test]
+  fn finds_blocks_by_height_and_hash() {
+    let mut chain = Chain::new();
+
+    let wallet = Wallet::new();
+
+    let block = wallet.sign_block(Block::new(
+      Vec::new(),
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      1,
+    ));
+
+    chain.add(block.clone()).unwrap();
+
+    assert_eq!(Some(&block), chain.block_by_height(1));
+    assert_eq!(Some(&block), chain.block_by_hash(&block.hash()));
+    assert_eq!(None, chain.block_by_height(2));
+    assert_eq!(None, chain.block_by_hash("unknown_hash"));
+  }
+
+  #[test]
+  fn rebuilds_the_chain_from_its_blocks() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let allocations = vec![(sender.clone(), 10), (receiver.clone(), 0)];
+
+    let mut chain = Chain::with_allocations(allocations.clone());
+
+    let transaction = wallet.sign_transaction(Transaction::transfer(sender, receiver, 10));
+
+    chain
+      .add(wallet.sign_block(Block::with_timestamp(
+        vec![transaction],
+        chain.tip().hash(),
+        String::from("forger_public_key"),
+        1,
+        1,
+      )))
+      .unwrap();
+
+    let mut rebuilt = Chain::with_allocations(allocations);
+
+    rebuilt.restore(chain.blocks()[1..].to_vec()).unwrap();
+
+    assert_eq!(chain.tip(), rebuilt.tip());
+    assert_eq!(chain.account(), rebuilt.account());
+    assert_eq!(Vec::<Event>::new(), rebuilt.events());
+  }
+
+  fn block_with(
+    chain: &Chain,
+    transactions: Vec<SignedTransaction>,
+    timestamp: u128,
+  ) -> SignedBlock {
+    Wallet::new().sign_block(Block::with_timestamp(
+      transactions,
+      chain.tip().hash(),
+      String::from("forger_public_key"),
+      chain.tip().block_count() + 1,
+      timestamp,
+    ))
+  }
+
+  fn transfer(amount: i64) -> SignedTransaction {
+    let wallet = Wallet::new();
+
+    wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      amount,
+    ))
+  }
+
+  fn chain_with(params: ConsensusParams) -> Chain {
+    Chain::with_params(Vec::new(), params, Arc::new(SystemClock))
+  }
+
+  #[test]
+  fn rejects_blocks_with_too_many_transactions() {
+    let mut chain = chain_with(ConsensusParams {
+      max_transactions: 1,
+      ..ConsensusParams::default()
+    });
+
+    let block = block_with(&chain, vec![transfer(1), transfer(2)], 1);
+
+    assert_eq!(
+      Err(ChainError::TooManyTransactions(block.clone())),
+      chain.add(block)
+    );
+  }
+
+  #[test]
+  fn rejects_blocks_that_are_too_large() {
+    let mut chain = chain_with(ConsensusParams {
+      max_block_size: 100,
+      ..ConsensusParams::default()
+    });
+
+    let block = block_with(&chain, Vec::new(), 1);
+
+    assert_eq!(
+      Err(ChainError::BlockTooLarge(block.clone())),
+      chain.add(block)
+    );
+  }
+
+  #[test]
+  fn rejects_blocks_older_than_their_parent() {
+    let mut chain = Chain::new();
+
+    chain.add(block_with(&chain, Vec::new(), 10)).unwrap();
+
+    let block = block_with(&chain, Vec::new(), 9);
+
+    assert_eq!(
+      Err(ChainError::TimestampBeforeParent(block.clone())),
+      chain.add(block)
+    );
+  }
+
+  #[test]
+  fn rejects_blocks_too_far_in_the_future() {
+    let clock = ManualClock::new();
+
+    let params = ConsensusParams::default();
+
+    let drift = params.max_timestamp_drift.as_micros();
+
+    let mut chain = Chain::with_params(Vec::new(), params, Arc::new(clock));
+
+    let block = block_with(&chain, Vec::new(), drift + 1);
+
+    assert_eq!(
+      Err(ChainError::TimestampInTheFuture(block.clone())),
+      chain.add(block)
+    );
+
+    chain.add(block_with(&chain, Vec::new(), drift)).unwrap();
+  }
+
+  #[test]
+  fn rejects_blocks_with_duplicate_transactions() {
+    let mut chain = Chain::new();
+
+    let transaction = transfer(1);
+
+    let block = block_with(&chain, vec![transaction.clone(), transaction.clone()], 1);
+
+    assert_eq!(
+      Err(ChainError::DuplicateTransaction(
+        transaction.transaction.id().clone()
+      )),
+      chain.add(block)
+    );
+  }
+
+  #[test]
+  fn rejects_blocks_with_transactions_the_sender_did_not_sign() {
+    let (victim, forger) = (Wallet::new(), Wallet::new());
+
+    let mut chain = Chain::with_allocations(vec![(victim.public_key(), 100)]);
+
+    let mut theft = forger.sign_transaction(Transaction::transfer(
+      victim.public_key(),
+      forger.public_key(),
+      100,
+    ));
+
+    let block = block_with(&chain, vec![theft.clone()], 1);
+
+    assert_eq!(
+      Err(ChainError::InvalidTransactionSignature(
+        theft.transaction.id().clone()
+      )),
+      chain.add(block)
+    );
+
+    theft.signature = String::from("00");
+
+    assert_eq!(
+      Err(ChainError::InvalidTransactionSignature(
+        theft.transaction.id().clone()
+      )),
+      chain.add(block_with(&chain, vec![theft], 1))
+    );
+
+    let mut cosigned = transfer(1);
+
+    forger.cosign(&mut cosigned);
+
+    cosigned.cosignatures[0].public_key = victim.public_key();
+
+    assert_eq!(
+      Err(ChainError::InvalidTransactionSignature(
+        cosigned.transaction.id().clone()
+      )),
+      chain.add(block_with(&chain, vec![cosigned], 1))
+    );
+    assert_eq!(Some(100), chain.account().balance(&victim.public_key()));
+    assert_eq!(0, chain.tip().block_count());
+  }
+
+  #[test]
+  fn rejects_transactions_that_were_already_included() {
+    let mut chain = Chain::new();
+
+    let transaction = transfer(1);
+
+    let _ = chain.add(block_with(&chain, vec![transaction.clone()], 1));
+
+    let block = block_with(&chain, vec![transaction.clone()], 2);
+
+    assert_eq!(
+      Err(ChainError::DuplicateTransaction(
+        transaction.transaction.id().clone()
+      )),
+      chain.add(block)
+    );
+  }
+
+  #[test]
+  fn credits_the_forger_with_the_block_reward() {
+    let mut chain = Chain::with_allocations(vec![(String::from("public_key"), 100)]);
+
+    let forger = String::from("forger_public_key");
+
+    let reward = chain.next_block_reward();
+
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+
+    assert_eq!(50, reward);
+    assert_eq!(Some(reward), chain.account().balance(&forger));
+    assert_eq!(100 + reward, chain.supply());
+  }
+
+  #[test]
+  fn stops_paying_rewards_at_the_max_supply() {
+    let mut chain = Chain::with_params(
+      vec![(String::from("public_key"), 90)],
+      ConsensusParams {
+        max_supply: 100,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
+
+    assert_eq!(100, chain.supply());
+    assert_eq!(
+      Some(10),
+      chain.account().balance(&String::from("forger_public_key"))
+    );
+  }
+
+  #[test]
+  fn finds_transactions_by_id_and_account() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    let transaction =
+      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+
+    chain
+      .add(block_with(&chain, vec![transaction.clone()], 1))
+      .unwrap();
+
+    let location = Location {
+      block_count: 1,
+      position: 0,
+    };
+
+    assert_eq!(
+      Some((location, &transaction)),
+      chain.transaction(transaction.transaction.id())
+    );
+    assert!(chain.contains_transaction(&transaction));
+    assert_eq!(
+      vec![(location, &transaction)],
+      chain.account_transactions(&sender).collect::<Vec<_>>()
+    );
+    assert_eq!(1, chain.account_transactions(&receiver).len());
+  }
+
+  #[test]
+  fn transactions_from_orphaned_blocks_are_removed_from_the_index() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let allocations = vec![(sender.clone(), 10), (receiver.clone(), 0)];
+
+    let mut chain = Chain::with_allocations(allocations.clone());
+
+    let transaction = wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver, 10));
+
+    chain
+      .add(block_with(&chain, vec![transaction.clone()], 1))
+      .unwrap();
+
+    let mut fork = Chain::with_allocations(allocations);
+
+    fork.add(block_with(&fork, Vec::new(), 2)).unwrap();
+    fork.add(block_with(&fork, Vec::new(), 3)).unwrap();
+
+    chain.reorganize(fork.blocks()[1..].to_vec()).unwrap();
+
+    assert_eq!(None, chain.transaction(transaction.transaction.id()));
+    assert_eq!(0, chain.account_transactions(&sender).len());
+  }
+
+  fn multisig(keys: &[&Wallet], threshold: usize) -> (Chain, PublicKey) {
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(receiver, 0)]);
+
+    let create = Wallet::new().sign_transaction(Transaction::create_multisig(
+      keys.iter().map(|wallet| wallet.public_key()).collect(),
+      threshold,
+    ));
+
+    let account = create.transaction.accounts()[0].clone();
+
+    chain.execute(&create).unwrap();
+    chain.account.update_balance(&account, 10).unwrap();
+
+    (chain, account)
+  }
+
+  #[test]
+  fn multisig_transfers_need_threshold_cosignatures() {
+    let (alice, bob, carol) = (Wallet::new(), Wallet::new(), Wallet::new());
+
+    let (mut chain, account) = multisig(&[&alice, &bob, &carol], 2);
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut transfer = alice.sign_transaction(Transaction::multisig_transfer(
+      account.clone(),
+      receiver.clone(),
+      4,
+    ));
+
+    alice.cosign(&mut transfer);
+    alice.cosign(&mut transfer);
+    Wallet::new().cosign(&mut transfer);
+
+    assert_eq!(
+      Err(ChainError::NotEnoughSignatures {
+        account: account.clone(),
+        required: 2,
+        valid: 1,
+      }),
+      chain.execute(&transfer)
+    );
+
+    carol.cosign(&mut transfer);
+
+    assert_eq!(Ok(()), chain.execute(&transfer));
+    assert_eq!(Some(6), chain.account().balance(&account));
+    assert_eq!(Some(4), chain.account().balance(&receiver));
+  }
+
+  #[test]
+  fn transfers_must_be_positive_and_covered_by_the_balance() {
+    let sender = String::from("sender_public_key");
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    for amount in [0, -5, 11] {
+      let transfer = Transaction::transfer(sender.clone(), receiver.clone(), amount);
+
+      assert_eq!(
+        Err(ChainError::InsufficientBalance(sender.clone())),
+        chain.execute(&Wallet::new().sign_transaction(transfer))
+      );
+    }
+
+    chain
+      .execute(&Wallet::new().sign_transaction(Transaction::transfer(
+        sender.clone(),
+        receiver.clone(),
+        10,
+      )))
+      .unwrap();
+
+    assert_eq!(Some(0), chain.account().balance(&sender));
+  }
+
+  #[test]
+  fn locked_transfers_must_be_positive_and_covered_by_the_balance() {
+    let sender = String::from("sender_public_key");
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    for amount in [0, -5, 11] {
+      let transfer =
+        Transaction::locked_transfer(sender.clone(), receiver.clone(), amount, Lock::Height(0));
+
+      assert_eq!(
+        Err(ChainError::InsufficientBalance(sender.clone())),
+        chain.execute(&Wallet::new().sign_transaction(transfer))
+      );
+    }
+  }
+
+  #[test]
+  fn multisig_transfers_must_be_positive_and_covered_by_the_balance() {
+    let wallet = Wallet::new();
+
+    let (mut chain, account) = multisig(&[&wallet], 1);
+
+    for amount in [0, -5, 11] {
+      let mut transfer = wallet.sign_transaction(Transaction::multisig_transfer(
+        account.clone(),
+        String::from("receiver_public_key"),
+        amount,
+      ));
+
+      wallet.cosign(&mut transfer);
+
+      assert_eq!(
+        Err(ChainError::InsufficientBalance(account.clone())),
+        chain.execute(&transfer)
+      );
+    }
+  }
+
+  #[test]
+  fn multisig_accounts_cant_send_plain_transfers() {
+    let wallet = Wallet::new();
+
+    let (mut chain, account) = multisig(&[&wallet], 1);
+
+    let transfer = wallet.sign_transaction(Transaction::transfer(
+      account.clone(),
+      String::from("receiver_public_key"),
+      1,
+    ));
+
+    assert_eq!(
+      Err(ChainError::MultisigRequired(account)),
+      chain.execute(&transfer)
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_multisig_accounts() {
+    let wallet = Wallet::new();
+
+    let mut chain = Chain::new();
+
+    for threshold in [0, 2] {
+      let create = wallet.sign_transaction(Transaction::create_multisig(
+        vec![wallet.public_key(), wallet.public_key()],
+        threshold,
+      ));
+
+      let account = create.transaction.accounts()[0].clone();
+
+      assert_eq!(
+        Err(ChainError::InvalidMultisig(account)),
+        chain.execute(&create)
+      );
+    }
+
+    let mut create =
+      wallet.sign_transaction(Transaction::create_multisig(vec![wallet.public_key()], 1));
+
+    if let Transaction::CreateMultisig { account, .. } = &mut create.transaction {
+      *account = String::from("someone_elses_account");
+    }
+
+    assert_eq!(
+      Err(ChainError::InvalidMultisig(String::from(
+        "someone_elses_account"
+      ))),
+      chain.execute(&create)
+    );
+  }
+
+  #[test]
+  fn locked_transfers_cant_be_included_before_they_unlock() {
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
+
+    let unlock = Lock::Time(chain.tip().block.timestamp() + 5);
+
+    let locked_transfer = || {
+      wallet.sign_transaction(Transaction::locked_transfer(
+        sender.clone(),
+        receiver.clone(),
+        10,
+        unlock,
+      ))
+    };
+
+    let transaction = locked_transfer();
+
+    assert_eq!(
+      Err(ChainError::TransactionsFailed(vec![
+        ChainError::TransactionLocked {
+          id: transaction.transaction.id().clone(),
+          unlock,
+        }
+      ])),
+      chain.add(block_with(&chain, vec![transaction.clone()], 5))
+    );
+    assert_eq!(Some(0), chain.account().balance(&receiver));
+
+    chain
+      .add(block_with(&chain, vec![locked_transfer()], 6))
+      .unwrap();
+
+    assert_eq!(Some(10), chain.account().balance(&receiver));
+  }
+
+  #[test]
+  fn height_locks_count_the_block_being_added() {
+    let chain = Chain::new();
+
+    let next = chain.tip().block_count() + 1;
+
+    assert!(chain.is_unlocked(&Lock::Height(next)));
+    assert!(!chain.is_unlocked(&Lock::Height(next + 1)));
+  }
+
+  #[test]
+  fn issued_assets_can_be_transferred_up_to_the_senders_balance() {
+    let issuer = String::from("issuer_public_key");
+
+    let receiver = String::from("receiver_public_key");
+
+    let mut chain = Chain::with_allocations(vec![(issuer.clone(), 0), (receiver.clone(), 0)]);
+
+    let wallet = Wallet::new();
+
+    let gold = String::from("GOLD");
+
+    chain
+      .execute(&wallet.sign_transaction(Transaction::issue_asset(
+        issuer.clone(),
+        gold.clone(),
+        2,
+        100,
+      )))
+      .unwrap();
+
+    assert_eq!(
+      Err(ChainError::AssetAlreadyExists(gold.clone())),
+      chain.execute(&wallet.sign_transaction(Transaction::issue_asset(
+        receiver.clone(),
+        gold.clone(),
+        2,
+        100,
+      )))
+    );
+
+    chain
+      .execute(&wallet.sign_transaction(Transaction::asset_transfer(
+        issuer.clone(),
+        receiver.clone(),
+        gold.clone(),
+        60,
+      )))
+      .unwrap();
+
+    assert_eq!(
+      Err(ChainError::InsufficientAssetBalance {
+        account: issuer.clone(),
+        symbol: gold.clone(),
+      }),
+      chain.execute(&wallet.sign_transaction(Transaction::asset_transfer(
+        issuer.clone(),
+        receiver.clone(),
+        gold.clone(),
+        41,
+      )))
+    );
+    assert_eq!(40, chain.account().asset_balance(&issuer, &gold));
+    assert_eq!(60, chain.account().asset_balance(&receiver, &gold));
+    assert_eq!(Some(0), chain.account().balance(&receiver));
+  }
+
+  #[test]
+  fn rejects_invalid_assets() {
+    let issuer = String::from("issuer_public_key");
+
+    let chain = Chain::with_allocations(vec![(issuer.clone(), 0)]);
+
+    let wallet = Wallet::new();
+
+    for (symbol, decimals, supply) in [
+      ("gold", 2, 100),
+      ("", 2, 100),
+      ("GOLD", 19, 100),
+      ("GOLD", 2, 0),
+    ] {
+      assert_eq!(
+        Err(ChainError::InvalidAsset(String::from(symbol))),
+        chain.can_execute(&wallet.sign_transaction(Transaction::issue_asset(
+          issuer.clone(),
+          String::from(symbol),
+          decimals,
+          supply,
+        )))
+      );
+    }
+
+    assert_eq!(
+      Err(ChainError::AssetNotFound(String::from("GOLD"))),
+      chain.can_execute(&wallet.sign_transaction(Transaction::asset_transfer(
+        issuer.clone(),
+        issuer,
+        String::from("GOLD"),
+        1,
+      )))
+    );
+  }
+
+  #[test]
+  fn batch_transfers_pay_every_output_or_none() {
+    let sender = String::from("sender_public_key");
+
+    let (alice, bob) = (String::from("alice"), String::from("bob"));
+
+    let mut chain = Chain::with_allocations(vec![
+      (sender.clone(), 10),
+      (alice.clone(), 0),
+      (bob.clone(), 0),
+    ]);
+
+    let batch = |amounts: [i64; 2]| {
+      Wallet::new().sign_transaction(Transaction::batch_transfer(
+        sender.clone(),
+        vec![
+          Output {
+            receiver: alice.clone(),
+            amount: amounts[0],
+          },
+          Output {
+            receiver: bob.clone(),
+            amount: amounts[1],
+          },
+        ],
+      ))
+    };
+
+    chain.execute(&batch([3, 4])).unwrap();
+
+    assert_eq!(
+      Err(ChainError::InsufficientBalance(sender.clone())),
+      chain.execute(&batch([1, 3]))
+    );
+    assert_eq!(Some(3), chain.account().balance(&sender));
+    assert_eq!(Some(3), chain.account().balance(&alice));
+    assert_eq!(Some(4), chain.account().balance(&bob));
+  }
+
+  #[test]
+  fn rejects_batches_with_too_many_outputs() {
+    let sender = String::from("sender_public_key");
+
+    let chain = Chain::with_params(
+      vec![(sender.clone(), 10)],
+      ConsensusParams {
+        max_batch_outputs: 1,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let output = Output {
+      receiver: sender.clone(),
+      amount: 1,
+    };
+
+    let batch = Wallet::new().sign_transaction(Transaction::batch_transfer(
+      sender,
+      vec![output.clone(), output],
+    ));
+
+    assert_eq!(
+      Err(ChainError::InvalidBatch(batch.transaction.id().clone())),
+      chain.can_execute(&batch)
+    );
+  }
+
+  #[test]
+  fn rejects_memos_over_the_limit() {
+    let sender = String::from("sender_public_key");
+
+    let chain = Chain::with_params(
+      vec![(sender.clone(), 10)],
+      ConsensusParams {
+        max_memo_size: 4,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let transfer = |memo: &str| {
+      Wallet::new().sign_transaction(
+        Transaction::transfer(sender.clone(), sender.clone(), 1).with_memo(String::from(memo)),
+      )
+    };
+
+    let too_large = transfer("12345");
+
+    assert_eq!(Ok(()), chain.can_execute(&transfer("1234")));
+    assert_eq!(
+      Err(ChainError::MemoTooLarge(too_large.transaction.id().clone())),
+      chain.can_execute(&too_large)
+    );
+  }
+
+  #[test]
+  fn contract_calls_update_storage_and_leave_receipts() {
+    use crate::vm::Instruction::*;
+
+    let wallet = Wallet::new();
+
+    let caller = wallet.public_key();
+
+    let mut chain = Chain::with_allocations(vec![(caller.clone(), 10_000)]);
+
+    // Adds the first argument to the counter under key 0 and logs the total.
+    let deploy = wallet.sign_transaction(Transaction::deploy_contract(
+      caller.clone(),
+      vec![Push(0), Push(0), Load, Arg(0), Add, Dup, Log, Store],
+    ));
+
+    let contract = Transaction::contract_account(deploy.transaction.id());
+
+    chain.execute(&deploy).unwrap();
+
+    let call = |args| {
+      wallet.sign_transaction(Transaction::call_contract(
+        caller.clone(),
+        contract.clone(),
+        args,
+        1000,
+      ))
+    };
+
+    let (first, failed, last) = (call(vec![5]), call(Vec::new()), call(vec![3]));
+
+    let _ = chain.add(block_with(
+      &chain,
+      vec![first.clone(), failed.clone(), last],
+      1,
+    ));
+
+    assert_eq!(
+      Some(&8),
+      chain.account().contract(&contract).unwrap().storage.get(&0)
+    );
+
+    let receipt = chain.receipt(first.transaction.id()).unwrap();
+
+    assert!(receipt.succeeded());
+    assert_eq!(vec![5], receipt.contract.as_ref().unwrap().logs);
+    assert_eq!(35, receipt.fee);
+
+    let receipt = chain.receipt(failed.transaction.id()).unwrap();
+
+    assert_eq!(
+      ExecutionStatus::Failed {
+        reason: format!(
+          "{:?}",
+          ChainError::ContractFailed {
+            id: failed.transaction.id().clone(),
+            error: VmError::MissingArgument(0),
+            gas_used: 8,
+          }
+        )
+      },
+      receipt.status
+    );
+    assert_eq!(
+      Some(ContractReceipt {
+        gas_used: 8,
+        logs: Vec::new(),
+      }),
+      receipt.contract
+    );
+    assert_eq!(8, receipt.fee);
+
+    // Failed calls pay for their gas too, fees are burned.
+    assert_eq!(Some(10_000 - 35 - 8 - 35), chain.account().balance(&caller));
+    assert_eq!(
+      10_000 + chain.params().initial_block_reward - 35 - 8 - 35,
+      chain.supply()
+    );
+  }
+
+  #[test]
+  fn callers_must_afford_the_gas_limit() {
+    let wallet = Wallet::new();
+
+    let caller = wallet.public_key();
+
+    let mut chain = Chain::with_allocations(vec![(caller.clone(), 100)]);
+
+    let deploy = wallet.sign_transaction(Transaction::deploy_contract(
+      caller.clone(),
+      vec![crate::vm::Instruction::Stop],
+    ));
+
+    let contract = Transaction::contract_account(deploy.transaction.id());
+
+    chain.execute(&deploy).unwrap();
+
+    let call = |gas_limit| {
+      wallet.sign_transaction(Transaction::call_contract(
+        caller.clone(),
+        contract.clone(),
+        Vec::new(),
+        gas_limit,
+      ))
+    };
+
+    assert_eq!(Ok(()), chain.can_execute(&call(100)));
+    assert_eq!(
+      Err(ChainError::InsufficientBalance(caller.clone())),
+      chain.can_execute(&call(101))
+    );
+  }
+
+  #[test]
+  fn rejects_empty_contracts_and_calls_to_unknown_ones() {
+    let caller = String::from("caller_public_key");
+
+    let chain = Chain::with_allocations(vec![(caller.clone(), 0)]);
+
+    let wallet = Wallet::new();
+
+    let deploy = wallet.sign_transaction(Transaction::deploy_contract(caller.clone(), Vec::new()));
+
+    assert_eq!(
+      Err(ChainError::InvalidContract(deploy.transaction.id().clone())),
+      chain.can_execute(&deploy)
+    );
+    assert_eq!(
+      Err(ChainError::ContractNotFound(String::from("contract"))),
+      chain.can_execute(&wallet.sign_transaction(Transaction::call_contract(
+        caller,
+        String::from("contract"),
+        Vec::new(),
+        chain.params().max_gas_per_call,
+      )))
+    );
   }
 
-  pub fn add(&mut self, signed_block: SignedBlock) -> Result<(), ChainError> {
-    let last_block = self.blocks.last().unwrap();
+  #[test]
+  fn keeps_a_receipt_for_every_transaction_in_the_block() {
+    let wallet = Wallet::new();
 
-    // TODO: make hash a property of the block to avoid computing it every time?
-    // NOTE: is it safe to make the hash a property of the block?
-    if last_block.hash() != signed_block.last_hash() {
-      return Err(ChainError::InvalidBlockHash(signed_block));
-    }
+    let sender = wallet.public_key();
 
-    if last_block.block_count() + 1 != signed_block.block_count() {
-      return Err(ChainError::InvalidBlockCount(signed_block));
-    }
+    let receiver = String::from("receiver_public_key");
 
-    let mut failed_transactions = Vec::new();
+    let mut chain = Chain::with_allocations(vec![(sender.clone(), 10), (receiver.clone(), 0)]);
 
-    for transaction in &signed_block.block.transactions {
-      if let Err(error) = self.execute(transaction) {
-        failed_transactions.push(error);
-      }
-    }
+    let sent = wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 4));
 
-    self.blocks.push(signed_block);
+    let failed = wallet.sign_transaction(Transaction::transfer(
+      sender.clone(),
+      String::from("unknown"),
+      4,
+    ));
 
-    if !failed_transactions.is_empty() {
-      return Err(ChainError::TransactionsFailed(failed_transactions));
-    }
+    let _ = chain.add(block_with(&chain, vec![sent.clone(), failed.clone()], 1));
 
-    Ok(())
+    assert_eq!(
+      Some(&Receipt {
+        id: sent.transaction.id().clone(),
+        block_count: 1,
+        position: 0,
+        status: ExecutionStatus::Success,
+        fee: 0,
+        balances: vec![(sender.clone(), 6), (receiver, 4)]
+          .into_iter()
+          .collect(),
+        contract: None,
+      }),
+      chain.receipt(sent.transaction.id())
+    );
+
+    let receipt = chain.receipt(failed.transaction.id()).unwrap();
+
+    assert_eq!(
+      ExecutionStatus::Failed {
+        reason: format!("{:?}", ChainError::AccountNotFound(String::from("unknown")))
+      },
+      receipt.status
+    );
+    assert_eq!(1, receipt.location().position);
+    assert_eq!(None, chain.receipt("unknown"));
   }
 
-  pub fn execute(
-    &mut self,
-    SignedTransaction { transaction, .. }: &SignedTransaction,
-  ) -> Result<(), ChainError> {
-    match transaction {
-      Transaction::Transfer {
-        sender,
-        receiver,
-        amount,
-        ..
-      } => {
-        // TODO: not atomic, is this a problem?
-        self
-          .account
-          .update_balance(&sender, -amount)
-          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))?;
-        self
-          .account
-          .update_balance(&receiver, *amount)
-          .map_err(|AccountError::AccountNotFound(account)| ChainError::AccountNotFound(account))
-      }
+  fn staked_chain(validators: &[&Wallet]) -> Chain {
+    let mut chain = Chain::with_params(
+      validators
+        .iter()
+        .map(|wallet| (wallet.public_key(), 10))
+        .collect(),
+      ConsensusParams {
+        checkpoint_interval: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    for wallet in validators {
+      chain
+        .execute(&wallet.sign_transaction(Transaction::stake(wallet.public_key(), 10)))
+        .unwrap();
     }
+
+    chain
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use crate::block::Block;
-  use crate::wallet::Wallet;
+  #[test]
+  fn checkpoints_become_final_with_two_thirds_of_the_stake() {
+    let (a, b, c) = (Wallet::new(), Wallet::new(), Wallet::new());
+
+    let mut chain = staked_chain(&[&a, &b, &c]);
+
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+
+    let not_a_checkpoint = Checkpoint {
+      block_count: 1,
+      hash: chain.tip().hash(),
+    };
+
+    let vote = a.sign_vote(not_a_checkpoint);
+
+    assert_eq!(
+      Err(ChainError::InvalidVote(vote.clone())),
+      chain.add_vote(&vote)
+    );
+
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
+
+    let checkpoint = chain.last_checkpoint().unwrap();
+
+    let vote = Wallet::new().sign_vote(checkpoint.clone());
+
+    assert_eq!(
+      Err(ChainError::InvalidVote(vote.clone())),
+      chain.add_vote(&vote)
+    );
+
+    assert_eq!(Ok(true), chain.add_vote(&a.sign_vote(checkpoint.clone())));
+    assert_eq!(Ok(false), chain.add_vote(&a.sign_vote(checkpoint.clone())));
+    assert_eq!(None, chain.finality().finalized());
+
+    assert_eq!(Ok(true), chain.add_vote(&b.sign_vote(checkpoint.clone())));
+    assert_eq!(Some(&checkpoint), chain.finality().finalized());
+  }
 
   #[test]
-  fn chain_starts_with_the_genesis_block() {
-    let chain = Chain::new();
+  fn keeps_votes_that_arrive_before_their_checkpoint() {
+    let validator = Wallet::new();
 
-    assert_eq!(chain.blocks, vec![SignedBlock::genesis()]);
+    let mut chain = staked_chain(&[&validator]);
+
+    let mut ahead = chain_with(chain.params().clone());
+
+    ahead.add(block_with(&ahead, Vec::new(), 1)).unwrap();
+    ahead.add(block_with(&ahead, Vec::new(), 2)).unwrap();
+
+    let checkpoint = ahead.last_checkpoint().unwrap();
+
+    assert_eq!(
+      Ok(false),
+      chain.add_vote(&Wallet::new().sign_vote(checkpoint.clone()))
+    );
+    assert_eq!(
+      Ok(true),
+      chain.add_vote(&validator.sign_vote(checkpoint.clone()))
+    );
+    assert_eq!(None, chain.finality().finalized());
+
+    chain.restore(ahead.blocks()[1..].to_vec()).unwrap();
+
+    assert_eq!(Some(&checkpoint), chain.finality().finalized());
   }
 
   #[test]
-  fn does_not_add_block_to_the_chain_if_last_block_hash_does_not_match() {
-    let mut chain = Chain::new();
+  fn votes_are_weighted_by_the_epoch_snapshot() {
+    let (a, b) = (Wallet::new(), Wallet::new());
+
+    let mut chain = Chain::with_params(
+      vec![(a.public_key(), 10), (b.public_key(), 100)],
+      ConsensusParams {
+        checkpoint_interval: 2,
+        epoch_length: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
 
-    let wallet = Wallet::new();
+    chain
+      .execute(&a.sign_transaction(Transaction::stake(a.public_key(), 10)))
+      .unwrap();
 
-    let block = wallet.sign_block(Block::new(
-      Vec::new(),
-      String::from("not_the_last_block_hash"),
-      String::from("forger_public_key"),
-      0,
-    ));
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
 
-    let expected = Err(ChainError::InvalidBlockHash(block.clone()));
+    chain
+      .execute(&b.sign_transaction(Transaction::stake(b.public_key(), 100)))
+      .unwrap();
 
-    let actual = chain.add(block);
+    chain.add(forged_by(&chain, &a, 3)).unwrap();
+    chain.add(forged_by(&chain, &a, 4)).unwrap();
 
-    assert_eq!(expected, actual);
+    let checkpoint = chain.last_checkpoint().unwrap();
+
+    let vote = b.sign_vote(checkpoint.clone());
+
+    assert_eq!(
+      Err(ChainError::InvalidVote(vote.clone())),
+      chain.add_vote(&vote)
+    );
+    assert_eq!(Ok(true), chain.add_vote(&a.sign_vote(checkpoint.clone())));
+    assert_eq!(Some(&checkpoint), chain.finality().finalized());
   }
 
   #[test]
-  fn does_not_add_block_to_the_chain_if_block_count_does_not_match() {
-    let mut chain = Chain::new();
+  fn refuses_to_reorg_past_the_final_checkpoint() {
+    let validator = Wallet::new();
 
-    let wallet = Wallet::new();
+    let mut chain = staked_chain(&[&validator]);
 
-    let block = wallet.sign_block(Block::new(
-      Vec::new(),
-      chain.blocks.last().unwrap().hash(),
-      String::from("forger_public_key"),
-      10,
-    ));
+    let mut fork = chain_with(chain.params().clone());
 
-    let expected = Err(ChainError::InvalidBlockCount(block.clone()));
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
 
-    let actual = chain.add(block);
+    chain
+      .add_vote(&validator.sign_vote(chain.last_checkpoint().unwrap()))
+      .unwrap();
 
-    assert_eq!(expected, actual);
+    for timestamp in 3..6 {
+      fork.add(block_with(&fork, Vec::new(), timestamp)).unwrap();
+    }
+
+    assert_eq!(
+      Err(ChainError::ReorgPastFinalized(fork.tip().clone())),
+      chain.reorganize(fork.blocks()[1..].to_vec())
+    );
+  }
+
+  fn forged_by(chain: &Chain, forger: &Wallet, timestamp: u128) -> SignedBlock {
+    forger.sign_block(Block::with_timestamp(
+      Vec::new(),
+      chain.tip().hash(),
+      forger.public_key(),
+      chain.tip().block_count() + 1,
+      timestamp,
+    ))
   }
 
   #[test]
-  fn after_adding_block_to_the_chain_returns_transactions_that_failed() {
-    let mut chain = Chain::new();
+  fn evidence_of_double_signing_slashes_and_jails_the_forger() {
+    let (forger, reporter) = (Wallet::new(), Wallet::new());
 
-    let sender = String::from("sender_public_key");
+    let mut chain = staked_chain(&[&forger]);
 
-    let receiver = String::from("receiver_public_key");
+    let first = forged_by(&chain, &forger, 1);
+    let second = forged_by(&chain, &forger, 2);
 
-    let wallet = Wallet::new();
+    chain.add(first.clone()).unwrap();
 
-    let transaction =
-      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+    let supply = chain.supply();
 
-    let block = wallet.sign_block(Block::new(
-      vec![transaction],
-      chain.blocks.last().unwrap().hash(),
-      String::from("forger_public_key"),
-      1,
-    ));
+    chain
+      .execute(&reporter.sign_transaction(Transaction::evidence(
+        reporter.public_key(),
+        first.clone(),
+        second.clone(),
+      )))
+      .unwrap();
 
-    let expected = Err(ChainError::TransactionsFailed(vec![
-      ChainError::AccountNotFound(sender),
-    ]));
+    assert_eq!(5, chain.account().stake(&forger.public_key()));
+    assert_eq!(supply - 5, chain.supply());
+    assert!(chain.account().is_jailed(&forger.public_key()));
 
-    let actual = chain.add(block);
+    let again = Transaction::evidence(reporter.public_key(), second, first);
 
-    assert_eq!(expected, actual);
+    assert_eq!(
+      Err(ChainError::InvalidEvidence(again.id().clone())),
+      chain.execute(&reporter.sign_transaction(again))
+    );
+
+    let block = forged_by(&chain, &forger, 3);
+
+    assert_eq!(
+      Err(ChainError::ForgerJailed(block.clone())),
+      chain.add(block)
+    );
+
+    chain.add(block_with(&chain, Vec::new(), 3)).unwrap();
+
+    let vote = forger.sign_vote(chain.last_checkpoint().unwrap());
+
+    assert_eq!(
+      Err(ChainError::InvalidVote(vote.clone())),
+      chain.add_vote(&vote)
+    );
   }
 
   #[test]
-  fn block_is_added_to_the_chain_even_if_one_of_its_transactions_fail() {
-    let mut chain = Chain::new();
+  fn rejects_evidence_that_is_not_an_equivocation() {
+    let (a, b) = (Wallet::new(), Wallet::new());
+
+    let mut chain = staked_chain(&[&a, &b]);
+
+    let block = forged_by(&chain, &a, 1);
+
+    let invalid = vec![
+      Transaction::evidence(a.public_key(), block.clone(), block.clone()),
+      Transaction::evidence(a.public_key(), block.clone(), forged_by(&chain, &b, 1)),
+      Transaction::evidence(
+        a.public_key(),
+        block.clone(),
+        Wallet::new().sign_block(forged_by(&chain, &a, 2).block),
+      ),
+    ];
+
+    for transaction in invalid {
+      assert_eq!(
+        Err(ChainError::InvalidEvidence(transaction.id().clone())),
+        chain.execute(&a.sign_transaction(transaction))
+      );
+    }
+  }
 
-    let sender = String::from("sender_public_key");
+  #[test]
+  fn only_the_validator_can_stake_or_unstake_its_coins() {
+    let (validator, attacker) = (Wallet::new(), Wallet::new());
 
-    let receiver = String::from("receiver_public_key");
+    let mut chain = Chain::with_allocations(vec![(validator.public_key(), 20)]);
 
-    let wallet = Wallet::new();
+    chain
+      .execute(&validator.sign_transaction(Transaction::stake(validator.public_key(), 10)))
+      .unwrap();
 
-    let transaction =
-      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+    let stake = attacker.sign_transaction(Transaction::stake(validator.public_key(), 10));
 
-    let block = wallet.sign_block(Block::new(
-      vec![transaction],
-      chain.blocks.last().unwrap().hash(),
-      String::from("forger_public_key"),
-      1,
-    ));
+    assert_eq!(
+      Err(ChainError::InvalidTransactionSignature(
+        stake.transaction.id().clone()
+      )),
+      chain.execute(&stake)
+    );
+
+    let unstake = attacker.sign_transaction(Transaction::unstake(validator.public_key(), 10));
 
     assert_eq!(
-      Err(ChainError::TransactionsFailed(vec![
-        ChainError::AccountNotFound(sender)
-      ])),
-      chain.add(block.clone())
+      Err(ChainError::InvalidTransactionSignature(
+        unstake.transaction.id().clone()
+      )),
+      chain.execute(&unstake)
     );
+    assert_eq!(10, chain.account().stake(&validator.public_key()));
+    assert_eq!(Some(10), chain.account().balance(&validator.public_key()));
+  }
 
-    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), block]);
+  #[test]
+  fn stake_changes_take_effect_at_the_next_epoch() {
+    let (a, b) = (Wallet::new(), Wallet::new());
+
+    let mut chain = Chain::with_params(
+      vec![(a.public_key(), 10), (b.public_key(), 10)],
+      ConsensusParams {
+        epoch_length: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    chain
+      .execute(&a.sign_transaction(Transaction::stake(a.public_key(), 10)))
+      .unwrap();
+
+    assert_eq!(None, chain.next_forger(1));
+
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
+
+    assert_eq!(1, chain.epoch().number);
+    assert_eq!(3, chain.epoch().start);
+    assert_eq!(Some(&a.public_key()), chain.next_forger(3));
+
+    let block = block_with(&chain, Vec::new(), 3);
+
+    assert_eq!(
+      Err(ChainError::UnexpectedForger(block.clone())),
+      chain.add(block)
+    );
+
+    chain.add(forged_by(&chain, &a, 3)).unwrap();
+
+    chain
+      .execute(&b.sign_transaction(Transaction::stake(b.public_key(), 10)))
+      .unwrap();
+
+    assert_eq!(1, chain.epoch().validators.len());
+    assert_eq!(Some(&a.public_key()), chain.next_forger(4));
+
+    chain.add(forged_by(&chain, &a, 4)).unwrap();
+
+    assert_eq!(2, chain.epoch().number);
+    assert_eq!(20, chain.epoch().total_stake());
+    assert_eq!(3, chain.epochs().len());
   }
 
   #[test]
-  fn adds_blocks_to_the_chain() {
-    let mut chain = Chain::new();
+  fn another_validator_may_forge_once_the_picked_one_timed_out() {
+    let (a, b) = (Wallet::new(), Wallet::new());
+
+    let mut chain = Chain::with_params(
+      vec![(a.public_key(), 10), (b.public_key(), 10)],
+      ConsensusParams {
+        epoch_length: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
 
-    let wallet = Wallet::new();
+    for wallet in [&a, &b] {
+      chain
+        .execute(&wallet.sign_transaction(Transaction::stake(wallet.public_key(), 10)))
+        .unwrap();
+    }
 
-    let block = wallet.sign_block(Block::new(
-      Vec::new(),
-      chain.blocks.last().unwrap().hash(),
-      String::from("forger_public_key"),
-      1,
-    ));
+    chain.add(block_with(&chain, Vec::new(), 1)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
 
-    assert_eq!(Ok(()), chain.add(block.clone()));
+    let offline = chain.next_forger(2).unwrap().clone();
 
-    assert_eq!(chain.blocks, vec![SignedBlock::genesis(), block]);
+    let online = if offline == a.public_key() { &b } else { &a };
+
+    let timeout = chain.params().forger_timeout.as_micros();
+
+    let round = (1..20)
+      .find(|round| chain.next_forger(2 + round * timeout) == Some(&online.public_key()))
+      .unwrap();
+
+    let block = forged_by(&chain, online, 3);
+
+    assert_eq!(
+      Err(ChainError::UnexpectedForger(block.clone())),
+      chain.add(block)
+    );
+
+    chain
+      .add(forged_by(&chain, online, 2 + round * timeout))
+      .unwrap();
   }
 
   #[test]
-  fn executes_block_transactions_before_adding_it_to_the_chain() {
-    let mut chain = Chain::new();
+  fn validators_cant_skip_ahead_to_a_round_they_forge_in() {
+    let (a, b) = (Wallet::new(), Wallet::new());
+
+    let clock = ManualClock::new();
+
+    let mut chain = Chain::with_params(
+      vec![(a.public_key(), 10), (b.public_key(), 10)],
+      ConsensusParams {
+        epoch_length: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(clock.clone()),
+    );
 
-    let sender = String::from("sender_public_key");
+    for wallet in [&a, &b] {
+      chain
+        .execute(&wallet.sign_transaction(Transaction::stake(wallet.public_key(), 10)))
+        .unwrap();
+    }
 
-    chain.account.add_account(sender.clone());
+    chain.add(block_with(&chain, Vec::new(), 0)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 0)).unwrap();
 
-    chain.account.update_balance(&sender, 10).unwrap();
+    let picked = chain.next_forger(0).unwrap().clone();
 
-    let receiver = String::from("receiver_public_key");
+    let other = if picked == a.public_key() { &b } else { &a };
 
-    chain.account.add_account(receiver.clone());
+    let timeout = chain.params().forger_timeout.as_micros();
 
-    let wallet = Wallet::new();
+    let round = (1..20)
+      .find(|round| chain.next_forger(round * timeout) == Some(&other.public_key()))
+      .unwrap();
 
-    let transaction =
-      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+    // The timestamp is within the allowed drift, but the round hasn't started yet.
+    clock.advance(Duration::from_micros((round * timeout - 1) as u64));
 
-    let block = wallet.sign_block(Block::new(
-      vec![transaction],
-      chain.blocks.last().unwrap().hash(),
-      String::from("forger_public_key"),
-      1,
-    ));
+    let block = forged_by(&chain, other, round * timeout);
 
-    chain.add(block).unwrap();
+    assert_eq!(
+      Err(ChainError::UnexpectedForger(block.clone())),
+      chain.add(block.clone())
+    );
 
-    assert_eq!(chain.account.balance(&sender), Some(0));
-    assert_eq!(chain.account.balance(&receiver), Some(10));
+    clock.advance(Duration::from_micros(1));
+
+    assert_eq!(Ok(()), chain.add(block));
   }
 
   #[test]
-  fn executes_transfer_transaction() {
-    let mut chain = Chain::new();
+  fn unstaked_coins_stay_slashable_for_an_epoch() {
+    let (forger, reporter) = (Wallet::new(), Wallet::new());
+
+    let mut chain = Chain::with_params(
+      vec![(forger.public_key(), 10)],
+      ConsensusParams {
+        epoch_length: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
 
-    let sender = String::from("sender_public_key");
+    chain
+      .execute(&forger.sign_transaction(Transaction::stake(forger.public_key(), 10)))
+      .unwrap();
 
-    chain.account.add_account(sender.clone());
+    let first = forged_by(&chain, &forger, 1);
+    let second = forged_by(&chain, &forger, 2);
 
-    chain.account.update_balance(&sender, 10).unwrap();
+    chain.add(first.clone()).unwrap();
 
-    let receiver = String::from("receiver_public_key");
+    chain
+      .execute(&forger.sign_transaction(Transaction::unstake(forger.public_key(), 4)))
+      .unwrap();
 
-    chain.account.add_account(receiver.clone());
+    let balance = chain.account().balance(&forger.public_key()).unwrap();
 
-    let wallet = Wallet::new();
+    assert_eq!(6, chain.account().stake(&forger.public_key()));
 
-    let transaction =
-      wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10));
+    let supply = chain.supply();
 
-    chain.execute(&transaction).unwrap();
+    chain
+      .execute(&reporter.sign_transaction(Transaction::evidence(
+        reporter.public_key(),
+        first,
+        second,
+      )))
+      .unwrap();
 
-    assert_eq!(chain.account.balance(&sender), Some(0));
-    assert_eq!(chain.account.balance(&receiver), Some(10));
+    assert_eq!(supply - 5, chain.supply());
+
+    let unstake = Transaction::unstake(forger.public_key(), 3);
+
+    assert_eq!(
+      Err(ChainError::ValidatorJailed(forger.public_key())),
+      chain.execute(&forger.sign_transaction(unstake))
+    );
+
+    chain.add(block_with(&chain, Vec::new(), 2)).unwrap();
+    chain.add(block_with(&chain, Vec::new(), 3)).unwrap();
+
+    assert_eq!(Some(balance), chain.account().balance(&forger.public_key()));
+
+    chain.add(block_with(&chain, Vec::new(), 4)).unwrap();
+
+    assert_eq!(
+      Some(balance + 2),
+      chain.account().balance(&forger.public_key())
+    );
   }
 }
@@ -0,0 +1,85 @@
+use std::fmt::Debug;
+use std::time::SystemTime;
+
+#[cfg(test)]
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+/// Source of the current time, in microseconds since the unix epoch.
+pub trait Clock: Debug + Send + Sync {
+  fn now(&self) -> u128;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> u128 {
+    SystemTime::now()
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .unwrap()
+      .as_micros()
+  }
+}
+
+/// A clock that only moves when it is told to.
+///
+/// Clones share the same time, so every node in a simulation
+/// can observe the same clock.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+  now: Arc<Mutex<u128>>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn advance(&self, duration: Duration) {
+    *self.now.lock().unwrap() += duration.as_micros();
+  }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+  fn now(&self) -> u128 {
+    *self.now.lock().unwrap()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn manual_clock_starts_at_zero() {
+    let clock = ManualClock::new();
+
+    assert_eq!(0, clock.now());
+  }
+
+  #[test]
+  fn manual_clock_only_moves_when_advanced() {
+    let clock = ManualClock::new();
+
+    clock.advance(Duration::from_millis(5));
+
+    assert_eq!(5_000, clock.now());
+  }
+
+  #[test]
+  fn manual_clock_clones_share_the_same_time() {
+    let clock = ManualClock::new();
+
+    let clone = clock.clone();
+
+    clock.advance(Duration::from_secs(1));
+
+    assert_eq!(1_000_000, clone.now());
+  }
+}
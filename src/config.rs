@@ -0,0 +1,237 @@
+use libp2p::Multiaddr;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+
+/// Node settings.
+///
+/// The http port is the first command line argument, everything else
+/// comes from environment variables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+  pub port: u16,
+  /// Comma separated multiaddrs dialed on startup, read from `BOOTSTRAP_PEERS`.
+  pub bootstrap_peers: Vec<Multiaddr>,
+  /// Where the node keeps its state, read from `DATA_DIR`.
+  pub data_dir: PathBuf,
+  /// Filter directives like `info` or `blockchain=debug`, read from `LOG_LEVEL`.
+  pub log_level: String,
+  /// Read from `LOG_FORMAT`, either `text` or `json`.
+  pub log_format: LogFormat,
+  /// How many blocks the node may be behind its peers and still be ready,
+  /// read from `READY_MAX_LAG`.
+  pub ready_max_lag: u128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+  Text,
+  Json,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+  MissingPort,
+  InvalidPort(String),
+  InvalidMultiaddr(String),
+  InvalidLogLevel(String),
+  InvalidLogFormat(String),
+  InvalidReadyMaxLag(String),
+}
+
+impl Config {
+  pub fn from_env() -> Result<Self, ConfigError> {
+    Config::parse(std::env::args().collect(), std::env::vars().collect())
+  }
+
+  pub fn parse(args: Vec<String>, vars: HashMap<String, String>) -> Result<Self, ConfigError> {
+    let port = args.get(1).ok_or(ConfigError::MissingPort)?;
+
+    let port = port
+      .parse()
+      .map_err(|_| ConfigError::InvalidPort(port.clone()))?;
+
+    let bootstrap_peers = match vars.get("BOOTSTRAP_PEERS") {
+      None => Vec::new(),
+      Some(peers) => peers
+        .split(',')
+        .map(str::trim)
+        .filter(|peer| !peer.is_empty())
+        .map(|peer| {
+          peer
+            .parse()
+            .map_err(|_| ConfigError::InvalidMultiaddr(peer.to_owned()))
+        })
+        .collect::<Result<_, _>>()?,
+    };
+
+    let data_dir = vars
+      .get("DATA_DIR")
+      .map(PathBuf::from)
+      .unwrap_or_else(|| PathBuf::from("data"));
+
+    let log_level = vars
+      .get("LOG_LEVEL")
+      .cloned()
+      .unwrap_or_else(|| String::from("info"));
+
+    EnvFilter::try_new(&log_level).map_err(|_| ConfigError::InvalidLogLevel(log_level.clone()))?;
+
+    let log_format = match vars.get("LOG_FORMAT").map(String::as_str) {
+      None | Some("text") => LogFormat::Text,
+      Some("json") => LogFormat::Json,
+      Some(format) => return Err(ConfigError::InvalidLogFormat(format.to_owned())),
+    };
+
+    let ready_max_lag = match vars.get("READY_MAX_LAG") {
+      None => 2,
+      Some(lag) => lag
+        .parse()
+        .map_err(|_| ConfigError::InvalidReadyMaxLag(lag.clone()))?,
+    };
+
+    Ok(Self {
+      port,
+      bootstrap_peers,
+      data_dir,
+      log_level,
+      log_format,
+      ready_max_lag,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn port_is_required() {
+    let actual = Config::parse(args(&["blockchain"]), HashMap::new());
+
+    assert_eq!(Err(ConfigError::MissingPort), actual);
+  }
+
+  #[test]
+  fn returns_error_when_port_is_not_a_number() {
+    let actual = Config::parse(args(&["blockchain", "abc"]), HashMap::new());
+
+    assert_eq!(Err(ConfigError::InvalidPort(String::from("abc"))), actual);
+  }
+
+  #[test]
+  fn uses_defaults_when_no_variables_are_set() {
+    let expected = Config {
+      port: 8080,
+      bootstrap_peers: Vec::new(),
+      data_dir: PathBuf::from("data"),
+      log_level: String::from("info"),
+      log_format: LogFormat::Text,
+      ready_max_lag: 2,
+    };
+
+    let actual = Config::parse(args(&["blockchain", "8080"]), HashMap::new());
+
+    assert_eq!(Ok(expected), actual);
+  }
+
+  #[test]
+  fn parses_bootstrap_peers() {
+    let mut vars = HashMap::new();
+
+    vars.insert(
+      String::from("BOOTSTRAP_PEERS"),
+      String::from("/ip4/10.0.0.1/tcp/4001, /ip4/10.0.0.2/tcp/4001"),
+    );
+
+    let config = Config::parse(args(&["blockchain", "8080"]), vars).unwrap();
+
+    assert_eq!(
+      config.bootstrap_peers,
+      vec![
+        "/ip4/10.0.0.1/tcp/4001".parse().unwrap(),
+        "/ip4/10.0.0.2/tcp/4001".parse().unwrap()
+      ]
+    );
+  }
+
+  #[test]
+  fn returns_error_when_a_bootstrap_peer_is_not_a_multiaddr() {
+    let mut vars = HashMap::new();
+
+    vars.insert(
+      String::from("BOOTSTRAP_PEERS"),
+      String::from("not_a_multiaddr"),
+    );
+
+    let actual = Config::parse(args(&["blockchain", "8080"]), vars);
+
+    assert_eq!(
+      Err(ConfigError::InvalidMultiaddr(String::from(
+        "not_a_multiaddr"
+      ))),
+      actual
+    );
+  }
+
+  #[test]
+  fn parses_log_settings() {
+    let mut vars = HashMap::new();
+
+    vars.insert(String::from("LOG_LEVEL"), String::from("blockchain=debug"));
+    vars.insert(String::from("LOG_FORMAT"), String::from("json"));
+
+    let config = Config::parse(args(&["blockchain", "8080"]), vars).unwrap();
+
+    assert_eq!("blockchain=debug", config.log_level);
+    assert_eq!(LogFormat::Json, config.log_format);
+  }
+
+  #[test]
+  fn returns_error_when_the_log_level_is_invalid() {
+    let mut vars = HashMap::new();
+
+    vars.insert(String::from("LOG_LEVEL"), String::from("blockchain=loud"));
+
+    let actual = Config::parse(args(&["blockchain", "8080"]), vars);
+
+    assert_eq!(
+      Err(ConfigError::InvalidLogLevel(String::from(
+        "blockchain=loud"
+      ))),
+      actual
+    );
+  }
+
+  #[test]
+  fn returns_error_when_the_log_format_is_unknown() {
+    let mut vars = HashMap::new();
+
+    vars.insert(String::from("LOG_FORMAT"), String::from("xml"));
+
+    let actual = Config::parse(args(&["blockchain", "8080"]), vars);
+
+    assert_eq!(
+      Err(ConfigError::InvalidLogFormat(String::from("xml"))),
+      actual
+    );
+  }
+
+  #[test]
+  fn returns_error_when_ready_max_lag_is_not_a_number() {
+    let mut vars = HashMap::new();
+
+    vars.insert(String::from("READY_MAX_LAG"), String::from("-1"));
+
+    let actual = Config::parse(args(&["blockchain", "8080"]), vars);
+
+    assert_eq!(
+      Err(ConfigError::InvalidReadyMaxLag(String::from("-1"))),
+      actual
+    );
+  }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+/// Rules every node must agree on for blocks to be valid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsensusParams {
+  /// Largest a signed block may be once serialized, in bytes.
+  pub max_block_size: usize,
+  pub max_transactions: usize,
+  /// How far ahead of our clock a block timestamp may be, less than
+  /// `forger_timeout` so it can't be used to skip to a later forger round.
+  pub max_timestamp_drift: Duration,
+  /// Credited to the forger of the first blocks.
+  pub initial_block_reward: i64,
+  /// The block reward halves every `halving_interval` blocks.
+  pub halving_interval: u128,
+  /// No reward is paid once genesis allocations plus rewards reach this.
+  pub max_supply: i64,
+  /// Most receivers a single batch transfer may pay.
+  pub max_batch_outputs: usize,
+  /// Longest memo a transaction may carry, in bytes.
+  pub max_memo_size: usize,
+  /// Most instructions a contract may have.
+  pub max_contract_size: usize,
+  /// Highest gas limit a contract call may ask for.
+  pub max_gas_per_call: u64,
+  /// Coins a contract call pays for each unit of gas it uses, failed calls
+  /// included. Fees are burned, like slashed stake.
+  pub gas_price: i64,
+  /// Validators vote on every block whose block count is a multiple of this.
+  pub checkpoint_interval: u128,
+  /// Share of a double signing forger's stake that is burned, in percent.
+  pub slash_percent: i64,
+  /// Blocks in an epoch, the validator set only changes between epochs.
+  pub epoch_length: u128,
+  /// How long the picked forger has before the next round picks another one,
+  /// counted from the parent block's timestamp.
+  pub forger_timeout: Duration,
+}
+
+impl Default for ConsensusParams {
+  fn default() -> Self {
+    Self {
+      max_block_size: 1024 * 1024,
+      max_transactions: 1000,
+      max_timestamp_drift: Duration::from_secs(15),
+      initial_block_reward: 50,
+      halving_interval: 210_000,
+      max_supply: 21_000_000,
+      max_batch_outputs: 100,
+      max_memo_size: 256,
+      max_contract_size: 1024,
+      max_gas_per_call: 100_000,
+      gas_price: 1,
+      checkpoint_interval: 10,
+      slash_percent: 50,
+      epoch_length: 100,
+      forger_timeout: Duration::from_secs(30),
+    }
+  }
+}
+
+impl ConsensusParams {
+  /// Panics on params that would let forgers skip their turn.
+  pub fn check(&self) {
+    assert!(
+      self.max_timestamp_drift < self.forger_timeout,
+      "max_timestamp_drift must be less than forger_timeout"
+    );
+  }
+
+  /// What a contract call that used `gas_used` gas pays.
+  pub fn fee(&self, gas_used: u64) -> i64 {
+    gas_used as i64 * self.gas_price
+  }
+
+  /// What the forger of block `block_count` earns when `supply` coins exist.
+  pub fn block_reward(&self, block_count: u128, supply: i64) -> i64 {
+    let halvings = block_count.saturating_sub(1) / self.halving_interval.max(1);
+
+    let reward = if halvings >= 63 {
+      0
+    } else {
+      self.initial_block_reward >> halvings
+    };
+
+    reward.min(self.max_supply - supply).max(0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params() -> ConsensusParams {
+    ConsensusParams {
+      initial_block_reward: 100,
+      halving_interval: 10,
+      max_supply: 1000,
+      ..ConsensusParams::default()
+    }
+  }
+
+  #[test]
+  fn reward_halves_every_interval() {
+    let params = params();
+
+    assert_eq!(100, params.block_reward(1, 0));
+    assert_eq!(100, params.block_reward(10, 0));
+    assert_eq!(50, params.block_reward(11, 0));
+    assert_eq!(25, params.block_reward(21, 0));
+    assert_eq!(0, params.block_reward(10_000, 0));
+  }
+
+  #[test]
+  #[should_panic(expected = "max_timestamp_drift must be less than forger_timeout")]
+  fn timestamps_cant_drift_past_a_forger_round() {
+    ConsensusParams {
+      max_timestamp_drift: Duration::from_secs(60),
+      forger_timeout: Duration::from_secs(30),
+      ..ConsensusParams::default()
+    }
+    .check();
+  }
+
+  #[test]
+  fn reward_never_exceeds_the_max_supply() {
+    let params = params();
+
+    assert_eq!(30, params.block_reward(1, 970));
+    assert_eq!(0, params.block_reward(1, 1000));
+    assert_eq!(0, params.block_reward(1, 2000));
+  }
+}
@@ -1,14 +1,20 @@
-use crate::node::{Node, NodeError};
+use crate::api_error::ApiError;
+use crate::chain::ChainError;
+use crate::config::Config;
+use crate::metrics::METRICS;
+use crate::node::Node;
+use crate::rpc;
 use crate::viewmodel;
 use crate::wallet::SignedTransaction;
-use actix_web::{post, web, HttpResponse, Responder};
+use crate::websocket;
+use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse, Responder};
 use std::sync::{Arc, Mutex};
 
 #[post("/transactions")]
 async fn add_transaction(
   node: web::Data<Arc<Mutex<Node>>>,
   input: web::Json<viewmodel::AddTransactionInput>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
   let mut node = node.lock().unwrap();
 
   let input = input.into_inner();
@@ -17,14 +23,270 @@ async fn add_transaction(
 
   let input: SignedTransaction = input.into();
 
-  match node.transaction(&public_key, input) {
-    Err(NodeError::InvalidSignature { .. }) => {
-      HttpResponse::UnprocessableEntity().json(viewmodel::Message {
-        message: "invalid signature".to_owned(),
-      })
-    }
-    Ok(()) => HttpResponse::Ok().json(viewmodel::Message {
-      message: "transaction added".to_owned(),
-    }),
+  node.transaction(&public_key, input)?;
+
+  Ok(HttpResponse::Ok().json(viewmodel::Message {
+    message: "transaction added".to_owned(),
+  }))
+}
+
+#[get("/admin/peers")]
+async fn peers(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  let mut peers: Vec<viewmodel::PeerOutput> = node
+    .peers()
+    .peers()
+    .iter()
+    .map(viewmodel::PeerOutput::from)
+    .collect();
+
+  peers.sort_by_key(|peer| peer.score);
+
+  HttpResponse::Ok().json(peers)
+}
+
+#[get("/ws")]
+async fn subscribe(
+  node: web::Data<Arc<Mutex<Node>>>,
+  request: HttpRequest,
+  body: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+  let (response, session, messages) = actix_ws::handle(&request, body)?;
+
+  let events = node.lock().unwrap().subscribe();
+
+  actix_web::rt::spawn(websocket::session(session, messages, events));
+
+  Ok(response)
+}
+
+#[post("/rpc")]
+async fn json_rpc(node: web::Data<Arc<Mutex<Node>>>, body: web::Bytes) -> impl Responder {
+  let mut node = node.lock().unwrap();
+
+  match rpc::handle(&mut node, &body) {
+    Some(response) => HttpResponse::Ok().json(response),
+    None => HttpResponse::NoContent().finish(),
   }
 }
+
+#[get("/metrics")]
+async fn metrics() -> impl Responder {
+  HttpResponse::Ok()
+    .content_type("text/plain; version=0.0.4")
+    .body(METRICS.gather())
+}
+
+/// The process is up, says nothing about whether the node is usable.
+#[get("/health")]
+async fn health() -> impl Responder {
+  HttpResponse::Ok().json(viewmodel::Message {
+    message: "ok".to_owned(),
+  })
+}
+
+/// The node is synced with its peers, its storage is open and it is listening for peers.
+#[get("/ready")]
+async fn ready(node: web::Data<Arc<Mutex<Node>>>, config: web::Data<Config>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  let sync_lag = node.sync_lag();
+
+  let synced = sync_lag <= config.ready_max_lag;
+
+  let storage_open = node.network().storage_open;
+
+  let listening = !node.network().listen_addresses.is_empty();
+
+  let output = viewmodel::ReadinessOutput {
+    ready: synced && storage_open && listening,
+    synced,
+    storage_open,
+    listening,
+    sync_lag,
+  };
+
+  if output.ready {
+    HttpResponse::Ok().json(output)
+  } else {
+    HttpResponse::ServiceUnavailable().json(output)
+  }
+}
+
+#[get("/node/info")]
+async fn node_info(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  HttpResponse::Ok().json(viewmodel::NodeInfoOutput::from(&*node))
+}
+
+#[get("/finality")]
+async fn finality(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  HttpResponse::Ok().json(viewmodel::FinalityOutput::from(&*node))
+}
+
+/// Validators of the current epoch and who forges the next block.
+#[get("/epoch")]
+async fn epoch(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  HttpResponse::Ok().json(viewmodel::EpochOutput::from(&*node))
+}
+
+#[get("/supply")]
+async fn supply(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  HttpResponse::Ok().json(viewmodel::SupplyOutput::from(&*node))
+}
+
+/// Transactions carrying the memo, newest first.
+#[get("/transactions")]
+async fn memo_transactions(
+  node: web::Data<Arc<Mutex<Node>>>,
+  memo: web::Query<viewmodel::MemoInput>,
+  page: web::Query<viewmodel::PageInput>,
+) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  let transactions = node.chain().memo_transactions(&memo.memo);
+
+  let limit = page.limit.min(viewmodel::PageInput::MAX_LIMIT);
+
+  let total = transactions.len();
+
+  let items = transactions
+    .rev()
+    .skip(page.offset)
+    .take(limit)
+    .map(viewmodel::TransactionOutput::from)
+    .collect();
+
+  HttpResponse::Ok().json(viewmodel::PageOutput {
+    items,
+    offset: page.offset,
+    limit,
+    total,
+  })
+}
+
+#[get("/contracts/{address}")]
+async fn contract(
+  node: web::Data<Arc<Mutex<Node>>>,
+  address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+  let node = node.lock().unwrap();
+
+  let address = address.into_inner();
+
+  match node.chain().account().contract(&address) {
+    None => Err(ChainError::ContractNotFound(address).into()),
+    Some(contract) => Ok(HttpResponse::Ok().json(contract)),
+  }
+}
+
+/// Whether the transaction succeeded, why not otherwise, and the balances it left.
+#[get("/transactions/{id}/receipt")]
+async fn receipt(
+  node: web::Data<Arc<Mutex<Node>>>,
+  id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+  let node = node.lock().unwrap();
+
+  match node.chain().receipt(&id) {
+    Some(receipt) => Ok(HttpResponse::Ok().json(receipt)),
+    None => Err(
+      ApiError::new(
+        StatusCode::NOT_FOUND,
+        "receipt_not_found",
+        "no transaction with this id made it into the chain",
+      )
+      .with_details(serde_json::json!({ "id": id.into_inner() })),
+    ),
+  }
+}
+
+/// Every issued asset, ordered by symbol.
+#[get("/assets")]
+async fn assets(node: web::Data<Arc<Mutex<Node>>>) -> impl Responder {
+  let node = node.lock().unwrap();
+
+  let mut assets: Vec<viewmodel::AssetOutput> = node
+    .chain()
+    .account()
+    .assets()
+    .map(viewmodel::AssetOutput::from)
+    .collect();
+
+  assets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+  HttpResponse::Ok().json(assets)
+}
+
+/// Balance of every asset the account holds, ordered by symbol.
+#[get("/accounts/{id}/assets")]
+async fn account_assets(
+  node: web::Data<Arc<Mutex<Node>>>,
+  account: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+  let node = node.lock().unwrap();
+
+  let account = account.into_inner();
+
+  let state = node.chain().account();
+
+  if state.balance(&account).is_none() {
+    return Err(ChainError::AccountNotFound(account).into());
+  }
+
+  let mut balances: Vec<viewmodel::AssetBalanceOutput> = state
+    .asset_balances(&account)
+    .into_iter()
+    .map(|(symbol, balance)| viewmodel::AssetBalanceOutput { symbol, balance })
+    .collect();
+
+  balances.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+  Ok(HttpResponse::Ok().json(balances))
+}
+
+/// Transactions the account sent or received, newest first.
+#[get("/accounts/{id}/transactions")]
+async fn account_transactions(
+  node: web::Data<Arc<Mutex<Node>>>,
+  account: web::Path<String>,
+  page: web::Query<viewmodel::PageInput>,
+) -> Result<HttpResponse, ApiError> {
+  let node = node.lock().unwrap();
+
+  let chain = node.chain();
+
+  let account = account.into_inner();
+
+  let transactions = chain.account_transactions(&account);
+
+  if transactions.len() == 0 && chain.account().balance(&account).is_none() {
+    return Err(ChainError::AccountNotFound(account).into());
+  }
+
+  let limit = page.limit.min(viewmodel::PageInput::MAX_LIMIT);
+
+  let total = transactions.len();
+
+  let items = transactions
+    .rev()
+    .skip(page.offset)
+    .take(limit)
+    .map(viewmodel::TransactionOutput::from)
+    .collect();
+
+  Ok(HttpResponse::Ok().json(viewmodel::PageOutput {
+    items,
+    offset: page.offset,
+    limit,
+    total,
+  }))
+}
@@ -0,0 +1,114 @@
+//! The chain is divided in epochs of `epoch_length` blocks. Validators and
+//! their stake are snapshotted when an epoch starts, so staking or unstaking
+//! only changes who forges from the next epoch on.
+
+use crate::transaction::PublicKey;
+use serde::Serialize;
+use sha2::Digest;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Epoch {
+  pub number: u128,
+  /// Block count of the first block in the epoch.
+  pub start: u128,
+  /// Stake of each validator when the epoch started.
+  pub validators: BTreeMap<PublicKey, i64>,
+}
+
+impl Epoch {
+  pub fn total_stake(&self) -> i64 {
+    self.validators.values().sum()
+  }
+
+  /// Picks the forger of the block after `parent_hash`, validators are
+  /// weighted by their stake. Every node picks the same one.
+  ///
+  /// When nobody forged in time, each later `round` picks again, so an
+  /// offline validator can't halt the chain.
+  ///
+  /// Returns None when no validator is eligible, anyone may forge then.
+  pub fn forger(
+    &self,
+    parent_hash: &str,
+    round: u128,
+    is_eligible: impl Fn(&PublicKey) -> bool,
+  ) -> Option<&PublicKey> {
+    let eligible: Vec<(&PublicKey, i64)> = self
+      .validators
+      .iter()
+      .filter(|(validator, stake)| **stake > 0 && is_eligible(validator))
+      .map(|(validator, stake)| (validator, *stake))
+      .collect();
+
+    let total: i64 = eligible.iter().map(|(_, stake)| stake).sum();
+
+    if total <= 0 {
+      return None;
+    }
+
+    let digest = sha2::Sha256::digest(format!("{}:{}", parent_hash, round).as_bytes());
+
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&digest[..8]);
+
+    let mut target = (u64::from_be_bytes(seed) % total as u64) as i64;
+
+    for (validator, stake) in eligible {
+      if target < stake {
+        return Some(validator);
+      }
+      target -= stake;
+    }
+
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn picks_forgers_weighted_by_stake() {
+    let epoch = Epoch {
+      number: 1,
+      start: 11,
+      validators: vec![(String::from("a"), 1), (String::from("b"), 3)]
+        .into_iter()
+        .collect(),
+    };
+
+    let picks: Vec<&PublicKey> = (0..400)
+      .map(|i| epoch.forger(&i.to_string(), 0, |_| true).unwrap())
+      .collect();
+
+    let a = picks.iter().filter(|forger| forger.as_str() == "a").count();
+
+    assert!(a > 50 && a < 150);
+    assert_eq!(
+      epoch.forger("hash", 0, |_| true),
+      epoch.forger("hash", 0, |_| true)
+    );
+    assert_eq!(
+      Some(&String::from("b")),
+      epoch.forger("hash", 0, |validator| validator == "b")
+    );
+    assert_eq!(None, epoch.forger("hash", 0, |_| false));
+  }
+
+  #[test]
+  fn later_rounds_fall_back_to_other_validators() {
+    let epoch = Epoch {
+      number: 1,
+      start: 11,
+      validators: vec![(String::from("a"), 1), (String::from("b"), 1)]
+        .into_iter()
+        .collect(),
+    };
+
+    let first = epoch.forger("hash", 0, |_| true).unwrap();
+
+    assert!((1..20).any(|round| epoch.forger("hash", round, |_| true).unwrap() != first));
+  }
+}
@@ -0,0 +1,161 @@
+use crate::transaction::{PublicKey, Transaction};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Things that happened to the node that clients may want to hear about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+  Block {
+    block_count: u128,
+    hash: String,
+    transactions: usize,
+  },
+  /// A transaction entered the pool.
+  Transaction {
+    transaction: Transaction,
+  },
+  TransactionStatus {
+    id: String,
+    status: TransactionStatus,
+  },
+  Balance {
+    account: PublicKey,
+    balance: i64,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TransactionStatus {
+  Pending,
+  Included { block_count: u128 },
+  Failed { block_count: u128, reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subscription {
+  Blocks,
+  Transactions,
+  TransactionStatus { id: String },
+  Balances { accounts: Vec<PublicKey> },
+}
+
+/// What a single client subscribed to.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+  blocks: bool,
+  transactions: bool,
+  transaction_ids: HashSet<String>,
+  accounts: HashSet<PublicKey>,
+}
+
+impl Subscriptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&mut self, subscription: Subscription) {
+    match subscription {
+      Subscription::Blocks => self.blocks = true,
+      Subscription::Transactions => self.transactions = true,
+      Subscription::TransactionStatus { id } => {
+        self.transaction_ids.insert(id);
+      }
+      Subscription::Balances { accounts } => self.accounts.extend(accounts),
+    }
+  }
+
+  pub fn matches(&self, event: &Event) -> bool {
+    match event {
+      Event::Block { .. } => self.blocks,
+      Event::Transaction { .. } => self.transactions,
+      Event::TransactionStatus { id, .. } => self.transaction_ids.contains(id),
+      Event::Balance { account, .. } => self.accounts.contains(account),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn balance(account: &str) -> Event {
+    Event::Balance {
+      account: String::from(account),
+      balance: 10,
+    }
+  }
+
+  #[test]
+  fn nothing_matches_when_there_are_no_subscriptions() {
+    let subscriptions = Subscriptions::new();
+
+    let block = Event::Block {
+      block_count: 1,
+      hash: String::from("hash"),
+      transactions: 0,
+    };
+
+    assert!(!subscriptions.matches(&block));
+    assert!(!subscriptions.matches(&balance("public_key")));
+  }
+
+  #[test]
+  fn matches_blocks_when_subscribed_to_blocks() {
+    let mut subscriptions = Subscriptions::new();
+
+    subscriptions.add(Subscription::Blocks);
+
+    let block = Event::Block {
+      block_count: 1,
+      hash: String::from("hash"),
+      transactions: 0,
+    };
+
+    assert!(subscriptions.matches(&block));
+  }
+
+  #[test]
+  fn matches_only_the_status_of_the_subscribed_transactions() {
+    let mut subscriptions = Subscriptions::new();
+
+    subscriptions.add(Subscription::TransactionStatus {
+      id: String::from("a"),
+    });
+
+    let status = |id: &str| Event::TransactionStatus {
+      id: String::from(id),
+      status: TransactionStatus::Pending,
+    };
+
+    assert!(subscriptions.matches(&status("a")));
+    assert!(!subscriptions.matches(&status("b")));
+  }
+
+  #[test]
+  fn matches_only_the_balances_of_the_subscribed_accounts() {
+    let mut subscriptions = Subscriptions::new();
+
+    subscriptions.add(Subscription::Balances {
+      accounts: vec![String::from("a"), String::from("b")],
+    });
+
+    assert!(subscriptions.matches(&balance("a")));
+    assert!(subscriptions.matches(&balance("b")));
+    assert!(!subscriptions.matches(&balance("c")));
+  }
+
+  #[test]
+  fn events_are_tagged_when_serialized() {
+    let event = Event::TransactionStatus {
+      id: String::from("a"),
+      status: TransactionStatus::Included { block_count: 1 },
+    };
+
+    assert_eq!(
+      r#"{"event":"transaction_status","id":"a","status":{"status":"included","block_count":1}}"#,
+      serde_json::to_string(&event).unwrap()
+    );
+  }
+}
@@ -0,0 +1,168 @@
+//! Validators vote on checkpoint blocks, once validators holding 2/3
+//! of the stake voted for one it's final and the chain never reorgs past it.
+
+use crate::transaction::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+
+/// A block at a checkpoint height.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Checkpoint {
+  pub block_count: u128,
+  pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Vote {
+  pub validator: PublicKey,
+  pub checkpoint: Checkpoint,
+}
+
+impl Vote {
+  pub fn hash(&self) -> String {
+    let as_string = format!("{:?}", self);
+
+    format!("{:x}", sha2::Sha256::digest(as_string.as_bytes()))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignedVote {
+  pub signature: String,
+  pub vote: Vote,
+}
+
+/// Votes seen for each checkpoint and the highest one that became final.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Finality {
+  #[serde(with = "votes_as_list")]
+  votes: HashMap<Checkpoint, HashSet<PublicKey>>,
+  finalized: Option<Checkpoint>,
+}
+
+impl Finality {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns false if the validator already voted for the checkpoint.
+  pub fn add(&mut self, vote: &Vote) -> bool {
+    self
+      .votes
+      .entry(vote.checkpoint.clone())
+      .or_default()
+      .insert(vote.validator.clone())
+  }
+
+  pub fn voters(&self, checkpoint: &Checkpoint) -> impl Iterator<Item = &PublicKey> {
+    self.votes.get(checkpoint).into_iter().flatten()
+  }
+
+  /// Checkpoints with votes, lowest first.
+  pub fn checkpoints(&self) -> Vec<Checkpoint> {
+    let mut checkpoints: Vec<Checkpoint> = self.votes.keys().cloned().collect();
+
+    checkpoints.sort_by_key(|checkpoint| checkpoint.block_count);
+
+    checkpoints
+  }
+
+  pub fn has_voted(&self, validator: &str, checkpoint: &Checkpoint) -> bool {
+    self
+      .votes
+      .get(checkpoint)
+      .map(|voters| voters.contains(validator))
+      .unwrap_or(false)
+  }
+
+  /// Votes for checkpoints at or below the final one aren't needed anymore.
+  pub fn finalize(&mut self, checkpoint: Checkpoint) {
+    self
+      .votes
+      .retain(|voted, _| voted.block_count > checkpoint.block_count);
+    self.finalized = Some(checkpoint);
+  }
+
+  pub fn finalized(&self) -> Option<&Checkpoint> {
+    self.finalized.as_ref()
+  }
+
+  /// Block count of the last final checkpoint, genesis is always final.
+  pub fn finalized_height(&self) -> u128 {
+    self
+      .finalized
+      .as_ref()
+      .map(|checkpoint| checkpoint.block_count)
+      .unwrap_or(0)
+  }
+}
+
+/// JSON object keys must be strings, so checkpoints and their voters are saved as a list.
+mod votes_as_list {
+  use super::*;
+  use serde::{Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(
+    votes: &HashMap<Checkpoint, HashSet<PublicKey>>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(votes.iter())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<HashMap<Checkpoint, HashSet<PublicKey>>, D::Error> {
+    let votes: Vec<(Checkpoint, HashSet<PublicKey>)> = Vec::deserialize(deserializer)?;
+
+    Ok(votes.into_iter().collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_each_validator_once_per_checkpoint() {
+    let mut finality = Finality::new();
+
+    let checkpoint = Checkpoint {
+      block_count: 10,
+      hash: String::from("hash"),
+    };
+
+    let vote = |validator: &str| Vote {
+      validator: String::from(validator),
+      checkpoint: checkpoint.clone(),
+    };
+
+    assert!(finality.add(&vote("a")));
+    assert!(!finality.add(&vote("a")));
+    assert!(finality.add(&vote("b")));
+    assert_eq!(2, finality.voters(&checkpoint).count());
+    assert!(finality.has_voted("a", &checkpoint));
+
+    finality.finalize(checkpoint.clone());
+
+    assert_eq!(10, finality.finalized_height());
+    assert_eq!(0, finality.voters(&checkpoint).count());
+  }
+
+  #[test]
+  fn survives_a_round_trip_through_json() {
+    let mut finality = Finality::new();
+
+    finality.add(&Vote {
+      validator: String::from("a"),
+      checkpoint: Checkpoint {
+        block_count: 10,
+        hash: String::from("hash"),
+      },
+    });
+
+    let json = serde_json::to_string(&finality).unwrap();
+
+    assert_eq!(finality, serde_json::from_str(&json).unwrap());
+  }
+}
@@ -0,0 +1,156 @@
+use crate::transaction::PublicKey;
+use crate::wallet::SignedBlock;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Where a transaction is in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+  pub block_count: u128,
+  /// Position of the transaction in the block.
+  pub position: usize,
+}
+
+/// Finds transactions without scanning every block.
+///
+/// The chain rebuilds the index with itself on a reorg, so
+/// transactions from orphaned blocks disappear from it too.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransactionIndex {
+  locations: HashMap<String, Location>,
+  /// Transactions each account sent or received, oldest first.
+  accounts: HashMap<PublicKey, Vec<Location>>,
+  /// Transactions carrying each memo, oldest first.
+  memos: HashMap<String, Vec<Location>>,
+}
+
+impl TransactionIndex {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&mut self, signed_block: &SignedBlock) {
+    for (position, signed_transaction) in signed_block.block.transactions.iter().enumerate() {
+      let location = Location {
+        block_count: signed_block.block_count(),
+        position,
+      };
+
+      self
+        .locations
+        .insert(signed_transaction.transaction.id().clone(), location);
+
+      let mut accounts = signed_transaction.transaction.accounts();
+
+      // Transfers to yourself show up once.
+      accounts.sort_unstable();
+      accounts.dedup();
+
+      for account in accounts {
+        self
+          .accounts
+          .entry(account.clone())
+          .or_default()
+          .push(location);
+      }
+
+      if let Some(memo) = signed_transaction.transaction.memo() {
+        self.memos.entry(memo.clone()).or_default().push(location);
+      }
+    }
+  }
+
+  pub fn location(&self, id: &str) -> Option<Location> {
+    self.locations.get(id).copied()
+  }
+
+  pub fn account(&self, account: &str) -> &[Location] {
+    self.accounts.get(account).map(Vec::as_slice).unwrap_or(&[])
+  }
+
+  pub fn memo(&self, memo: &str) -> &[Location] {
+    self.memos.get(memo).map(Vec::as_slice).unwrap_or(&[])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::Block;
+  use crate::transaction::Transaction;
+  use crate::wallet::Wallet;
+
+  #[test]
+  fn indexes_transactions_by_id_and_account() {
+    let wallet = Wallet::new();
+
+    let transfer = |sender: &str, receiver: &str| {
+      wallet.sign_transaction(Transaction::transfer(
+        String::from(sender),
+        String::from(receiver),
+        10,
+      ))
+    };
+
+    let (a, b) = (transfer("alice", "bob"), transfer("bob", "bob"));
+
+    let block = wallet.sign_block(Block::new(
+      vec![a.clone(), b.clone()],
+      SignedBlock::genesis().hash(),
+      wallet.public_key(),
+      1,
+    ));
+
+    let mut index = TransactionIndex::new();
+
+    index.add(&block);
+
+    let first = Location {
+      block_count: 1,
+      position: 0,
+    };
+
+    let second = Location {
+      block_count: 1,
+      position: 1,
+    };
+
+    assert_eq!(Some(first), index.location(a.transaction.id()));
+    assert_eq!(Some(second), index.location(b.transaction.id()));
+    assert_eq!(None, index.location("unknown"));
+    assert_eq!(&[first], index.account("alice"));
+    assert_eq!(&[first, second], index.account("bob"));
+    assert!(index.account("carol").is_empty());
+  }
+
+  #[test]
+  fn indexes_transactions_by_memo() {
+    let wallet = Wallet::new();
+
+    let transfer = |memo: &str| {
+      wallet.sign_transaction(
+        Transaction::transfer(String::from("alice"), String::from("bob"), 10)
+          .with_memo(String::from(memo)),
+      )
+    };
+
+    let block = wallet.sign_block(Block::new(
+      vec![transfer("invoice-1"), transfer("invoice-2")],
+      SignedBlock::genesis().hash(),
+      wallet.public_key(),
+      1,
+    ));
+
+    let mut index = TransactionIndex::new();
+
+    index.add(&block);
+
+    let second = Location {
+      block_count: 1,
+      position: 1,
+    };
+
+    assert_eq!(&[second], index.memo("invoice-2"));
+    assert!(index.memo("invoice-3").is_empty());
+  }
+}
@@ -0,0 +1,214 @@
+use crate::wallet::SignedTransaction;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A change to the mempool.
+///
+/// Externally tagged, serde can't read `u128`s inside internally tagged enums.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalEntry {
+  Added { transaction: SignedTransaction },
+  Removed { hash: String },
+}
+
+/// Where the node writes down changes to its mempool as they happen,
+/// so pending transactions survive a crash.
+pub trait Journal: Debug + Send {
+  fn append(&mut self, entry: &JournalEntry) -> io::Result<()>;
+}
+
+/// Journal kept as one json entry per line.
+#[derive(Debug)]
+pub struct FileJournal {
+  file: File,
+}
+
+impl FileJournal {
+  /// Starts a journal at `path` holding only `transactions`,
+  /// which is how the journal is kept from growing forever.
+  pub fn create(path: PathBuf, transactions: &[SignedTransaction]) -> io::Result<Self> {
+    let temporary_path = path.with_extension("tmp");
+
+    let mut contents = Vec::new();
+
+    for transaction in transactions {
+      serde_json::to_writer(
+        &mut contents,
+        &JournalEntry::Added {
+          transaction: transaction.clone(),
+        },
+      )?;
+      contents.push(b'\n');
+    }
+
+    fs::write(&temporary_path, contents)?;
+
+    fs::rename(&temporary_path, &path)?;
+
+    let file = OpenOptions::new().append(true).open(&path)?;
+
+    Ok(Self { file })
+  }
+
+  /// Returns the pending transactions the journal at `path` describes,
+  /// in the order they were added.
+  ///
+  /// A crash may leave the last line half written, lines that
+  /// can't be read are skipped.
+  pub fn replay(path: &Path) -> io::Result<Vec<SignedTransaction>> {
+    let file = match File::open(path) {
+      Ok(file) => file,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(error) => return Err(error),
+    };
+
+    let mut transactions: Vec<SignedTransaction> = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+      let line = line?;
+
+      match serde_json::from_str(&line) {
+        Err(error) => warn!(%error, path = ?path, "skipping unreadable journal entry"),
+        Ok(JournalEntry::Added { transaction }) => {
+          if !transactions.contains(&transaction) {
+            transactions.push(transaction);
+          }
+        }
+        Ok(JournalEntry::Removed { hash }) => {
+          transactions.retain(|transaction| transaction.transaction.hash() != hash)
+        }
+      }
+    }
+
+    Ok(transactions)
+  }
+}
+
+impl Journal for FileJournal {
+  fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+
+    line.push(b'\n');
+
+    self.file.write_all(&line)?;
+
+    self.file.flush()
+  }
+}
+
+/// Keeps entries in memory so tests can look at them.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct MemoryJournal {
+  pub entries: std::sync::Arc<std::sync::Mutex<Vec<JournalEntry>>>,
+}
+
+#[cfg(test)]
+impl Journal for MemoryJournal {
+  fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+    self.entries.lock().unwrap().push(entry.clone());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::transaction::Transaction;
+  use crate::wallet::Wallet;
+  use uuid::Uuid;
+
+  fn path() -> PathBuf {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+
+    fs::create_dir_all(&dir).unwrap();
+
+    dir.join("mempool.log")
+  }
+
+  fn transaction(amount: i64) -> SignedTransaction {
+    Wallet::new().sign_transaction(Transaction::transfer(
+      String::from("sender_public_key"),
+      String::from("receiver_public_key"),
+      amount,
+    ))
+  }
+
+  #[test]
+  fn replaying_a_missing_journal_returns_no_transactions() {
+    assert_eq!(
+      Vec::<SignedTransaction>::new(),
+      FileJournal::replay(&path()).unwrap()
+    );
+  }
+
+  #[test]
+  fn replays_additions_and_removals() {
+    let path = path();
+
+    let (a, b, c) = (transaction(1), transaction(2), transaction(3));
+
+    let mut journal = FileJournal::create(path.clone(), std::slice::from_ref(&a)).unwrap();
+
+    journal
+      .append(&JournalEntry::Added {
+        transaction: b.clone(),
+      })
+      .unwrap();
+    journal
+      .append(&JournalEntry::Added {
+        transaction: c.clone(),
+      })
+      .unwrap();
+    journal
+      .append(&JournalEntry::Removed {
+        hash: b.transaction.hash(),
+      })
+      .unwrap();
+
+    assert_eq!(vec![a, c], FileJournal::replay(&path).unwrap());
+  }
+
+  #[test]
+  fn skips_half_written_entries() {
+    let path = path();
+
+    let a = transaction(1);
+
+    FileJournal::create(path.clone(), std::slice::from_ref(&a)).unwrap();
+
+    OpenOptions::new()
+      .append(true)
+      .open(&path)
+      .unwrap()
+      .write_all(br#"{"added":{"transac"#)
+      .unwrap();
+
+    assert_eq!(vec![a], FileJournal::replay(&path).unwrap());
+  }
+
+  #[test]
+  fn creating_a_journal_compacts_it() {
+    let path = path();
+
+    let (a, b) = (transaction(1), transaction(2));
+
+    let mut journal = FileJournal::create(path.clone(), &[a.clone(), b.clone()]).unwrap();
+
+    journal
+      .append(&JournalEntry::Removed {
+        hash: a.transaction.hash(),
+      })
+      .unwrap();
+
+    FileJournal::create(path.clone(), std::slice::from_ref(&b)).unwrap();
+
+    assert_eq!(1, fs::read_to_string(&path).unwrap().lines().count());
+    assert_eq!(vec![b], FileJournal::replay(&path).unwrap());
+  }
+}
@@ -0,0 +1,37 @@
+// Errors carry the offending block or transaction so callers can report it.
+#![allow(clippy::result_large_err)]
+// The original tests compare against literal bools.
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
+pub mod account;
+pub mod api_error;
+pub mod block;
+pub mod chain;
+pub mod clock;
+pub mod config;
+pub mod consensus;
+pub mod controllers;
+pub mod epoch;
+pub mod events;
+pub mod finality;
+pub mod index;
+pub mod journal;
+pub mod metrics;
+pub mod network;
+pub mod node;
+pub mod peer_manager;
+pub mod peers;
+pub mod receipt;
+pub mod rpc;
+#[cfg(test)]
+mod simulation;
+pub mod storage;
+pub mod telemetry;
+pub mod transaction;
+pub mod viewmodel;
+pub mod vm;
+pub mod wallet;
+pub mod websocket;
+
+#[macro_use]
+extern crate lazy_static;
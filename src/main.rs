@@ -1,23 +1,16 @@
-mod account;
-mod block;
-mod chain;
-mod controllers;
-mod node;
-mod transaction;
-mod viewmodel;
-mod wallet;
-
-use node::Node;
-
-use std::env;
+use blockchain::{
+  api_error, chain::Chain, config::Config, controllers, network, node::Node, storage::Storage,
+  telemetry,
+};
 
 use actix_web::{web, App, HttpServer};
+use libp2p::identity;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+use tracing_actix_web::TracingLogger;
 
 use std::sync::{Arc, Mutex};
 
-#[macro_use]
-extern crate lazy_static;
-
 fn main() {
   actix_web::rt::System::with_tokio_rt(|| {
     tokio::runtime::Builder::new_multi_thread()
@@ -31,18 +24,100 @@ fn main() {
 }
 
 async fn async_main() {
-  let node = web::Data::new(Arc::new(Mutex::new(Node::new().await.unwrap())));
+  let config = Config::from_env().unwrap();
+
+  telemetry::init(&config);
+
+  let storage = match Storage::open(config.data_dir.clone()) {
+    Ok(storage) => Some(storage),
+    Err(error) => {
+      error!(%error, data_dir = ?config.data_dir, "couldn't open the data directory, state won't be saved");
+      None
+    }
+  };
+
+  let node = match storage
+    .as_ref()
+    .map(|storage| storage.load_node(Chain::new()))
+  {
+    None => Node::new(),
+    Some(Ok(node)) => node,
+    Some(Err(error)) => panic!("couldn't load the saved state: {:?}", error),
+  };
+
+  let identity = match storage.as_ref().map(Storage::load_identity) {
+    None => identity::Keypair::generate_ed25519(),
+    Some(Ok(identity)) => identity,
+    Some(Err(error)) => panic!("couldn't load the network identity: {:?}", error),
+  };
+
+  let node = Arc::new(Mutex::new(node));
+
+  node.lock().unwrap().network_mut().storage_open = storage.is_some();
 
-  let port = env::args().nth(1).unwrap();
+  let (shutdown, shutdown_signal) = oneshot::channel();
+
+  let network = tokio::spawn(network::run(
+    Arc::clone(&node),
+    config.clone(),
+    identity,
+    shutdown_signal,
+  ));
+
+  let data = web::Data::new(Arc::clone(&node));
+
+  let port = config.port;
+
+  let config = web::Data::new(config);
 
   HttpServer::new(move || {
     App::new()
-      .app_data(node.clone())
+      .wrap(TracingLogger::default())
+      .app_data(data.clone())
+      .app_data(config.clone())
+      .app_data(web::JsonConfig::default().error_handler(api_error::json_error_handler))
+      .app_data(web::QueryConfig::default().error_handler(api_error::query_error_handler))
       .service(controllers::add_transaction)
+      .service(controllers::peers)
+      .service(controllers::subscribe)
+      .service(controllers::json_rpc)
+      .service(controllers::metrics)
+      .service(controllers::health)
+      .service(controllers::ready)
+      .service(controllers::node_info)
+      .service(controllers::supply)
+      .service(controllers::finality)
+      .service(controllers::epoch)
+      .service(controllers::account_transactions)
+      .service(controllers::memo_transactions)
+      .service(controllers::contract)
+      .service(controllers::receipt)
+      .service(controllers::assets)
+      .service(controllers::account_assets)
   })
   .bind(format!("127.0.0.1:{}", port))
   .unwrap()
   .run()
   .await
   .unwrap();
+
+  // The server stops accepting requests on SIGINT/SIGTERM and waits for
+  // the ones in flight, the network goes next so nothing changes while saving.
+  info!("shutting down");
+
+  let _ = shutdown.send(());
+
+  if let Err(error) = network.await {
+    error!(%error, "network task failed");
+  }
+
+  if let Some(storage) = storage {
+    // Taking the lock waits for a block that is being applied.
+    let node = node.lock().unwrap();
+
+    match storage.save_node(&node) {
+      Ok(()) => info!(block_count = %node.chain().tip().block_count(), "state saved"),
+      Err(error) => error!(%error, "couldn't save state"),
+    }
+  }
 }
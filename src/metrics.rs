@@ -0,0 +1,138 @@
+//! Prometheus metrics, served at `/metrics`.
+
+use prometheus::{
+  Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+  TextEncoder,
+};
+
+lazy_static! {
+  pub static ref METRICS: Metrics = Metrics::new();
+}
+
+pub struct Metrics {
+  registry: Registry,
+  pub chain_height: IntGauge,
+  pub mempool_transactions: IntGauge,
+  pub transactions_accepted: IntCounter,
+  /// Labeled by `reason`.
+  pub transactions_rejected: IntCounterVec,
+  pub block_apply_seconds: Histogram,
+  pub peers: IntGauge,
+  /// Labeled by `direction` (in or out) and `topic`.
+  pub gossip_messages: IntCounterVec,
+  pub sync_requests: IntCounter,
+  /// Highest block count announced by a peer, the node is synced once
+  /// `chain_height` catches up with it.
+  pub sync_target_height: IntGauge,
+}
+
+impl Metrics {
+  fn new() -> Self {
+    let registry = Registry::new();
+
+    let metrics = Self {
+      chain_height: IntGauge::new("chain_height", "Number of blocks after genesis").unwrap(),
+      mempool_transactions: IntGauge::new(
+        "mempool_transactions",
+        "Transactions waiting to be forged",
+      )
+      .unwrap(),
+      transactions_accepted: IntCounter::new(
+        "transactions_accepted_total",
+        "Transactions added to the pool",
+      )
+      .unwrap(),
+      transactions_rejected: IntCounterVec::new(
+        Opts::new(
+          "transactions_rejected_total",
+          "Transactions not added to the pool",
+        ),
+        &["reason"],
+      )
+      .unwrap(),
+      block_apply_seconds: Histogram::with_opts(HistogramOpts::new(
+        "block_apply_seconds",
+        "Time taken to add a block to the chain",
+      ))
+      .unwrap(),
+      peers: IntGauge::new("peers", "Connected peers").unwrap(),
+      gossip_messages: IntCounterVec::new(
+        Opts::new(
+          "gossip_messages_total",
+          "Gossip messages received and published",
+        ),
+        &["direction", "topic"],
+      )
+      .unwrap(),
+      sync_requests: IntCounter::new("sync_requests_total", "Blocks requested from peers").unwrap(),
+      sync_target_height: IntGauge::new(
+        "sync_target_height",
+        "Highest block count announced by peers",
+      )
+      .unwrap(),
+      registry,
+    };
+
+    let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+      Box::new(metrics.chain_height.clone()),
+      Box::new(metrics.mempool_transactions.clone()),
+      Box::new(metrics.transactions_accepted.clone()),
+      Box::new(metrics.transactions_rejected.clone()),
+      Box::new(metrics.block_apply_seconds.clone()),
+      Box::new(metrics.peers.clone()),
+      Box::new(metrics.gossip_messages.clone()),
+      Box::new(metrics.sync_requests.clone()),
+      Box::new(metrics.sync_target_height.clone()),
+    ];
+
+    for collector in collectors {
+      metrics.registry.register(collector).unwrap();
+    }
+
+    metrics
+  }
+
+  /// Renders every metric in the prometheus text format.
+  pub fn gather(&self) -> String {
+    let mut buffer = Vec::new();
+
+    TextEncoder::new()
+      .encode(&self.registry.gather(), &mut buffer)
+      .unwrap();
+
+    String::from_utf8(buffer).unwrap()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn gathers_every_metric() {
+    METRICS
+      .transactions_rejected
+      .with_label_values(&["invalid_signature"])
+      .inc();
+    METRICS
+      .gossip_messages
+      .with_label_values(&["in", "blocks"])
+      .inc();
+
+    let output = METRICS.gather();
+
+    for name in [
+      "chain_height",
+      "mempool_transactions",
+      "transactions_accepted_total",
+      "transactions_rejected_total{reason=\"invalid_signature\"}",
+      "block_apply_seconds_bucket",
+      "peers",
+      "gossip_messages_total{direction=\"in\",topic=\"blocks\"}",
+      "sync_requests_total",
+      "sync_target_height",
+    ] {
+      assert!(output.contains(name), "{} is missing", name);
+    }
+  }
+}
@@ -0,0 +1,754 @@
+use crate::config::Config;
+use crate::metrics::METRICS;
+use crate::node::{Message, Node, NodeError, Outgoing, Recipient};
+use crate::peers::{self, PeerStore};
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use libp2p::{
+  core::{upgrade, ConnectedPoint, ProtocolName},
+  gossipsub::{
+    Gossipsub, GossipsubConfigBuilder, GossipsubEvent, GossipsubMessage, IdentTopic,
+    MessageAcceptance, MessageAuthenticity, MessageId, PeerScoreParams, PeerScoreThresholds,
+    TopicScoreParams,
+  },
+  identity,
+  kad::{record::store::MemoryStore, Kademlia, KademliaEvent},
+  mdns::{Mdns, MdnsEvent},
+  mplex, noise,
+  request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+    RequestResponseEvent, RequestResponseMessage, ResponseChannel,
+  },
+  swarm::{NetworkBehaviourEventProcess, Swarm, SwarmBuilder, SwarmEvent},
+  tcp::TokioTcpConfig,
+  NetworkBehaviour, PeerId, Transport,
+};
+
+use std::io::BufRead;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, info_span, warn};
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+
+/// How often the node announces its tip to its peers.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often we ask the DHT for new peers.
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync responses carry every block we have, so they can get large.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long a peer has to send its chain after we asked for it.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Score lost for each invalid message, squared by gossipsub.
+/// Three invalid messages are enough to get a peer graylisted.
+const INVALID_MESSAGE_WEIGHT: f64 = -10.0;
+
+lazy_static! {
+  /// Free form text typed on stdin.
+  static ref NETWORK_TOPIC: IdentTopic = IdentTopic::new("network");
+  static ref TRANSACTIONS_TOPIC: IdentTopic = IdentTopic::new("transactions");
+  static ref BLOCKS_TOPIC: IdentTopic = IdentTopic::new("blocks");
+  static ref VOTES_TOPIC: IdentTopic = IdentTopic::new("votes");
+}
+
+/// Sync messages are never gossiped, they go straight to the peer that asked.
+fn topic(message: &Message) -> Option<&'static IdentTopic> {
+  match message {
+    Message::Transaction { .. } => Some(&TRANSACTIONS_TOPIC),
+    Message::Block(_) | Message::Tip(_) => Some(&BLOCKS_TOPIC),
+    Message::BlocksRequest { .. } | Message::Blocks(_) => None,
+    Message::Vote(_) => Some(&VOTES_TOPIC),
+  }
+}
+
+#[derive(Debug, Clone)]
+struct SyncProtocol;
+
+impl ProtocolName for SyncProtocol {
+  fn protocol_name(&self) -> &[u8] {
+    b"/blockchain/sync/1"
+  }
+}
+
+/// Requests and responses are length prefixed json encoded messages.
+#[derive(Debug, Clone)]
+struct SyncCodec;
+
+async fn read_message<T: AsyncRead + Unpin + Send>(io: &mut T) -> io::Result<Message> {
+  let bytes = upgrade::read_length_prefixed(io, MAX_MESSAGE_SIZE).await?;
+
+  serde_json::from_slice(&bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+async fn write_message<T: AsyncWrite + Unpin + Send>(
+  io: &mut T,
+  message: Message,
+) -> io::Result<()> {
+  upgrade::write_length_prefixed(io, serde_json::to_vec(&message)?).await?;
+
+  io.close().await
+}
+
+#[async_trait]
+impl RequestResponseCodec for SyncCodec {
+  type Protocol = SyncProtocol;
+  type Request = Message;
+  type Response = Message;
+
+  async fn read_request<T>(&mut self, _: &SyncProtocol, io: &mut T) -> io::Result<Message>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    read_message(io).await
+  }
+
+  async fn read_response<T>(&mut self, _: &SyncProtocol, io: &mut T) -> io::Result<Message>
+  where
+    T: AsyncRead + Unpin + Send,
+  {
+    read_message(io).await
+  }
+
+  async fn write_request<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+    request: Message,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    write_message(io, request).await
+  }
+
+  async fn write_response<T>(
+    &mut self,
+    _: &SyncProtocol,
+    io: &mut T,
+    response: Message,
+  ) -> io::Result<()>
+  where
+    T: AsyncWrite + Unpin + Send,
+  {
+    write_message(io, response).await
+  }
+}
+
+fn sync() -> RequestResponse<SyncCodec> {
+  let mut config = RequestResponseConfig::default();
+  config.set_request_timeout(SYNC_TIMEOUT);
+
+  RequestResponse::new(
+    SyncCodec,
+    std::iter::once((SyncProtocol, ProtocolSupport::Full)),
+    config,
+  )
+}
+
+/// Transactions and blocks are identified by their hash so gossipsub
+/// drops copies of them, everything else by who published it.
+fn message_id(message: &GossipsubMessage) -> MessageId {
+  let id = serde_json::from_slice::<Message>(&message.data)
+    .ok()
+    .and_then(|payload| payload.id());
+
+  match id {
+    Some(id) => MessageId::from(id),
+    None => MessageId::from(format!(
+      "{}{}",
+      message
+        .source
+        .map(|peer| peer.to_base58())
+        .unwrap_or_default(),
+      message.sequence_number.unwrap_or_default()
+    )),
+  }
+}
+
+/// Messages the node rejected are not forwarded and count against the peer that sent them.
+/// Messages from peers we are ignoring are dropped without counting against them again.
+fn acceptance(result: &Result<(), NodeError>) -> MessageAcceptance {
+  match result {
+    Ok(()) => MessageAcceptance::Accept,
+    Err(NodeError::PeerBanned(_)) | Err(NodeError::RateLimited(_)) => MessageAcceptance::Ignore,
+    Err(_) => MessageAcceptance::Reject,
+  }
+}
+
+fn gossipsub(keys: identity::Keypair) -> Gossipsub {
+  let config = GossipsubConfigBuilder::default()
+    .validate_messages()
+    .message_id_fn(message_id)
+    .max_transmit_size(MAX_MESSAGE_SIZE)
+    .build()
+    .expect("valid gossipsub config");
+
+  let mut gossipsub =
+    Gossipsub::new(MessageAuthenticity::Signed(keys), config).expect("valid gossipsub behaviour");
+
+  let mut params = PeerScoreParams::default();
+
+  for topic in [&*TRANSACTIONS_TOPIC, &*BLOCKS_TOPIC, &*VOTES_TOPIC] {
+    params.topics.insert(
+      topic.hash(),
+      TopicScoreParams {
+        topic_weight: 1.0,
+        // We don't expect peers to keep the mesh busy, only to not lie to us.
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: INVALID_MESSAGE_WEIGHT,
+        ..TopicScoreParams::default()
+      },
+    );
+  }
+
+  gossipsub
+    .with_peer_score(params, PeerScoreThresholds::default())
+    .expect("valid peer score params");
+
+  for topic in [
+    &*NETWORK_TOPIC,
+    &*TRANSACTIONS_TOPIC,
+    &*BLOCKS_TOPIC,
+    &*VOTES_TOPIC,
+  ] {
+    gossipsub.subscribe(topic).unwrap();
+  }
+
+  gossipsub
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(event_process = true)]
+struct NodeBehaviour {
+  gossipsub: Gossipsub,
+  sync: RequestResponse<SyncCodec>,
+  mdns: Mdns,
+  kademlia: Kademlia<MemoryStore>,
+  #[behaviour(ignore)]
+  node: Arc<Mutex<Node>>,
+  /// Sync requests the node is answering, dropped if it didn't answer by the next flush.
+  #[behaviour(ignore)]
+  pending_responses: HashMap<PeerId, ResponseChannel<Message>>,
+  /// Peers we discovered but may not be connected to yet.
+  #[behaviour(ignore)]
+  discovered: VecDeque<PeerId>,
+}
+
+impl NetworkBehaviourEventProcess<GossipsubEvent> for NodeBehaviour {
+  fn inject_event(&mut self, event: GossipsubEvent) {
+    if let GossipsubEvent::Message {
+      propagation_source,
+      message_id,
+      message,
+    } = event
+    {
+      let _span = info_span!(
+        "gossip_message",
+        peer = %propagation_source,
+        topic = %message.topic,
+      )
+      .entered();
+
+      METRICS
+        .gossip_messages
+        .with_label_values(&["in", message.topic.as_str()])
+        .inc();
+
+      let acceptance = if message.topic == NETWORK_TOPIC.hash() {
+        info!(
+          data = %String::from_utf8_lossy(&message.data),
+          source = ?message.source,
+          "received text",
+        );
+        MessageAcceptance::Accept
+      } else {
+        match serde_json::from_slice::<Message>(&message.data) {
+          Err(error) => {
+            warn!(%error, "undecodable message");
+            MessageAcceptance::Reject
+          }
+          Ok(payload) => {
+            let result = self
+              .node
+              .lock()
+              .unwrap()
+              .message(&propagation_source.to_base58(), payload);
+
+            if let Err(error) = &result {
+              debug!(?error, "message rejected");
+            }
+
+            acceptance(&result)
+          }
+        }
+      };
+
+      // Only fails if the message already left the cache, nothing left to do then.
+      let _ = self.gossipsub.report_message_validation_result(
+        &message_id,
+        &propagation_source,
+        acceptance,
+      );
+    }
+  }
+}
+
+impl NetworkBehaviourEventProcess<RequestResponseEvent<Message, Message>> for NodeBehaviour {
+  fn inject_event(&mut self, event: RequestResponseEvent<Message, Message>) {
+    match event {
+      RequestResponseEvent::Message { peer, message } => {
+        let _span = info_span!("sync_message", %peer).entered();
+
+        let payload = match message {
+          RequestResponseMessage::Request {
+            request, channel, ..
+          } => {
+            self.pending_responses.insert(peer, channel);
+            request
+          }
+          RequestResponseMessage::Response { response, .. } => response,
+        };
+
+        METRICS
+          .gossip_messages
+          .with_label_values(&["in", "sync"])
+          .inc();
+
+        let result = self
+          .node
+          .lock()
+          .unwrap()
+          .message(&peer.to_base58(), payload);
+
+        if let Err(error) = &result {
+          debug!(?error, "sync message rejected");
+        }
+      }
+      RequestResponseEvent::OutboundFailure { peer, error, .. } => {
+        warn!(%peer, ?error, "sync request failed");
+      }
+      RequestResponseEvent::InboundFailure { peer, error, .. } => {
+        debug!(%peer, ?error, "couldn't answer sync request");
+      }
+      RequestResponseEvent::ResponseSent { .. } => {}
+    }
+  }
+}
+
+impl NetworkBehaviourEventProcess<MdnsEvent> for NodeBehaviour {
+  fn inject_event(&mut self, event: MdnsEvent) {
+    if let MdnsEvent::Discovered(list) = event {
+      for (peer, address) in list {
+        self.kademlia.add_address(&peer, address);
+        self.discovered.push_back(peer);
+      }
+    }
+  }
+}
+
+impl NetworkBehaviourEventProcess<KademliaEvent> for NodeBehaviour {
+  fn inject_event(&mut self, event: KademliaEvent) {
+    if let KademliaEvent::RoutingUpdated { peer, .. } = event {
+      self.discovered.push_back(peer);
+    }
+  }
+}
+
+async fn swarm(node: Arc<Mutex<Node>>, peer_id_keys: identity::Keypair) -> Swarm<NodeBehaviour> {
+  let peer_id = PeerId::from(peer_id_keys.public());
+  info!(%peer_id, "starting swarm");
+
+  node.lock().unwrap().network_mut().peer_id = Some(peer_id.to_base58());
+
+  let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
+    .into_authentic(&peer_id_keys)
+    .expect("couldn't sign libp2p-noise static DH keypair");
+
+  let transport = TokioTcpConfig::new()
+    .nodelay(true)
+    .upgrade(upgrade::Version::V1)
+    .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
+    .multiplex(mplex::MplexConfig::new())
+    .boxed();
+
+  let mdns = Mdns::new(Default::default()).await.unwrap();
+  let behaviour = NodeBehaviour {
+    gossipsub: gossipsub(peer_id_keys),
+    sync: sync(),
+    mdns,
+    kademlia: Kademlia::new(peer_id, MemoryStore::new(peer_id)),
+    node,
+    pending_responses: HashMap::new(),
+    discovered: VecDeque::new(),
+  };
+
+  let mut swarm = SwarmBuilder::new(transport, behaviour, peer_id)
+    .executor(Box::new(|fut| {
+      tokio::spawn(fut);
+    }))
+    .build();
+
+  swarm
+    .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
+    .unwrap();
+
+  swarm
+}
+
+/// Sends the messages produced by the node to the network.
+fn flush(swarm: &mut Swarm<NodeBehaviour>) {
+  let outgoing = swarm.behaviour().node.lock().unwrap().outgoing();
+
+  for Outgoing { recipient, message } in outgoing {
+    match (recipient, topic(&message)) {
+      (Recipient::Peer(peer), _) => send_to_peer(swarm, &peer, message),
+      (Recipient::All, Some(topic)) => {
+        METRICS
+          .gossip_messages
+          .with_label_values(&["out", topic.hash().as_str()])
+          .inc();
+
+        // Publishing fails for messages gossipsub already forwarded
+        // and when we have no peers, neither is a problem.
+        let _ = swarm
+          .behaviour_mut()
+          .gossipsub
+          .publish(topic.clone(), serde_json::to_vec(&message).unwrap());
+      }
+      (Recipient::All, None) => warn!(?message, "sync messages can't be broadcast"),
+    }
+  }
+
+  // Requests the node chose not to answer, e.g. from rate limited peers.
+  swarm.behaviour_mut().pending_responses.clear();
+}
+
+/// Chains answer the peer's pending sync request, anything else is a new request.
+fn send_to_peer(swarm: &mut Swarm<NodeBehaviour>, peer: &str, message: Message) {
+  let peer = match peer.parse::<PeerId>() {
+    Ok(peer) => peer,
+    Err(error) => {
+      warn!(%peer, ?error, "invalid peer id");
+      return;
+    }
+  };
+
+  METRICS
+    .gossip_messages
+    .with_label_values(&["out", "sync"])
+    .inc();
+
+  let behaviour = swarm.behaviour_mut();
+
+  if !matches!(message, Message::Blocks(_)) {
+    behaviour.sync.send_request(&peer, message);
+    return;
+  }
+
+  match behaviour.pending_responses.remove(&peer) {
+    // Fails if the peer went away in the meantime.
+    Some(channel) => {
+      let _ = behaviour.sync.send_response(channel, message);
+    }
+    None => debug!(%peer, "no sync request to answer"),
+  }
+}
+
+/// Disconnects from peers the node banned and lets them back in once the ban is over.
+fn enforce_bans(swarm: &mut Swarm<NodeBehaviour>) {
+  let (banned, unbanned) = {
+    let mut node = swarm.behaviour().node.lock().unwrap();
+    let peers = node.peers_mut();
+    (peers.banned(), peers.unbanned())
+  };
+
+  for peer in banned.iter().filter_map(|peer| peer.parse::<PeerId>().ok()) {
+    warn!(%peer, "banning peer");
+    swarm.ban_peer_id(peer);
+  }
+
+  for peer in unbanned
+    .iter()
+    .filter_map(|peer| peer.parse::<PeerId>().ok())
+  {
+    swarm.unban_peer_id(peer);
+  }
+}
+
+/// Dials peers found through mdns or the DHT that we aren't connected to.
+fn dial_discovered(swarm: &mut Swarm<NodeBehaviour>) {
+  while let Some(peer) = swarm.behaviour_mut().discovered.pop_front() {
+    if !swarm.is_connected(&peer) {
+      if let Err(error) = swarm.dial(&peer) {
+        warn!(%peer, ?error, "couldn't dial peer");
+      }
+    }
+  }
+}
+
+/// Dials the bootstrap peers and the peers we were connected to before.
+fn bootstrap(swarm: &mut Swarm<NodeBehaviour>, config: &Config, peer_store: &PeerStore) {
+  let known_peers = peer_store
+    .peers()
+    .into_iter()
+    .map(|(peer, address)| (Some(peer), address));
+
+  let bootstrap_peers =
+    config
+      .bootstrap_peers
+      .iter()
+      .map(|address| match peers::split_peer_id(address.clone()) {
+        Some((peer, address)) => (Some(peer), address),
+        None => (None, address.clone()),
+      });
+
+  for (peer, address) in bootstrap_peers.chain(known_peers) {
+    if let Some(peer) = peer {
+      swarm
+        .behaviour_mut()
+        .kademlia
+        .add_address(&peer, address.clone());
+    }
+
+    if let Err(error) = swarm.dial_addr(address.clone()) {
+      warn!(%address, ?error, "couldn't dial address");
+    }
+  }
+
+  // Fails when no peer is known yet, mdns may still find some.
+  let _ = swarm.behaviour_mut().kademlia.bootstrap();
+}
+
+/// Remembers peers we could dial and forgets the ones we can't reach anymore.
+fn track_peer(peer_store: &mut PeerStore, event: &SwarmEvent<(), impl std::fmt::Debug>) {
+  let changed = match event {
+    SwarmEvent::ConnectionEstablished {
+      peer_id,
+      endpoint: ConnectedPoint::Dialer { address },
+      ..
+    } => peer_store.insert(*peer_id, address.clone()),
+    SwarmEvent::UnreachableAddr {
+      peer_id,
+      attempts_remaining: 0,
+      ..
+    } => peer_store.remove(peer_id),
+    _ => false,
+  };
+
+  if changed {
+    if let Err(error) = peer_store.save() {
+      error!(%error, "couldn't save known peers");
+    }
+  }
+}
+
+/// Reads stdin on its own thread.
+///
+/// Reading blocks and can't be interrupted, a thread nobody waits for
+/// keeps it from holding up shutdown. The channel closes when stdin does.
+fn stdin_lines() -> mpsc::UnboundedReceiver<std::io::Result<String>> {
+  let (sender, receiver) = mpsc::unbounded_channel();
+
+  std::thread::spawn(move || {
+    for line in std::io::stdin().lock().lines() {
+      let failed = line.is_err();
+
+      if sender.send(line).is_err() || failed {
+        break;
+      }
+    }
+  });
+
+  receiver
+}
+
+/// Says goodbye to our peers, nothing we publish after this is delivered.
+fn disconnect(swarm: &mut Swarm<NodeBehaviour>) {
+  let peers: Vec<PeerId> = swarm
+    .behaviour()
+    .node
+    .lock()
+    .unwrap()
+    .peers()
+    .peers()
+    .keys()
+    .filter_map(|peer| peer.parse().ok())
+    .collect();
+
+  for peer in peers {
+    if swarm.is_connected(&peer) {
+      let _ = swarm.disconnect_peer_id(peer);
+    }
+  }
+}
+
+/// Connects the node to its peers as `identity` and drives it until `shutdown` fires.
+pub async fn run(
+  node: Arc<Mutex<Node>>,
+  config: Config,
+  identity: identity::Keypair,
+  mut shutdown: oneshot::Receiver<()>,
+) {
+  let mut swarm = swarm(node, identity).await;
+
+  let path = config.data_dir.join("peers.json");
+
+  // Peers are found again through bootstrap nodes and discovery.
+  let mut peer_store = match PeerStore::load(path.clone()) {
+    Ok(peer_store) => peer_store,
+    Err(error) => {
+      error!(%error, ?path, "couldn't load known peers, starting without them");
+      PeerStore::empty(path)
+    }
+  };
+
+  bootstrap(&mut swarm, &config, &peer_store);
+
+  let mut stdin = stdin_lines();
+
+  // Reading stdin is optional, the node keeps running when there is none.
+  let mut stdin_open = true;
+
+  let mut ticks = tokio::time::interval(TICK_INTERVAL);
+
+  let mut discovery = tokio::time::interval(DISCOVERY_INTERVAL);
+
+  loop {
+    tokio::select! {
+      _ = &mut shutdown => break,
+      line = stdin.recv(), if stdin_open => match line {
+        Some(Ok(line)) => {
+          let _ = swarm.behaviour_mut().gossipsub.publish(NETWORK_TOPIC.clone(), line.as_bytes());
+        }
+        None => {
+          debug!("stdin closed");
+          stdin_open = false;
+        }
+        Some(Err(error)) => {
+          warn!(%error, "couldn't read stdin");
+          stdin_open = false;
+        }
+      },
+      _ = ticks.tick() => {
+        swarm.behaviour().node.lock().unwrap().tick();
+      }
+      _ = discovery.tick() => {
+        let _ = swarm.behaviour_mut().kademlia.bootstrap();
+      }
+      event = swarm.select_next_some() => {
+        track_peer(&mut peer_store, &event);
+
+        METRICS.peers.set(swarm.network_info().num_peers() as i64);
+
+        match event {
+          SwarmEvent::NewListenAddr { address, .. } => {
+            info!(%address, "listening");
+            let mut node = swarm.behaviour().node.lock().unwrap();
+            node.network_mut().listen_addresses.push(address.to_string());
+          }
+          SwarmEvent::ExpiredListenAddr { address, .. } => {
+            let mut node = swarm.behaviour().node.lock().unwrap();
+            node
+              .network_mut()
+              .listen_addresses
+              .retain(|listen_address| *listen_address != address.to_string());
+          }
+          _ => {}
+        }
+      }
+    }
+
+    enforce_bans(&mut swarm);
+    dial_discovered(&mut swarm);
+    flush(&mut swarm);
+  }
+
+  // Whatever the node still wanted to say, e.g. a block it just forged.
+  flush(&mut swarm);
+
+  disconnect(&mut swarm);
+
+  if let Err(error) = peer_store.save() {
+    error!(%error, "couldn't save known peers");
+  }
+
+  info!("network stopped");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::wallet::SignedBlock;
+
+  fn gossipsub_message(data: Vec<u8>) -> GossipsubMessage {
+    GossipsubMessage {
+      source: None,
+      data,
+      sequence_number: Some(7),
+      topic: BLOCKS_TOPIC.hash(),
+    }
+  }
+
+  #[test]
+  fn blocks_are_identified_by_their_hash() {
+    let signed_block = SignedBlock::genesis();
+
+    let message = Message::Block(signed_block.clone());
+
+    let actual = message_id(&gossipsub_message(serde_json::to_vec(&message).unwrap()));
+
+    assert_eq!(MessageId::from(signed_block.hash()), actual);
+  }
+
+  #[test]
+  fn other_messages_are_identified_by_their_sequence_number() {
+    let message = Message::BlocksRequest { from: 1 };
+
+    let actual = message_id(&gossipsub_message(serde_json::to_vec(&message).unwrap()));
+
+    assert_eq!(MessageId::from("7"), actual);
+  }
+
+  #[test]
+  fn messages_rejected_by_the_node_are_rejected_by_gossipsub() {
+    let rejected = Err(NodeError::InvalidBlockSignature(SignedBlock::genesis()));
+
+    assert!(matches!(acceptance(&rejected), MessageAcceptance::Reject));
+    assert!(matches!(acceptance(&Ok(())), MessageAcceptance::Accept));
+
+    let banned = Err(NodeError::PeerBanned(String::from("peer")));
+
+    assert!(matches!(acceptance(&banned), MessageAcceptance::Ignore));
+  }
+
+  #[test]
+  fn messages_are_published_on_the_topic_of_their_kind() {
+    assert_eq!(
+      Some(BLOCKS_TOPIC.hash()),
+      topic(&Message::Block(SignedBlock::genesis())).map(IdentTopic::hash)
+    );
+    assert_eq!(
+      None,
+      topic(&Message::BlocksRequest { from: 1 }).map(IdentTopic::hash)
+    );
+  }
+
+  #[test]
+  fn tip_announcements_are_not_mistaken_for_copies() {
+    let message = Message::Tip(SignedBlock::genesis());
+
+    let actual = message_id(&gossipsub_message(serde_json::to_vec(&message).unwrap()));
+
+    assert_eq!(MessageId::from("7"), actual);
+  }
+}
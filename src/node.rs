@@ -1,40 +1,69 @@
-use crate::chain::Chain;
-use crate::transaction::PublicKey;
-use crate::wallet::{SignedTransaction, Wallet};
-use std::collections::HashSet;
-
-use libp2p::{
-  core::upgrade,
-  floodsub::{self, Floodsub, FloodsubEvent},
-  identity,
-  mdns::{Mdns, MdnsEvent},
-  mplex, noise,
-  swarm::{NetworkBehaviourEventProcess, Swarm, SwarmBuilder, SwarmEvent},
-  tcp::TokioTcpConfig,
-  NetworkBehaviour, PeerId, Transport,
-};
-
-use tokio::{
-  io::{self, AsyncBufReadExt},
-  runtime::Runtime,
-};
-
-use std::pin::Pin;
-
-use std::sync::{
-  mpsc,
-  mpsc::{Receiver, Sender},
-  Arc, Mutex,
-};
-
-use futures::{Future, StreamExt};
-
-use std::error::Error;
+use crate::block::Block;
+use crate::chain::{Chain, ChainError};
+use crate::clock::{Clock, SystemClock};
+use crate::events::{Event, TransactionStatus};
+use crate::finality::SignedVote;
+use crate::journal::{Journal, JournalEntry};
+use crate::metrics::METRICS;
+use crate::peer_manager::{Offense, PeerManager};
+use crate::storage::ChainStore;
+use crate::transaction::{PublicKey, Transaction};
+use crate::wallet::{SignedBlock, SignedTransaction, Wallet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, instrument, warn};
 
+/// Identifies the peer a message came from or is going to.
+pub type Peer = String;
+
+/// How many events a slow subscriber may fall behind before missing some.
+const EVENTS_CAPACITY: usize = 1024;
+
+/// How long a peer's announced block count counts towards the sync target.
+const PEER_HEIGHT_TTL: Duration = Duration::from_secs(60);
+
+/// Most bytes of blocks a single `Blocks` message carries, well below what
+/// the network accepts so the chain can be synced no matter how long it is.
+const SYNC_PAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// A sync whose next page doesn't arrive in this long is abandoned.
+const SYNC_PAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pending transactions older than this are dropped when the node restarts.
+pub const TRANSACTION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// What the network task knows about this node, kept here so the api can read it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkStatus {
+  pub peer_id: Option<Peer>,
+  pub listen_addresses: Vec<String>,
+  /// Whether the data directory could be opened.
+  pub storage_open: bool,
+}
+
+#[derive(Debug)]
 pub struct Node {
   transactions: HashSet<SignedTransaction>,
   wallet: Wallet,
   chain: Chain,
+  clock: Arc<dyn Clock>,
+  outgoing: Vec<Outgoing>,
+  peers: PeerManager,
+  events: broadcast::Sender<Event>,
+  network: NetworkStatus,
+  journal: Option<Box<dyn Journal>>,
+  chain_store: Option<Box<dyn ChainStore>>,
+  /// Block count each peer last announced and when, claims expire
+  /// after `PEER_HEIGHT_TTL` and are dropped once the peer sent its chain.
+  peer_heights: HashMap<Peer, (u128, u128)>,
+  /// Forgers and heights we already submitted evidence for.
+  reported: HashSet<(PublicKey, u128)>,
+  /// Blocks received so far from each peer we are syncing from,
+  /// and when the last page arrived.
+  syncing: HashMap<Peer, (Vec<SignedBlock>, u128)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -43,257 +72,1643 @@ pub enum NodeError {
     public_key: PublicKey,
     signed_transaction: SignedTransaction,
   },
+  InvalidBlockSignature(SignedBlock),
+  Chain(ChainError),
+  PeerBanned(Peer),
+  RateLimited(Peer),
+  /// The epoch's validator set picked someone else to forge the next block.
+  NotForger(PublicKey),
+  /// The transaction would fail on top of our tip, e.g. it overdraws the sender.
+  NotExecutable(ChainError),
 }
 
-#[derive(NetworkBehaviour)]
-#[behaviour(event_process = true)]
-struct NodeBehaviour {
-  floodsub: Floodsub,
-  mdns: Mdns,
+/// Messages nodes exchange with each other.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+  Transaction {
+    public_key: PublicKey,
+    signed_transaction: SignedTransaction,
+  },
+  Block(SignedBlock),
+  /// Our tip, announced again now and then so peers that missed it can sync.
+  Tip(SignedBlock),
+  /// Only sent to a single peer, which answers with `Blocks` starting
+  /// at block count `from`.
+  BlocksRequest {
+    from: u128,
+  },
+  /// A page of at most `SYNC_PAGE_SIZE` bytes of blocks, an empty one
+  /// means there are no more.
+  Blocks(Vec<SignedBlock>),
+  Vote(SignedVote),
 }
 
-lazy_static! {
-  static ref CHANNEL: (Mutex<Sender<String>>, Mutex<Receiver<String>>) = {
-    let (sender, receiver) = mpsc::channel();
-    (Mutex::new(sender), Mutex::new(receiver))
-  };
-  static ref FLOODSUB_TOPIC: floodsub::Topic = floodsub::Topic::new("network");
-  static ref SWARM: Arc<Mutex<Swarm<NodeBehaviour>>> = {
-    Runtime::new().unwrap().block_on(async {
-      let peer_id_keys = identity::Keypair::generate_ed25519();
-
-      let peer_id = PeerId::from(peer_id_keys.public());
-      println!("peer id: {:?}", &peer_id);
-
-      let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
-        .into_authentic(&peer_id_keys)
-        .expect("couldn't sign libp2p-noise static DH keypair");
-
-      let transport = TokioTcpConfig::new()
-        .nodelay(true)
-        .upgrade(upgrade::Version::V1)
-        .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
-        .multiplex(mplex::MplexConfig::new())
-        .boxed();
-
-      impl NetworkBehaviourEventProcess<FloodsubEvent> for NodeBehaviour {
-        fn inject_event(&mut self, message: FloodsubEvent) {
-          if let FloodsubEvent::Message(message) = message {
-            let payload = format!(
-              "received {:?} from {:?}",
-              String::from_utf8_lossy(&message.data),
-              message.source,
-            );
-
-            CHANNEL.0.lock().unwrap().send(payload.clone()).unwrap();
-
-            println!("{}", payload);
-          }
-        }
+impl Message {
+  /// Identifies the message by what it carries, so the same transaction
+  /// or block is recognized no matter who sent it.
+  pub fn id(&self) -> Option<String> {
+    match self {
+      Message::Transaction {
+        signed_transaction, ..
+      } => Some(signed_transaction.transaction.hash()),
+      Message::Block(signed_block) => Some(signed_block.hash()),
+      Message::Vote(signed_vote) => Some(signed_vote.vote.hash()),
+      // Announcing the same tip again must not look like a copy of the first announcement.
+      Message::Tip(_) | Message::BlocksRequest { .. } | Message::Blocks(_) => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recipient {
+  All,
+  Peer(Peer),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+  pub recipient: Recipient,
+  pub message: Message,
+}
+
+impl Default for Node {
+  fn default() -> Self {
+    Node::new()
+  }
+}
+
+impl Node {
+  pub fn new() -> Self {
+    Node::from_parts(Wallet::new(), Chain::new(), Arc::new(SystemClock))
+  }
+
+  pub fn from_parts(wallet: Wallet, chain: Chain, clock: Arc<dyn Clock>) -> Self {
+    Self {
+      transactions: HashSet::new(),
+      wallet,
+      chain,
+      clock,
+      outgoing: Vec::new(),
+      peers: PeerManager::new(),
+      events: broadcast::channel(EVENTS_CAPACITY).0,
+      network: NetworkStatus::default(),
+      journal: None,
+      chain_store: None,
+      peer_heights: HashMap::new(),
+      reported: HashSet::new(),
+      syncing: HashMap::new(),
+    }
+  }
+
+  pub fn public_key(&self) -> PublicKey {
+    self.wallet.public_key()
+  }
+
+  pub fn chain(&self) -> &Chain {
+    &self.chain
+  }
+
+  pub fn transactions(&self) -> &HashSet<SignedTransaction> {
+    &self.transactions
+  }
+
+  /// Puts transactions saved before a restart back in the pool.
+  ///
+  /// Signatures were checked when the transactions first got here,
+  /// the ones that made it into the chain in the meantime, can't be
+  /// executed anymore or expired are dropped. Locked transfers don't
+  /// expire until they unlock.
+  pub fn restore_transactions(&mut self, transactions: Vec<SignedTransaction>) {
+    let now = self.clock.now();
+
+    for transaction in transactions {
+      let (executable, locked) = match self.chain.can_execute(&transaction) {
+        Ok(()) => (true, false),
+        Err(ChainError::TransactionLocked { .. }) => (true, true),
+        Err(_) => (false, false),
+      };
+
+      let expired =
+        !locked && transaction.transaction.created_at() + TRANSACTION_TTL.as_micros() < now;
+
+      if expired || self.chain.contains_transaction(&transaction) || !executable {
+        debug!(id = %transaction.transaction.id(), expired, "dropping saved transaction");
+        continue;
       }
 
-      impl NetworkBehaviourEventProcess<MdnsEvent> for NodeBehaviour {
-        fn inject_event(&mut self, event: MdnsEvent) {
-          match event {
-            MdnsEvent::Discovered(list) => {
-              for (peer, _) in list {
-                self.floodsub.add_node_to_partial_view(peer)
-              }
-            }
-            MdnsEvent::Expired(list) => {
-              for (peer, _) in list {
-                if !self.mdns.has_node(&peer) {
-                  self.floodsub.remove_node_from_partial_view(&peer);
-                }
-              }
-            }
-          }
-        }
+      self.transactions.insert(transaction);
+    }
+
+    self.update_gauges();
+  }
+
+  /// Every change to the pool from now on is written to `journal`.
+  pub fn set_journal(&mut self, journal: Box<dyn Journal>) {
+    self.journal = Some(journal);
+  }
+
+  fn record(&mut self, entry: JournalEntry) {
+    if let Some(journal) = &mut self.journal {
+      if let Err(error) = journal.append(&entry) {
+        error!(%error, "couldn't write to the mempool journal");
       }
+    }
+  }
 
-      let mut swarm = {
-        let mdns = Mdns::new(Default::default()).await.unwrap();
-        let mut behaviour = NodeBehaviour {
-          floodsub: Floodsub::new(peer_id.clone()),
-          mdns,
-        };
-
-        behaviour.floodsub.subscribe(FLOODSUB_TOPIC.clone());
-        SwarmBuilder::new(transport, behaviour, peer_id)
-          .executor(Box::new(|fut| {
-            tokio::spawn(fut);
-          }))
-          .build()
-      };
+  /// The chain is saved to `chain_store` from now on whenever it changes.
+  pub fn set_chain_store(&mut self, chain_store: Box<dyn ChainStore>) {
+    self.chain_store = Some(chain_store);
+  }
 
-      swarm
-        .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
-        .unwrap();
+  fn save_chain(&mut self) {
+    if let Some(chain_store) = &mut self.chain_store {
+      if let Err(error) = chain_store.chain_changed(&self.chain) {
+        error!(%error, "couldn't save the chain");
+      }
+    }
+  }
 
-      Arc::new(Mutex::new(swarm))
-    })
-  };
-}
+  /// Returns false if the transaction was already in the pool.
+  fn add_to_pool(&mut self, transaction: SignedTransaction) -> bool {
+    if !self.transactions.insert(transaction.clone()) {
+      return false;
+    }
 
-impl Node {
-  async fn handle_swarm_messages() -> impl Future<Output = ()> + Send {
-    let swarm_clone = Arc::clone(&SWARM);
-    async move {
-      let mut stdin = io::BufReader::new(io::stdin()).lines();
-      loop {
-        let swarm = swarm_clone.lock().unwrap();
-        tokio::select! {
-          line = stdin.next_line() => {
-            let line = line.unwrap().expect("stdin closed");
-            swarm.behaviour_mut().floodsub.publish(FLOODSUB_TOPIC.clone(), line.as_bytes());
-          }
-          event = swarm.select_next_some() => {
-            if let SwarmEvent::NewListenAddr { address, .. } = event {
-                println!("Listening on {:?}", address);
-            }
-          }
-        }
-        drop(swarm);
+    self.record(JournalEntry::Added { transaction });
+
+    true
+  }
+
+  fn remove_from_pool(&mut self, transaction: &SignedTransaction) {
+    if self.transactions.remove(transaction) {
+      self.record(JournalEntry::Removed {
+        hash: transaction.transaction.hash(),
+      });
+    }
+  }
+
+  pub fn peers(&self) -> &PeerManager {
+    &self.peers
+  }
+
+  pub fn peers_mut(&mut self) -> &mut PeerManager {
+    &mut self.peers
+  }
+
+  pub fn network(&self) -> &NetworkStatus {
+    &self.network
+  }
+
+  pub fn network_mut(&mut self) -> &mut NetworkStatus {
+    &mut self.network
+  }
+
+  /// How many blocks we are behind the best chain our peers told us about.
+  pub fn sync_lag(&self) -> u128 {
+    self
+      .sync_target()
+      .saturating_sub(self.chain.tip().block_count())
+  }
+
+  /// Highest block count a peer announced recently and hasn't disproved
+  /// by sending a shorter chain.
+  fn sync_target(&self) -> u128 {
+    let now = self.clock.now();
+
+    self
+      .peer_heights
+      .values()
+      .filter(|(_, seen_at)| *seen_at + PEER_HEIGHT_TTL.as_micros() >= now)
+      .map(|(block_count, _)| *block_count)
+      .max()
+      .unwrap_or(0)
+  }
+
+  fn update_sync_target(&mut self) {
+    let now = self.clock.now();
+
+    self
+      .peer_heights
+      .retain(|_, (_, seen_at)| *seen_at + PEER_HEIGHT_TTL.as_micros() >= now);
+
+    METRICS.sync_target_height.set(self.sync_target() as i64);
+  }
+
+  pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+    self.events.subscribe()
+  }
+
+  fn emit(&self, event: Event) {
+    // Fails when nobody is listening, which is fine.
+    let _ = self.events.send(event);
+  }
+
+  fn update_gauges(&self) {
+    METRICS
+      .chain_height
+      .set(self.chain.tip().block_count() as i64);
+    METRICS
+      .mempool_transactions
+      .set(self.transactions.len() as i64);
+  }
+
+  fn emit_chain_events(&mut self) {
+    for event in self.chain.events() {
+      self.emit(event);
+    }
+  }
+
+  /// Takes the messages that should be sent to other peers.
+  pub fn outgoing(&mut self) -> Vec<Outgoing> {
+    std::mem::take(&mut self.outgoing)
+  }
+
+  fn send(&mut self, recipient: Recipient, message: Message) {
+    self.outgoing.push(Outgoing { recipient, message });
+  }
+
+  /// Handles a message from a peer, peers that misbehave are penalized.
+  #[instrument(skip(self, message))]
+  pub fn message(&mut self, peer: &Peer, message: Message) -> Result<(), NodeError> {
+    if self.peers.is_banned(peer) {
+      return Err(NodeError::PeerBanned(peer.clone()));
+    }
+
+    let now = self.clock.now();
+
+    if !self.peers.allow(peer, now) {
+      return Err(NodeError::RateLimited(peer.clone()));
+    }
+
+    let offense = match message {
+      Message::Blocks(_) => Offense::FailedSync,
+      _ => Offense::InvalidMessage,
+    };
+
+    let result = self.dispatch(peer, message);
+
+    if result.is_err() {
+      self.peers.penalize(peer, offense, now);
+    }
+
+    result
+  }
+
+  fn dispatch(&mut self, peer: &Peer, message: Message) -> Result<(), NodeError> {
+    match message {
+      Message::Transaction {
+        public_key,
+        signed_transaction,
+      } => self.transaction(&public_key, signed_transaction),
+      Message::Block(signed_block) | Message::Tip(signed_block) => self.block(peer, signed_block),
+      Message::BlocksRequest { from } => {
+        let blocks = self.page(from);
+        self.send(Recipient::Peer(peer.clone()), Message::Blocks(blocks));
+        Ok(())
       }
+      Message::Blocks(blocks) => self.page_received(peer, blocks),
+      Message::Vote(signed_vote) => self.vote(signed_vote),
     }
   }
 
-  pub async fn new() -> Result<Self, Box<dyn Error>> {
-    // let s: dyn Future<Output = ()> + Send = async move {
-    //   let mut stdin = io::BufReader::new(io::stdin()).lines();
-    //   loop {
-    //     let swarm = swarm_clone.lock().unwrap();
-    //     tokio::select! {
-    //       line = stdin.next_line() => {
-    //         let line = line.unwrap().expect("stdin closed");
-    //         swarm.behaviour_mut().floodsub.publish(FLOODSUB_TOPIC.clone(), line.as_bytes());
-    //       }
-    //       event = swarm.select_next_some() => {
-    //         if let SwarmEvent::NewListenAddr { address, .. } = event {
-    //             println!("Listening on {:?}", address);
-    //         }
-    //       }
-    //     }
-    //     drop(swarm);
-    //   }
-    // };
-    tokio::task::spawn(Node::handle_swarm_messages);
-
-    Ok(Self {
-      transactions: HashSet::new(),
-      wallet: Wallet::new(),
-      chain: Chain::new(),
-    })
+  /// Counts a checkpoint vote and passes it on if it's new to us.
+  pub fn vote(&mut self, signed_vote: SignedVote) -> Result<(), NodeError> {
+    if self
+      .chain
+      .add_vote(&signed_vote)
+      .map_err(NodeError::Chain)?
+    {
+      self.save_chain();
+
+      self.send(Recipient::All, Message::Vote(signed_vote));
+    }
+
+    Ok(())
+  }
+
+  /// Validators vote for the last checkpoint in their chain, once.
+  fn vote_for_checkpoint(&mut self) {
+    let public_key = self.wallet.public_key();
+
+    let checkpoint = match self.chain.last_checkpoint() {
+      None => return,
+      Some(checkpoint) => checkpoint,
+    };
+
+    if !self
+      .chain
+      .voting_stake(checkpoint.block_count)
+      .contains_key(&public_key)
+      || checkpoint.block_count <= self.chain.finality().finalized_height()
+      || self.chain.finality().has_voted(&public_key, &checkpoint)
+    {
+      return;
+    }
+
+    let signed_vote = self.wallet.sign_vote(checkpoint);
+
+    if let Err(error) = self.vote(signed_vote) {
+      error!(?error, "couldn't vote for our own checkpoint");
+    }
   }
 
+  #[instrument(skip_all, fields(id = %transaction.transaction.id()))]
   pub fn transaction(
     &mut self,
     public_key: &PublicKey,
     transaction: SignedTransaction,
   ) -> Result<(), NodeError> {
-    if false && !Wallet::verify_transaction(public_key, &transaction) {
+    // The key must belong to the account the transaction spends from,
+    // anyone could sign someone else's transfer otherwise.
+    let signed_by_the_account = transaction
+      .transaction
+      .signer()
+      .is_none_or(|signer| signer == public_key);
+
+    if !signed_by_the_account || !Wallet::verify_transaction(public_key, &transaction) {
+      METRICS
+        .transactions_rejected
+        .with_label_values(&["invalid_signature"])
+        .inc();
       return Err(NodeError::InvalidSignature {
         public_key: public_key.clone(),
         signed_transaction: transaction.clone(),
       });
     }
 
-    let transaction_wasnt_in_the_set = self.transactions.insert(transaction);
+    if let Err(error) = self.chain.check_memo(&transaction.transaction) {
+      METRICS
+        .transactions_rejected
+        .with_label_values(&["memo_too_large"])
+        .inc();
+      return Err(NodeError::Chain(error));
+    }
+
+    if let Err(error) = self.chain.verify_signatures(&transaction) {
+      METRICS
+        .transactions_rejected
+        .with_label_values(&["not_enough_signatures"])
+        .inc();
+      return Err(NodeError::Chain(error));
+    }
+
+    if self.chain.contains_transaction(&transaction) {
+      METRICS
+        .transactions_rejected
+        .with_label_values(&["already_included"])
+        .inc();
+      return Ok(());
+    }
+
+    // Locked transfers wait in the pool until they unlock.
+    match self.chain.can_execute(&transaction) {
+      Ok(()) | Err(ChainError::TransactionLocked { .. }) => {}
+      Err(error) => {
+        METRICS
+          .transactions_rejected
+          .with_label_values(&["not_executable"])
+          .inc();
+        return Err(NodeError::NotExecutable(error));
+      }
+    }
+
+    let transaction_wasnt_in_the_set = self.add_to_pool(transaction.clone());
+
+    if !transaction_wasnt_in_the_set {
+      METRICS
+        .transactions_rejected
+        .with_label_values(&["duplicate"])
+        .inc();
+    } else {
+      METRICS.transactions_accepted.inc();
+      self.update_gauges();
+
+      debug!("transaction added to the pool");
+
+      self.emit(Event::Transaction {
+        transaction: transaction.transaction.clone(),
+      });
+      self.emit(Event::TransactionStatus {
+        id: transaction.transaction.id().clone(),
+        status: TransactionStatus::Pending,
+      });
+
+      self.send(
+        Recipient::All,
+        Message::Transaction {
+          public_key: public_key.clone(),
+          signed_transaction: transaction,
+        },
+      );
+    }
+
+    Ok(())
+  }
+
+  #[instrument(skip(self, signed_block), fields(block_count = %signed_block.block_count()))]
+  pub fn block(&mut self, peer: &Peer, signed_block: SignedBlock) -> Result<(), NodeError> {
+    if self.chain.contains_block(&signed_block) {
+      return Ok(());
+    }
+
+    if !Wallet::verify_block_forger(&signed_block) {
+      return Err(NodeError::InvalidBlockSignature(signed_block));
+    }
+
+    self
+      .peer_heights
+      .insert(peer.clone(), (signed_block.block_count(), self.clock.now()));
+    self.update_sync_target();
+
+    let tip = self.chain.tip();
+
+    let extends_tip =
+      tip.hash() == signed_block.last_hash() && tip.block_count() + 1 == signed_block.block_count();
 
-    if transaction_wasnt_in_the_set {
-      // self
-      //   .sender
-      //   .lock()
-      //   .unwrap()
-      //   .send(String::from("new transaction added homie"))
-      //   .unwrap();
+    if !extends_tip {
+      self.report_equivocation(&signed_block);
+      if self.chain.prefers(&signed_block) && !self.is_syncing_from(peer) {
+        METRICS.sync_requests.inc();
+        info!("found a better chain, requesting blocks");
+        self
+          .syncing
+          .insert(peer.clone(), (Vec::new(), self.clock.now()));
+        self.send(
+          Recipient::Peer(peer.clone()),
+          Message::BlocksRequest { from: 1 },
+        );
+      }
+      return Ok(());
     }
 
+    self.apply(signed_block.clone())?;
+
+    self.send(Recipient::All, Message::Block(signed_block));
+
     Ok(())
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use crate::transaction::Transaction;
+  /// Submits evidence if the forger of `signed_block` also signed
+  /// a different block at the same height in our chain.
+  fn report_equivocation(&mut self, signed_block: &SignedBlock) {
+    let ours = match self.chain.equivocation(signed_block) {
+      None => return,
+      Some(ours) => ours.clone(),
+    };
 
-  #[actix_rt::test]
-  async fn returns_error_when_we_try_to_add_a_transaction_with_an_invalid_signature() {
-    let wallet_a = Wallet::new();
+    let forger = signed_block.block.forger().clone();
 
-    let transaction = Transaction::transfer(
-      String::from("sender_public_key"),
-      String::from("receiver_public_key"),
-      10,
-    );
+    if !self
+      .reported
+      .insert((forger.clone(), signed_block.block_count()))
+    {
+      return;
+    }
 
-    let transaction_signed_by_wallet_a = wallet_a.sign_transaction(transaction.clone());
+    warn!(%forger, block_count = %signed_block.block_count(), "forger signed two blocks at the same height");
 
-    let wallet_b = Wallet::new();
+    let transaction = self.wallet.sign_transaction(Transaction::evidence(
+      self.public_key(),
+      ours,
+      signed_block.clone(),
+    ));
 
-    let mut node = Node::new().await.unwrap();
+    if let Err(error) = self.transaction(&self.public_key(), transaction) {
+      error!(?error, "couldn't submit evidence");
+    }
+  }
 
-    let expected = Err(NodeError::InvalidSignature {
-      public_key: wallet_b.public_key(),
-      signed_transaction: transaction_signed_by_wallet_a.clone(),
-    });
+  /// Our blocks starting at block count `from`, up to `SYNC_PAGE_SIZE` bytes
+  /// but at least one so a page always makes progress.
+  fn page(&self, from: u128) -> Vec<SignedBlock> {
+    let mut size = 0;
 
-    let actual = node.transaction(&wallet_b.public_key(), transaction_signed_by_wallet_a);
+    self
+      .chain
+      .blocks()
+      .iter()
+      .skip(from.max(1) as usize)
+      .take_while(|signed_block| {
+        let first = size == 0;
+        size += signed_block.size();
+        first || size <= SYNC_PAGE_SIZE
+      })
+      .cloned()
+      .collect()
+  }
 
-    assert_eq!(expected, actual);
+  fn is_syncing_from(&self, peer: &Peer) -> bool {
+    self
+      .syncing
+      .get(peer)
+      .map(|(_, received_at)| *received_at + SYNC_PAGE_TIMEOUT.as_micros() >= self.clock.now())
+      .unwrap_or(false)
   }
 
-  #[actix_rt::test]
-  async fn adds_transaction_to_transaction_set() {
-    let wallet = Wallet::new();
+  /// Collects the pages of the peer's chain and switches to it once the last one arrived.
+  fn page_received(&mut self, peer: &Peer, page: Vec<SignedBlock>) -> Result<(), NodeError> {
+    if page.is_empty() {
+      // Whatever the peer claimed before, this is the chain it has.
+      self.peer_heights.remove(peer);
+      self.update_sync_target();
 
-    let transaction = Transaction::transfer(
-      String::from("sender_public_key"),
-      String::from("receiver_public_key"),
-      10,
+      let blocks = self
+        .syncing
+        .remove(peer)
+        .map(|(blocks, _)| blocks)
+        .unwrap_or_default();
+
+      return self.blocks(blocks);
+    }
+
+    let (mut blocks, _) = self.syncing.remove(peer).unwrap_or_default();
+
+    if let Some(signed_block) = page
+      .iter()
+      .find(|signed_block| !Wallet::verify_block_forger(signed_block))
+    {
+      return Err(NodeError::InvalidBlockSignature(signed_block.clone()));
+    }
+
+    for signed_block in page {
+      if signed_block.block_count() != blocks.len() as u128 + 1 {
+        return Err(NodeError::Chain(ChainError::InvalidBlockCount(
+          signed_block,
+        )));
+      }
+      blocks.push(signed_block);
+    }
+
+    let from = blocks.len() as u128 + 1;
+
+    self
+      .syncing
+      .insert(peer.clone(), (blocks, self.clock.now()));
+
+    self.send(
+      Recipient::Peer(peer.clone()),
+      Message::BlocksRequest { from },
     );
 
-    let signed_transaction = wallet.sign_transaction(transaction.clone());
+    Ok(())
+  }
 
-    let mut node = Node::new().await.unwrap();
+  #[instrument(skip_all, fields(blocks = blocks.len()))]
+  fn blocks(&mut self, blocks: Vec<SignedBlock>) -> Result<(), NodeError> {
+    if let Some(signed_block) = blocks
+      .iter()
+      .find(|signed_block| !Wallet::verify_block_forger(signed_block))
+    {
+      return Err(NodeError::InvalidBlockSignature(signed_block.clone()));
+    }
 
-    let mut expected = HashSet::new();
+    let orphaned = match self.chain.reorganize(blocks) {
+      Err(ChainError::ForkNotPreferred(_)) => return Ok(()),
+      Err(error) => return Err(NodeError::Chain(error)),
+      Ok(orphaned) => orphaned,
+    };
 
-    expected.insert(signed_transaction.clone());
+    self.save_chain();
 
-    let actual = node.transaction(&wallet.public_key(), signed_transaction);
+    info!(
+      orphaned = orphaned.len(),
+      block_count = %self.chain.tip().block_count(),
+      "switched to a better chain"
+    );
 
-    assert_eq!(Ok(()), actual);
+    self.emit_chain_events();
 
-    assert_eq!(node.transactions, expected);
+    for signed_transaction in orphaned
+      .into_iter()
+      .flat_map(|block| block.block.transactions)
+    {
+      if !self.chain.contains_transaction(&signed_transaction) {
+        self.emit(Event::TransactionStatus {
+          id: signed_transaction.transaction.id().clone(),
+          status: TransactionStatus::Pending,
+        });
+        self.add_to_pool(signed_transaction);
+      }
+    }
+
+    let included: Vec<SignedTransaction> = self
+      .transactions
+      .iter()
+      .filter(|transaction| self.chain.contains_transaction(transaction))
+      .cloned()
+      .collect();
+
+    for transaction in &included {
+      self.remove_from_pool(transaction);
+    }
+
+    self.update_gauges();
+
+    self.send(Recipient::All, Message::Block(self.chain.tip().clone()));
+
+    self.vote_for_checkpoint();
+
+    Ok(())
   }
 
-  #[actix_rt::test]
-  async fn each_transaction_is_only_added_once() {
-    let wallet = Wallet::new();
+  /// Adds a block to the chain and removes its transactions from the pool.
+  ///
+  /// Failed transactions don't stop a block from being added to the chain.
+  fn apply(&mut self, signed_block: SignedBlock) -> Result<(), NodeError> {
+    let transactions = signed_block.block.transactions.clone();
 
-    let transaction = Transaction::transfer(
-      String::from("sender_public_key"),
-      String::from("receiver_public_key"),
-      10,
+    match self.chain.add(signed_block) {
+      Ok(()) | Err(ChainError::TransactionsFailed(_)) => {}
+      Err(error) => return Err(NodeError::Chain(error)),
+    }
+
+    self.save_chain();
+
+    for transaction in &transactions {
+      self.remove_from_pool(transaction);
+    }
+
+    self.update_gauges();
+
+    self.emit_chain_events();
+
+    self.vote_for_checkpoint();
+
+    Ok(())
+  }
+
+  /// Our clock may be behind the one of whoever forged the tip.
+  fn next_timestamp(&self) -> u128 {
+    std::cmp::max(self.clock.now(), self.chain.tip().block.timestamp())
+  }
+
+  /// Who may forge a block on top of our tip right now.
+  pub fn next_forger(&self) -> Option<PublicKey> {
+    self.chain.next_forger(self.next_timestamp()).cloned()
+  }
+
+  /// Creates a block on top of our chain with as many pending
+  /// transactions as the consensus rules allow.
+  pub fn forge(&mut self) -> Result<SignedBlock, NodeError> {
+    if let Some(forger) = self.next_forger() {
+      if forger != self.public_key() {
+        return Err(NodeError::NotForger(forger));
+      }
+    }
+
+    // Locked transfers stay in the pool until they can be included.
+    let mut transactions: Vec<SignedTransaction> = self
+      .transactions
+      .iter()
+      .filter(|signed_transaction| match &signed_transaction.transaction {
+        Transaction::LockedTransfer { unlock, .. } => self.chain.is_unlocked(unlock),
+        _ => true,
+      })
+      .cloned()
+      .collect();
+
+    transactions.sort_by_key(|signed_transaction| signed_transaction.transaction.hash());
+
+    let params = self.chain.params().clone();
+
+    transactions.truncate(params.max_transactions);
+
+    let tip = self.chain.tip();
+
+    let timestamp = self.next_timestamp();
+
+    let sign = |transactions: Vec<SignedTransaction>| {
+      self.wallet.sign_block(Block::with_timestamp(
+        transactions,
+        tip.hash(),
+        self.wallet.public_key(),
+        tip.block_count() + 1,
+        timestamp,
+      ))
+    };
+
+    let mut signed_block = sign(transactions);
+
+    // Whatever doesn't fit waits for the next block.
+    while signed_block.size() > params.max_block_size {
+      let mut transactions = signed_block.block.transactions;
+      transactions.pop();
+      signed_block = sign(transactions);
+    }
+
+    self.apply(signed_block.clone())?;
+
+    info!(
+      block_count = %signed_block.block_count(),
+      transactions = signed_block.block.transactions.len(),
+      "forged block"
     );
 
-    let signed_transaction = wallet.sign_transaction(transaction.clone());
+    self.send(Recipient::All, Message::Block(signed_block.clone()));
 
-    let mut node = Node::new().await.unwrap();
+    Ok(signed_block)
+  }
 
-    let mut expected = HashSet::new();
+  /// Announces our tip so peers that are behind, or on another fork,
+  /// know they should catch up.
+  pub fn tick(&mut self) {
+    self.peers.tick(self.clock.now());
 
-    expected.insert(signed_transaction.clone());
+    self.update_sync_target();
 
-    node
-      .transaction(&wallet.public_key(), signed_transaction.clone())
-      .unwrap();
-    node
-      .transaction(&wallet.public_key(), signed_transaction)
-      .unwrap();
+    let now = self.clock.now();
 
-    assert_eq!(node.transactions, expected);
+    self
+      .syncing
+      .retain(|_, (_, received_at)| *received_at + SYNC_PAGE_TIMEOUT.as_micros() >= now);
+
+    let tip = self.chain.tip().clone();
+
+    if tip.block_count() > 0 {
+      self.send(Recipient::All, Message::Tip(tip));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::clock::ManualClock;
+  use crate::consensus::ConsensusParams;
+  use crate::finality::Checkpoint;
+  use crate::journal::MemoryJournal;
+  use crate::transaction::Lock;
+
+  /// A node whose chain credits `wallet` with 100 coins.
+  fn funding(wallet: &Wallet) -> Node {
+    Node::from_parts(
+      Wallet::new(),
+      Chain::with_allocations(vec![
+        (wallet.public_key(), 100),
+        (String::from("receiver_public_key"), 0),
+      ]),
+      Arc::new(SystemClock),
+    )
+  }
+
+  #[test]
+  fn returns_error_when_we_try_to_add_a_transaction_with_an_invalid_signature() {
+    let wallet_a = Wallet::new();
+
+    let wallet_b = Wallet::new();
+
+    let transaction = Transaction::transfer(
+      wallet_b.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    );
+
+    let transaction_signed_by_wallet_a = wallet_a.sign_transaction(transaction.clone());
+
+    let mut node = Node::new();
+
+    let expected = Err(NodeError::InvalidSignature {
+      public_key: wallet_b.public_key(),
+      signed_transaction: transaction_signed_by_wallet_a.clone(),
+    });
+
+    let actual = node.transaction(&wallet_b.public_key(), transaction_signed_by_wallet_a);
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rejects_transactions_signed_by_someone_other_than_the_sender() {
+    let (victim, thief) = (Wallet::new(), Wallet::new());
+
+    let mut node = Node::from_parts(
+      Wallet::new(),
+      Chain::with_allocations(vec![(victim.public_key(), 100), (thief.public_key(), 0)]),
+      Arc::new(SystemClock),
+    );
+
+    let theft = thief.sign_transaction(Transaction::transfer(
+      victim.public_key(),
+      thief.public_key(),
+      100,
+    ));
+
+    assert_eq!(
+      Err(NodeError::InvalidSignature {
+        public_key: thief.public_key(),
+        signed_transaction: theft.clone(),
+      }),
+      node.message(
+        &String::from("peer"),
+        Message::Transaction {
+          public_key: thief.public_key(),
+          signed_transaction: theft,
+        }
+      )
+    );
+    assert!(node.transactions().is_empty());
+  }
+
+  #[test]
+  fn adds_transaction_to_transaction_set() {
+    let wallet = Wallet::new();
+
+    let transaction =
+      Transaction::transfer(wallet.public_key(), String::from("receiver_public_key"), 10);
+
+    let signed_transaction = wallet.sign_transaction(transaction.clone());
+
+    let mut node = funding(&wallet);
+
+    let mut expected = HashSet::new();
+
+    expected.insert(signed_transaction.clone());
+
+    let actual = node.transaction(&wallet.public_key(), signed_transaction);
+
+    assert_eq!(Ok(()), actual);
+
+    assert_eq!(node.transactions, expected);
+  }
+
+  #[test]
+  fn each_transaction_is_only_added_once() {
+    let wallet = Wallet::new();
+
+    let transaction =
+      Transaction::transfer(wallet.public_key(), String::from("receiver_public_key"), 10);
+
+    let signed_transaction = wallet.sign_transaction(transaction.clone());
+
+    let mut node = funding(&wallet);
+
+    let mut expected = HashSet::new();
+
+    expected.insert(signed_transaction.clone());
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+    node
+      .transaction(&wallet.public_key(), signed_transaction)
+      .unwrap();
+
+    assert_eq!(node.transactions, expected);
+  }
+
+  #[test]
+  fn keeps_multisig_transfers_without_enough_cosignatures_out_of_the_pool() {
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+
+    let mut node = funding(&alice);
+
+    let create = alice.sign_transaction(Transaction::create_multisig(
+      vec![alice.public_key(), bob.public_key()],
+      2,
+    ));
+
+    let account = create.transaction.accounts()[0].clone();
+
+    node.chain.execute(&create).unwrap();
+    node
+      .chain
+      .execute(&alice.sign_transaction(Transaction::transfer(
+        alice.public_key(),
+        account.clone(),
+        10,
+      )))
+      .unwrap();
+
+    let mut transfer = alice.sign_transaction(Transaction::multisig_transfer(
+      account.clone(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    alice.cosign(&mut transfer);
+
+    assert_eq!(
+      Err(NodeError::Chain(ChainError::NotEnoughSignatures {
+        account,
+        required: 2,
+        valid: 1,
+      })),
+      node.transaction(&alice.public_key(), transfer.clone())
+    );
+    assert!(node.transactions.is_empty());
+
+    bob.cosign(&mut transfer);
+
+    assert_eq!(Ok(()), node.transaction(&alice.public_key(), transfer));
+    assert_eq!(1, node.transactions.len());
+  }
+
+  #[test]
+  fn keeps_transactions_that_cant_be_executed_out_of_the_pool() {
+    let wallet = Wallet::new();
+
+    let mut node = funding(&wallet);
+
+    let overdraft = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      101,
+    ));
+
+    assert_eq!(
+      Err(NodeError::NotExecutable(ChainError::InsufficientBalance(
+        wallet.public_key()
+      ))),
+      node.transaction(&wallet.public_key(), overdraft)
+    );
+    assert!(node.transactions.is_empty());
+    assert!(node.outgoing().is_empty());
+  }
+
+  #[test]
+  fn gossips_new_transactions_to_every_peer() {
+    let wallet = Wallet::new();
+
+    let transaction =
+      Transaction::transfer(wallet.public_key(), String::from("receiver_public_key"), 10);
+
+    let signed_transaction = wallet.sign_transaction(transaction);
+
+    let mut node = funding(&wallet);
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+
+    let expected = vec![Outgoing {
+      recipient: Recipient::All,
+      message: Message::Transaction {
+        public_key: wallet.public_key(),
+        signed_transaction,
+      },
+    }];
+
+    assert_eq!(expected, node.outgoing());
+    assert_eq!(Vec::<Outgoing>::new(), node.outgoing());
+  }
+
+  #[test]
+  fn forging_adds_pending_transactions_to_the_chain() {
+    let wallet = Wallet::new();
+
+    let signed_transaction = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    let mut node = funding(&wallet);
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+
+    let signed_block = node.forge().unwrap();
+
+    assert_eq!(signed_block.block.transactions, vec![signed_transaction]);
+    assert_eq!(node.chain.tip(), &signed_block);
+    assert_eq!(node.transactions, HashSet::new());
+  }
+
+  #[test]
+  fn locked_transfers_wait_in_the_pool_until_they_unlock() {
+    let wallet = Wallet::new();
+
+    let mut node = Node::new();
+
+    let signed_transaction = wallet.sign_transaction(Transaction::locked_transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+      Lock::Height(node.chain.tip().block_count() + 2),
+    ));
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+
+    let signed_block = node.forge().unwrap();
+
+    assert!(signed_block.block.transactions.is_empty());
+    assert_eq!(1, node.transactions.len());
+
+    let signed_block = node.forge().unwrap();
+
+    assert_eq!(signed_block.block.transactions, vec![signed_transaction]);
+    assert!(node.transactions.is_empty());
+  }
+
+  #[test]
+  fn rejects_blocks_that_were_not_signed_by_their_forger() {
+    let node_a = Node::new();
+
+    let block = Block::new(
+      Vec::new(),
+      node_a.chain.tip().hash(),
+      node_a.wallet.public_key(),
+      1,
+    );
+
+    let signed_block = Wallet::new().sign_block(block);
+
+    let mut node_b = Node::new();
+
+    let expected = Err(NodeError::InvalidBlockSignature(signed_block.clone()));
+
+    let actual = node_b.block(&String::from("node_a"), signed_block);
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rejects_synced_blocks_that_were_not_signed_by_their_forger() {
+    let mut node_a = Node::new();
+
+    let mut signed_block = node_a.forge().unwrap();
+
+    signed_block.signature = Wallet::new()
+      .sign_block(signed_block.block.clone())
+      .signature;
+
+    let mut node_b = Node::new();
+
+    let expected = Err(NodeError::InvalidBlockSignature(signed_block.clone()));
+
+    let actual = node_b.message(&String::from("node_a"), Message::Blocks(vec![signed_block]));
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn peers_that_keep_sending_invalid_messages_are_banned() {
+    let node_a = Node::new();
+
+    let signed_block = Wallet::new().sign_block(Block::new(
+      Vec::new(),
+      node_a.chain.tip().hash(),
+      node_a.wallet.public_key(),
+      1,
+    ));
+
+    let mut node_b = Node::new();
+
+    let peer = String::from("node_a");
+
+    while !node_b.peers.is_banned(&peer) {
+      assert_eq!(
+        Err(NodeError::InvalidBlockSignature(signed_block.clone())),
+        node_b.message(&peer, Message::Block(signed_block.clone()))
+      );
+    }
+
+    assert_eq!(vec![peer.clone()], node_b.peers.banned());
+
+    assert_eq!(
+      Err(NodeError::PeerBanned(peer.clone())),
+      node_b.message(&peer, Message::BlocksRequest { from: 1 })
+    );
+  }
+
+  #[test]
+  fn invalid_sync_responses_count_as_failed_syncs() {
+    let mut node_a = Node::new();
+
+    let mut signed_block = node_a.forge().unwrap();
+
+    signed_block.signature = Wallet::new()
+      .sign_block(signed_block.block.clone())
+      .signature;
+
+    let mut node_b = Node::new();
+
+    let peer = String::from("node_a");
+
+    let _ = node_b.message(&peer, Message::Blocks(vec![signed_block]));
+
+    assert_eq!(1, node_b.peers.peers()[&peer].failed_syncs);
+  }
+
+  #[test]
+  fn subscribers_hear_about_new_transactions_and_blocks() {
+    let wallet = Wallet::new();
+
+    let signed_transaction = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    let id = signed_transaction.transaction.id().clone();
+
+    let mut node = funding(&wallet);
+
+    let mut events = node.subscribe();
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+
+    let signed_block = node.forge().unwrap();
+
+    let expected = vec![
+      Event::Transaction {
+        transaction: signed_transaction.transaction,
+      },
+      Event::TransactionStatus {
+        id: id.clone(),
+        status: TransactionStatus::Pending,
+      },
+      Event::TransactionStatus {
+        id,
+        status: TransactionStatus::Included { block_count: 1 },
+      },
+      Event::Balance {
+        account: wallet.public_key(),
+        balance: 90,
+      },
+      Event::Balance {
+        account: String::from("receiver_public_key"),
+        balance: 10,
+      },
+      Event::Balance {
+        account: node.public_key(),
+        balance: 50,
+      },
+      Event::Block {
+        block_count: 1,
+        hash: signed_block.hash(),
+        transactions: 1,
+      },
+    ];
+
+    let actual: Vec<Event> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn transaction_and_block_messages_are_identified_by_their_hash() {
+    let wallet = Wallet::new();
+
+    let signed_transaction = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    let message = Message::Transaction {
+      public_key: wallet.public_key(),
+      signed_transaction: signed_transaction.clone(),
+    };
+
+    assert_eq!(Some(signed_transaction.transaction.hash()), message.id());
+
+    let signed_block = SignedBlock::genesis();
+
+    assert_eq!(Some(signed_block.hash()), Message::Block(signed_block).id());
+
+    assert_eq!(None, Message::BlocksRequest { from: 1 }.id());
+  }
+
+  #[test]
+  fn requests_blocks_from_peers_that_are_ahead() {
+    let mut node_a = Node::new();
+
+    node_a.forge().unwrap();
+
+    let signed_block = node_a.forge().unwrap();
+
+    let mut node_b = Node::new();
+
+    let peer = String::from("node_a");
+
+    node_b.block(&peer, signed_block).unwrap();
+
+    let expected = vec![Outgoing {
+      recipient: Recipient::Peer(peer),
+      message: Message::BlocksRequest { from: 1 },
+    }];
+
+    assert_eq!(expected, node_b.outgoing());
+  }
+
+  #[test]
+  fn syncs_the_chain_a_page_at_a_time() {
+    let mut node_a = Node::new();
+
+    for _ in 0..3 {
+      node_a.forge().unwrap();
+    }
+
+    node_a.outgoing();
+
+    node_a
+      .message(&String::from("node_b"), Message::BlocksRequest { from: 2 })
+      .unwrap();
+
+    assert_eq!(
+      vec![Outgoing {
+        recipient: Recipient::Peer(String::from("node_b")),
+        message: Message::Blocks(node_a.chain().blocks()[2..].to_vec()),
+      }],
+      node_a.outgoing()
+    );
+
+    let mut node_b = Node::new();
+
+    let (a, b) = (String::from("node_a"), String::from("node_b"));
+
+    node_b.block(&a, node_a.chain().tip().clone()).unwrap();
+
+    let mut requests = Vec::new();
+
+    // Passes messages back and forth until the sync is over.
+    loop {
+      let request = node_b
+        .outgoing()
+        .into_iter()
+        .find(|outgoing| outgoing.recipient == Recipient::Peer(a.clone()));
+
+      let request = match request {
+        None => break,
+        Some(request) => request.message,
+      };
+
+      requests.push(request.clone());
+
+      node_a.message(&b, request).unwrap();
+
+      for outgoing in node_a.outgoing() {
+        node_b.message(&a, outgoing.message).unwrap();
+      }
+    }
+
+    assert_eq!(
+      vec![
+        Message::BlocksRequest { from: 1 },
+        Message::BlocksRequest { from: 4 }
+      ],
+      requests
+    );
+    assert_eq!(node_a.chain().tip(), node_b.chain().tip());
+  }
+
+  #[test]
+  fn rejects_pages_that_skip_blocks() {
+    let mut node_a = Node::new();
+
+    node_a.forge().unwrap();
+
+    let signed_block = node_a.forge().unwrap();
+
+    let mut node_b = Node::new();
+
+    assert_eq!(
+      Err(NodeError::Chain(ChainError::InvalidBlockCount(
+        signed_block.clone()
+      ))),
+      node_b.message(&String::from("node_a"), Message::Blocks(vec![signed_block]))
+    );
+  }
+
+  #[test]
+  fn tracks_how_far_behind_peers_the_node_is() {
+    let mut node_a = Node::new();
+
+    node_a.forge().unwrap();
+    node_a.forge().unwrap();
+
+    let signed_block = node_a.forge().unwrap();
+
+    let mut node_b = Node::new();
+
+    assert_eq!(0, node_b.sync_lag());
+
+    node_b.block(&String::from("node_a"), signed_block).unwrap();
+
+    assert_eq!(3, node_b.sync_lag());
+
+    node_b
+      .message(
+        &String::from("node_a"),
+        Message::Blocks(node_a.chain().blocks()[1..].to_vec()),
+      )
+      .unwrap();
+    node_b
+      .message(&String::from("node_a"), Message::Blocks(Vec::new()))
+      .unwrap();
+
+    assert_eq!(0, node_b.sync_lag());
+  }
+
+  #[test]
+  fn forgets_block_counts_peers_did_not_back_up() {
+    let clock = ManualClock::new();
+
+    let mut node = Node::from_parts(Wallet::new(), Chain::new(), Arc::new(clock.clone()));
+
+    let liar = Wallet::new();
+
+    let announce = |node: &mut Node| {
+      node
+        .block(
+          &String::from("liar"),
+          liar.sign_block(Block::new(
+            Vec::new(),
+            String::from("hash"),
+            liar.public_key(),
+            1_000_000,
+          )),
+        )
+        .unwrap();
+    };
+
+    announce(&mut node);
+
+    assert_eq!(1_000_000, node.sync_lag());
+
+    clock.advance(PEER_HEIGHT_TTL + Duration::from_secs(1));
+
+    assert_eq!(0, node.sync_lag());
+
+    announce(&mut node);
+
+    let _ = node.message(&String::from("liar"), Message::Blocks(Vec::new()));
+
+    assert_eq!(0, node.sync_lag());
+  }
+
+  #[test]
+  fn journals_changes_to_the_pool() {
+    let wallet = Wallet::new();
+
+    let mut node = funding(&wallet);
+
+    let journal = MemoryJournal::default();
+
+    node.set_journal(Box::new(journal.clone()));
+
+    let signed_transaction = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    node
+      .transaction(&wallet.public_key(), signed_transaction.clone())
+      .unwrap();
+
+    node.forge().unwrap();
+
+    let expected = vec![
+      JournalEntry::Added {
+        transaction: signed_transaction.clone(),
+      },
+      JournalEntry::Removed {
+        hash: signed_transaction.transaction.hash(),
+      },
+    ];
+
+    assert_eq!(expected, *journal.entries.lock().unwrap());
+  }
+
+  #[test]
+  fn drops_expired_transactions_when_restoring_the_pool() {
+    let clock = ManualClock::new();
+
+    let chain = Chain::with_allocations(vec![
+      (String::from("sender_public_key"), 100),
+      (String::from("receiver_public_key"), 0),
+    ]);
+
+    let mut node = Node::from_parts(Wallet::new(), chain, Arc::new(clock.clone()));
+
+    let wallet = Wallet::new();
+
+    let transaction = |timestamp| {
+      wallet.sign_transaction(Transaction::Transfer {
+        id: format!("transaction_{}", timestamp),
+        sender: String::from("sender_public_key"),
+        receiver: String::from("receiver_public_key"),
+        amount: 10,
+        memo: None,
+        timestamp,
+      })
+    };
+
+    clock.advance(TRANSACTION_TTL + Duration::from_micros(10));
+
+    let expired = transaction(0);
+
+    let fresh = transaction(10);
+
+    node.restore_transactions(vec![expired, fresh.clone()]);
+
+    assert_eq!(vec![&fresh], node.transactions().iter().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn forged_blocks_respect_the_transaction_limit() {
+    let wallet = Wallet::new();
+
+    let chain = Chain::with_params(
+      vec![
+        (wallet.public_key(), 100),
+        (String::from("receiver_public_key"), 0),
+      ],
+      ConsensusParams {
+        max_transactions: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let mut node = Node::from_parts(Wallet::new(), chain, Arc::new(SystemClock));
+
+    for amount in 1..=3 {
+      node
+        .transaction(
+          &wallet.public_key(),
+          wallet.sign_transaction(Transaction::transfer(
+            wallet.public_key(),
+            String::from("receiver_public_key"),
+            amount,
+          )),
+        )
+        .unwrap();
+    }
+
+    let signed_block = node.forge().unwrap();
+
+    assert_eq!(2, signed_block.block.transactions.len());
+    assert_eq!(1, node.transactions().len());
+  }
+
+  #[test]
+  fn validators_vote_for_checkpoints_and_gossip_their_votes() {
+    let wallet = Wallet::new();
+
+    let chain = Chain::with_params(
+      vec![(wallet.public_key(), 10)],
+      ConsensusParams {
+        checkpoint_interval: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let mut node = Node::from_parts(wallet, chain, Arc::new(SystemClock));
+
+    node
+      .chain
+      .execute(
+        &node
+          .wallet
+          .sign_transaction(Transaction::stake(node.public_key(), 10)),
+      )
+      .unwrap();
+
+    node.forge().unwrap();
+
+    assert_eq!(0, node.chain.finality().finalized_height());
+
+    let checkpoint = node.forge().unwrap();
+
+    assert_eq!(2, node.chain.finality().finalized_height());
+    assert!(node.outgoing().iter().any(|outgoing| matches!(
+      &outgoing.message,
+      Message::Vote(signed_vote) if signed_vote.vote.checkpoint.hash == checkpoint.hash()
+    )));
+  }
+
+  #[test]
+  fn submits_evidence_when_a_forger_signs_two_blocks_at_the_same_height() {
+    let forger = Wallet::new();
+
+    let chain = Chain::with_allocations(vec![(forger.public_key(), 10)]);
+
+    let mut node = Node::from_parts(Wallet::new(), chain, Arc::new(SystemClock));
+
+    let forged_at = |node: &Node, timestamp: u128| {
+      forger.sign_block(Block::with_timestamp(
+        Vec::new(),
+        node.chain.tip().hash(),
+        forger.public_key(),
+        1,
+        timestamp,
+      ))
+    };
+
+    let (first, second) = (forged_at(&node, 1), forged_at(&node, 2));
+
+    node.chain.add(first.clone()).unwrap();
+
+    let peer = String::from("node_a");
+
+    node.block(&peer, second.clone()).unwrap();
+    node.block(&peer, second.clone()).unwrap();
+
+    let evidence = node
+      .transactions()
+      .iter()
+      .filter(|signed_transaction| {
+        matches!(
+          &signed_transaction.transaction,
+          Transaction::Evidence { first: ours, second: theirs, .. }
+            if **ours == first && **theirs == second
+        )
+      })
+      .count();
+
+    assert_eq!(1, evidence);
+  }
+
+  #[test]
+  fn only_forges_when_picked_by_the_epoch() {
+    let validator = Wallet::new();
+
+    let chain = Chain::with_params(
+      vec![(validator.public_key(), 10)],
+      ConsensusParams {
+        epoch_length: 1,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let mut node = Node::from_parts(Wallet::new(), chain, Arc::new(SystemClock));
+
+    node
+      .chain
+      .execute(&validator.sign_transaction(Transaction::stake(validator.public_key(), 10)))
+      .unwrap();
+
+    node.forge().unwrap();
+
+    assert_eq!(
+      Err(NodeError::NotForger(validator.public_key())),
+      node.forge()
+    );
+  }
+
+  #[test]
+  fn does_not_penalize_votes_for_checkpoints_we_do_not_have_yet() {
+    let validator = Wallet::new();
+
+    let chain = Chain::with_params(
+      vec![(validator.public_key(), 10)],
+      ConsensusParams {
+        checkpoint_interval: 2,
+        ..ConsensusParams::default()
+      },
+      Arc::new(SystemClock),
+    );
+
+    let mut node = Node::from_parts(Wallet::new(), chain, Arc::new(SystemClock));
+
+    node
+      .chain
+      .execute(&validator.sign_transaction(Transaction::stake(validator.public_key(), 10)))
+      .unwrap();
+
+    let vote = validator.sign_vote(Checkpoint {
+      block_count: 2,
+      hash: String::from("hash"),
+    });
+
+    let peer = String::from("node_a");
+
+    assert_eq!(Ok(()), node.message(&peer, Message::Vote(vote.clone())));
+    assert!(node
+      .outgoing()
+      .iter()
+      .any(|outgoing| outgoing.message == Message::Vote(vote.clone())));
+    assert!(!node.peers.is_banned(&peer));
+  }
+
+  #[test]
+  fn ticks_announce_the_tip_apart_from_the_block_itself() {
+    let mut node = Node::new();
+
+    let signed_block = node.forge().unwrap();
+
+    node.outgoing();
+    node.tick();
+
+    let expected = vec![Outgoing {
+      recipient: Recipient::All,
+      message: Message::Tip(signed_block),
+    }];
+
+    assert_eq!(expected, node.outgoing());
+    assert_eq!(None, expected[0].message.id());
   }
 }
@@ -0,0 +1,303 @@
+use crate::node::Peer;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Score lost for each offense.
+const INVALID_MESSAGE_PENALTY: i64 = 10;
+const FAILED_SYNC_PENALTY: i64 = 20;
+const SPAM_PENALTY: i64 = 5;
+
+/// Peers whose score drops below this are banned.
+const BAN_THRESHOLD: i64 = -50;
+
+const BAN_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// A penalized peer gets a point back every `SCORE_RECOVERY_INTERVAL`, up to
+/// a clean score, so the odd stale block doesn't add up to a ban over time.
+const SCORE_RECOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Peers we haven't heard from in this long are forgotten.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Peers may send at most `MAX_MESSAGES_PER_WINDOW` messages every `RATE_WINDOW`.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+const MAX_MESSAGES_PER_WINDOW: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Offense {
+  InvalidMessage,
+  FailedSync,
+  Spam,
+}
+
+impl Offense {
+  fn penalty(&self) -> i64 {
+    match self {
+      Offense::InvalidMessage => INVALID_MESSAGE_PENALTY,
+      Offense::FailedSync => FAILED_SYNC_PENALTY,
+      Offense::Spam => SPAM_PENALTY,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PeerRecord {
+  pub score: i64,
+  pub messages: u64,
+  pub invalid_messages: u64,
+  pub failed_syncs: u64,
+  pub spam_offenses: u64,
+  pub banned_until: Option<u128>,
+  window_start: u128,
+  window_messages: u64,
+  /// Last time the peer sent a message or was penalized.
+  last_seen: u128,
+  /// Recovery is counted from here.
+  recovered_at: u128,
+}
+
+impl PeerRecord {
+  /// Gives back the points earned since the last recovery.
+  fn recover(&mut self, now: u128) {
+    let interval = SCORE_RECOVERY_INTERVAL.as_micros();
+
+    let points = now.saturating_sub(self.recovered_at) / interval;
+
+    self.score = (self.score + points as i64).min(0);
+    self.recovered_at += points * interval;
+  }
+}
+
+/// Keeps track of how well peers behave and bans the ones that don't.
+///
+/// Times are in microseconds, like the rest of the node.
+#[derive(Debug, Default)]
+pub struct PeerManager {
+  peers: HashMap<Peer, PeerRecord>,
+  banned: Vec<Peer>,
+  unbanned: Vec<Peer>,
+}
+
+impl PeerManager {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn peers(&self) -> &HashMap<Peer, PeerRecord> {
+    &self.peers
+  }
+
+  pub fn is_banned(&self, peer: &Peer) -> bool {
+    self
+      .peers
+      .get(peer)
+      .map(|record| record.banned_until.is_some())
+      .unwrap_or(false)
+  }
+
+  /// Counts a message from `peer`, returns false if the peer is sending too many.
+  pub fn allow(&mut self, peer: &Peer, now: u128) -> bool {
+    let record = self.peers.entry(peer.clone()).or_default();
+
+    record.messages += 1;
+    record.last_seen = now;
+
+    if now >= record.window_start + RATE_WINDOW.as_micros() {
+      record.window_start = now;
+      record.window_messages = 0;
+    }
+
+    record.window_messages += 1;
+
+    if record.window_messages > MAX_MESSAGES_PER_WINDOW {
+      self.penalize(peer, Offense::Spam, now);
+      return false;
+    }
+
+    true
+  }
+
+  /// Lowers the peer score and bans it if the score gets too low.
+  pub fn penalize(&mut self, peer: &Peer, offense: Offense, now: u128) {
+    let record = self.peers.entry(peer.clone()).or_default();
+
+    record.recover(now);
+    record.last_seen = now;
+    record.score -= offense.penalty();
+
+    match offense {
+      Offense::InvalidMessage => record.invalid_messages += 1,
+      Offense::FailedSync => record.failed_syncs += 1,
+      Offense::Spam => record.spam_offenses += 1,
+    }
+
+    if record.score < BAN_THRESHOLD && record.banned_until.is_none() {
+      record.banned_until = Some(now + BAN_DURATION.as_micros());
+      self.banned.push(peer.clone());
+    }
+  }
+
+  /// Lifts bans that are over, the peer starts again with a clean score.
+  /// Other peers recover some of their score and idle ones are forgotten.
+  pub fn tick(&mut self, now: u128) {
+    for (peer, record) in self.peers.iter_mut() {
+      match record.banned_until {
+        Some(banned_until) if banned_until <= now => {
+          record.banned_until = None;
+          record.score = 0;
+          record.recovered_at = now;
+          self.unbanned.push(peer.clone());
+        }
+        Some(_) => {}
+        None => record.recover(now),
+      }
+    }
+
+    self.peers.retain(|_, record| {
+      record.banned_until.is_some() || now < record.last_seen + IDLE_TIMEOUT.as_micros()
+    });
+  }
+
+  /// Takes the peers that were banned since the last call.
+  pub fn banned(&mut self) -> Vec<Peer> {
+    std::mem::take(&mut self.banned)
+  }
+
+  /// Takes the peers whose ban was lifted since the last call.
+  pub fn unbanned(&mut self) -> Vec<Peer> {
+    std::mem::take(&mut self.unbanned)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn peers_start_with_a_clean_score() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    assert!(peer_manager.allow(&peer, 0));
+
+    assert_eq!(0, peer_manager.peers()[&peer].score);
+    assert_eq!(1, peer_manager.peers()[&peer].messages);
+    assert!(!peer_manager.is_banned(&peer));
+  }
+
+  #[test]
+  fn penalizes_offenses() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    peer_manager.penalize(&peer, Offense::InvalidMessage, 0);
+    peer_manager.penalize(&peer, Offense::FailedSync, 0);
+
+    let record = &peer_manager.peers()[&peer];
+
+    assert_eq!(-30, record.score);
+    assert_eq!(1, record.invalid_messages);
+    assert_eq!(1, record.failed_syncs);
+  }
+
+  #[test]
+  fn bans_peers_whose_score_is_below_the_threshold() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    for _ in 0..5 {
+      peer_manager.penalize(&peer, Offense::InvalidMessage, 0);
+    }
+
+    assert!(!peer_manager.is_banned(&peer));
+
+    peer_manager.penalize(&peer, Offense::InvalidMessage, 0);
+
+    assert!(peer_manager.is_banned(&peer));
+    assert_eq!(vec![peer], peer_manager.banned());
+    assert_eq!(Vec::<Peer>::new(), peer_manager.banned());
+  }
+
+  #[test]
+  fn bans_are_lifted_after_a_while() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    for _ in 0..6 {
+      peer_manager.penalize(&peer, Offense::InvalidMessage, 0);
+    }
+
+    peer_manager.tick(BAN_DURATION.as_micros() - 1);
+
+    assert!(peer_manager.is_banned(&peer));
+
+    peer_manager.tick(BAN_DURATION.as_micros());
+
+    assert!(!peer_manager.is_banned(&peer));
+    assert_eq!(0, peer_manager.peers()[&peer].score);
+    assert_eq!(vec![peer], peer_manager.unbanned());
+  }
+
+  #[test]
+  fn scores_recover_over_time() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    let interval = SCORE_RECOVERY_INTERVAL.as_micros();
+
+    peer_manager.penalize(&peer, Offense::InvalidMessage, 0);
+
+    peer_manager.tick(3 * interval);
+
+    assert_eq!(-7, peer_manager.peers()[&peer].score);
+
+    // A stale block now and then never adds up to a ban.
+    for i in 1..100 {
+      peer_manager.penalize(&peer, Offense::InvalidMessage, i * 20 * interval);
+    }
+
+    assert!(!peer_manager.is_banned(&peer));
+
+    peer_manager.tick(2000 * interval);
+
+    assert_eq!(0, peer_manager.peers()[&peer].score);
+  }
+
+  #[test]
+  fn forgets_idle_peers() {
+    let mut peer_manager = PeerManager::new();
+
+    let (idle, active) = (String::from("idle"), String::from("active"));
+
+    peer_manager.allow(&idle, 0);
+    peer_manager.allow(&active, 0);
+
+    peer_manager.allow(&active, IDLE_TIMEOUT.as_micros() - 1);
+
+    peer_manager.tick(IDLE_TIMEOUT.as_micros());
+
+    assert!(!peer_manager.peers().contains_key(&idle));
+    assert!(peer_manager.peers().contains_key(&active));
+  }
+
+  #[test]
+  fn peers_that_send_too_many_messages_are_penalized() {
+    let mut peer_manager = PeerManager::new();
+
+    let peer = String::from("peer");
+
+    for _ in 0..MAX_MESSAGES_PER_WINDOW {
+      assert!(peer_manager.allow(&peer, 0));
+    }
+
+    assert!(!peer_manager.allow(&peer, 0));
+    assert_eq!(-SPAM_PENALTY, peer_manager.peers()[&peer].score);
+
+    assert!(peer_manager.allow(&peer, RATE_WINDOW.as_micros()));
+  }
+}
@@ -0,0 +1,153 @@
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Peers we managed to connect to, kept on disk so they can be
+/// dialed again after a restart.
+#[derive(Debug)]
+pub struct PeerStore {
+  path: PathBuf,
+  peers: BTreeMap<String, String>,
+}
+
+impl PeerStore {
+  /// Loads the peers saved at `path`, a missing file means no peers are known.
+  pub fn load(path: PathBuf) -> io::Result<Self> {
+    let peers = match fs::read(&path) {
+      Ok(contents) => serde_json::from_slice(&contents)?,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+      Err(error) => return Err(error),
+    };
+
+    Ok(Self { path, peers })
+  }
+
+  pub fn empty(path: PathBuf) -> Self {
+    Self {
+      path,
+      peers: BTreeMap::new(),
+    }
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&self.path, serde_json::to_vec_pretty(&self.peers)?)
+  }
+
+  /// Returns true if the peer or its address were not known.
+  pub fn insert(&mut self, peer: PeerId, address: Multiaddr) -> bool {
+    self.peers.insert(peer.to_base58(), address.to_string()) != Some(address.to_string())
+  }
+
+  /// Returns true if the peer was known.
+  pub fn remove(&mut self, peer: &PeerId) -> bool {
+    self.peers.remove(&peer.to_base58()).is_some()
+  }
+
+  pub fn peers(&self) -> Vec<(PeerId, Multiaddr)> {
+    self
+      .peers
+      .iter()
+      .filter_map(|(peer, address)| Some((peer.parse().ok()?, address.parse().ok()?)))
+      .collect()
+  }
+}
+
+/// Splits `/ip4/1.2.3.4/tcp/1/p2p/<peer id>` into the peer id and its address.
+pub fn split_peer_id(mut address: Multiaddr) -> Option<(PeerId, Multiaddr)> {
+  match address.pop() {
+    Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash)
+      .ok()
+      .map(|peer| (peer, address)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use uuid::Uuid;
+
+  fn path() -> PathBuf {
+    std::env::temp_dir()
+      .join(Uuid::new_v4().to_string())
+      .join("peers.json")
+  }
+
+  #[test]
+  fn no_peers_are_known_when_the_file_does_not_exist() {
+    let store = PeerStore::load(path()).unwrap();
+
+    assert_eq!(Vec::<(PeerId, Multiaddr)>::new(), store.peers());
+  }
+
+  #[test]
+  fn saved_peers_are_loaded_again() {
+    let path = path();
+
+    let peer = PeerId::random();
+
+    let address: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+
+    let mut store = PeerStore::load(path.clone()).unwrap();
+
+    store.insert(peer, address.clone());
+
+    store.save().unwrap();
+
+    let store = PeerStore::load(path).unwrap();
+
+    assert_eq!(vec![(peer, address)], store.peers());
+  }
+
+  #[test]
+  fn inserting_a_known_peer_with_the_same_address_is_not_a_change() {
+    let mut store = PeerStore::load(path()).unwrap();
+
+    let peer = PeerId::random();
+
+    let address: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+
+    assert!(store.insert(peer, address.clone()));
+    assert!(!store.insert(peer, address));
+    assert!(store.insert(peer, "/ip4/10.0.0.1/tcp/4002".parse().unwrap()));
+  }
+
+  #[test]
+  fn removes_peers() {
+    let mut store = PeerStore::load(path()).unwrap();
+
+    let peer = PeerId::random();
+
+    store.insert(peer, "/ip4/10.0.0.1/tcp/4001".parse().unwrap());
+
+    assert!(store.remove(&peer));
+    assert!(!store.remove(&peer));
+    assert_eq!(Vec::<(PeerId, Multiaddr)>::new(), store.peers());
+  }
+
+  #[test]
+  fn splits_the_peer_id_from_an_address() {
+    let peer = PeerId::random();
+
+    let address: Multiaddr = format!("/ip4/10.0.0.1/tcp/4001/p2p/{}", peer)
+      .parse()
+      .unwrap();
+
+    let expected = Some((peer, "/ip4/10.0.0.1/tcp/4001".parse().unwrap()));
+
+    assert_eq!(expected, split_peer_id(address));
+  }
+
+  #[test]
+  fn addresses_without_a_peer_id_are_not_split() {
+    let address: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+
+    assert_eq!(None, split_peer_id(address));
+  }
+}
@@ -0,0 +1,47 @@
+//! What happened when a transaction in the chain was executed.
+
+use crate::index::Location;
+use crate::transaction::PublicKey;
+use crate::vm::ContractReceipt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Receipt {
+  pub id: String,
+  pub block_count: u128,
+  pub position: usize,
+  #[serde(flatten)]
+  pub status: ExecutionStatus,
+  /// Coins paid for the gas a contract call used, other transactions are free.
+  pub fee: i64,
+  /// Balances of the accounts the transaction touched, right after it ran.
+  pub balances: BTreeMap<PublicKey, i64>,
+  /// Gas used and logs, for contract calls that ran.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub contract: Option<ContractReceipt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExecutionStatus {
+  Success,
+  /// The transaction stays in the block but changed nothing,
+  /// except for the fee a contract call paid.
+  Failed {
+    reason: String,
+  },
+}
+
+impl Receipt {
+  pub fn location(&self) -> Location {
+    Location {
+      block_count: self.block_count,
+      position: self.position,
+    }
+  }
+
+  pub fn succeeded(&self) -> bool {
+    self.status == ExecutionStatus::Success
+  }
+}
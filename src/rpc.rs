@@ -0,0 +1,742 @@
+//! JSON-RPC 2.0 interface to the node.
+
+use crate::chain::ChainError;
+use crate::node::{Node, NodeError};
+use crate::transaction::PublicKey;
+use crate::viewmodel;
+use crate::wallet::SignedTransaction;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+// Application errors, in the range the spec reserves for servers.
+pub const INVALID_SIGNATURE: i64 = -32001;
+pub const INVALID_BLOCK_SIGNATURE: i64 = -32002;
+pub const PEER_BANNED: i64 = -32003;
+pub const RATE_LIMITED: i64 = -32004;
+pub const INVALID_BLOCK_HASH: i64 = -32010;
+pub const INVALID_BLOCK_COUNT: i64 = -32011;
+pub const ACCOUNT_NOT_FOUND: i64 = -32012;
+pub const TRANSACTIONS_FAILED: i64 = -32013;
+pub const FORK_NOT_PREFERRED: i64 = -32014;
+pub const BLOCK_TOO_LARGE: i64 = -32015;
+pub const TOO_MANY_TRANSACTIONS: i64 = -32016;
+pub const TIMESTAMP_BEFORE_PARENT: i64 = -32017;
+pub const TIMESTAMP_IN_THE_FUTURE: i64 = -32018;
+pub const DUPLICATE_TRANSACTION: i64 = -32019;
+pub const BLOCK_NOT_FOUND: i64 = -32020;
+pub const INVALID_MULTISIG: i64 = -32021;
+pub const ACCOUNT_ALREADY_EXISTS: i64 = -32022;
+pub const NOT_MULTISIG: i64 = -32023;
+pub const MULTISIG_REQUIRED: i64 = -32024;
+pub const NOT_ENOUGH_SIGNATURES: i64 = -32025;
+pub const TRANSACTION_LOCKED: i64 = -32026;
+pub const INVALID_ASSET: i64 = -32027;
+pub const ASSET_ALREADY_EXISTS: i64 = -32028;
+pub const ASSET_NOT_FOUND: i64 = -32029;
+pub const INSUFFICIENT_ASSET_BALANCE: i64 = -32030;
+pub const INVALID_BATCH: i64 = -32031;
+pub const INSUFFICIENT_BALANCE: i64 = -32032;
+pub const MEMO_TOO_LARGE: i64 = -32033;
+pub const INVALID_CONTRACT: i64 = -32034;
+pub const CONTRACT_NOT_FOUND: i64 = -32035;
+pub const CONTRACT_FAILED: i64 = -32036;
+pub const RECEIPT_NOT_FOUND: i64 = -32037;
+pub const INSUFFICIENT_STAKE: i64 = -32038;
+pub const INVALID_VOTE: i64 = -32039;
+pub const REORG_PAST_FINALIZED: i64 = -32040;
+pub const INVALID_EVIDENCE: i64 = -32041;
+pub const FORGER_JAILED: i64 = -32042;
+pub const UNEXPECTED_FORGER: i64 = -32043;
+pub const NOT_FORGER: i64 = -32044;
+pub const VALIDATOR_JAILED: i64 = -32045;
+pub const INVALID_TRANSACTION_SIGNATURE: i64 = -32046;
+pub const NOT_EXECUTABLE: i64 = -32047;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+  jsonrpc: String,
+  method: String,
+  #[serde(default)]
+  params: Value,
+  id: Option<Value>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Response {
+  jsonrpc: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  result: Option<Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<RpcError>,
+  id: Value,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RpcError {
+  pub code: i64,
+  pub message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub data: Option<Value>,
+}
+
+impl RpcError {
+  fn new(code: i64, message: &str) -> Self {
+    Self {
+      code,
+      message: message.to_owned(),
+      data: None,
+    }
+  }
+
+  fn with_data(code: i64, message: &str, data: Value) -> Self {
+    Self {
+      code,
+      message: message.to_owned(),
+      data: Some(data),
+    }
+  }
+}
+
+impl From<ChainError> for RpcError {
+  fn from(error: ChainError) -> Self {
+    match error {
+      ChainError::InvalidBlockHash(block) => RpcError::with_data(
+        INVALID_BLOCK_HASH,
+        "invalid block hash",
+        json!({ "hash": block.hash(), "last_hash": block.last_hash() }),
+      ),
+      ChainError::InvalidBlockCount(block) => RpcError::with_data(
+        INVALID_BLOCK_COUNT,
+        "invalid block count",
+        json!({ "hash": block.hash(), "block_count": block.block_count() }),
+      ),
+      ChainError::AccountNotFound(account) => RpcError::with_data(
+        ACCOUNT_NOT_FOUND,
+        "account not found",
+        json!({ "account": account }),
+      ),
+      ChainError::TransactionsFailed(errors) => RpcError::with_data(
+        TRANSACTIONS_FAILED,
+        "transactions failed",
+        json!({ "errors": errors.into_iter().map(RpcError::from).collect::<Vec<_>>() }),
+      ),
+      ChainError::BlockTooLarge(block) => RpcError::with_data(
+        BLOCK_TOO_LARGE,
+        "block too large",
+        json!({ "hash": block.hash(), "size": block.size() }),
+      ),
+      ChainError::TooManyTransactions(block) => RpcError::with_data(
+        TOO_MANY_TRANSACTIONS,
+        "too many transactions",
+        json!({ "hash": block.hash(), "transactions": block.block.transactions.len() }),
+      ),
+      ChainError::TimestampBeforeParent(block) => RpcError::with_data(
+        TIMESTAMP_BEFORE_PARENT,
+        "timestamp before parent",
+        json!({ "hash": block.hash(), "timestamp": block.block.timestamp() }),
+      ),
+      ChainError::TimestampInTheFuture(block) => RpcError::with_data(
+        TIMESTAMP_IN_THE_FUTURE,
+        "timestamp in the future",
+        json!({ "hash": block.hash(), "timestamp": block.block.timestamp() }),
+      ),
+      ChainError::DuplicateTransaction(id) => RpcError::with_data(
+        DUPLICATE_TRANSACTION,
+        "duplicate transaction",
+        json!({ "id": id }),
+      ),
+      ChainError::InvalidTransactionSignature(id) => RpcError::with_data(
+        INVALID_TRANSACTION_SIGNATURE,
+        "invalid transaction signature",
+        json!({ "id": id }),
+      ),
+      ChainError::InvalidMultisig(account) => RpcError::with_data(
+        INVALID_MULTISIG,
+        "invalid multisig",
+        json!({ "account": account }),
+      ),
+      ChainError::AccountAlreadyExists(account) => RpcError::with_data(
+        ACCOUNT_ALREADY_EXISTS,
+        "account already exists",
+        json!({ "account": account }),
+      ),
+      ChainError::NotMultisig(account) => {
+        RpcError::with_data(NOT_MULTISIG, "not multisig", json!({ "account": account }))
+      }
+      ChainError::MultisigRequired(account) => RpcError::with_data(
+        MULTISIG_REQUIRED,
+        "multisig required",
+        json!({ "account": account }),
+      ),
+      ChainError::NotEnoughSignatures {
+        account,
+        required,
+        valid,
+      } => RpcError::with_data(
+        NOT_ENOUGH_SIGNATURES,
+        "not enough signatures",
+        json!({ "account": account, "required": required, "valid": valid }),
+      ),
+      ChainError::MemoTooLarge(id) => {
+        RpcError::with_data(MEMO_TOO_LARGE, "memo too large", json!({ "id": id }))
+      }
+      ChainError::InvalidContract(id) => {
+        RpcError::with_data(INVALID_CONTRACT, "invalid contract", json!({ "id": id }))
+      }
+      ChainError::ContractNotFound(contract) => RpcError::with_data(
+        CONTRACT_NOT_FOUND,
+        "contract not found",
+        json!({ "contract": contract }),
+      ),
+      ChainError::ContractFailed {
+        id,
+        error,
+        gas_used,
+      } => RpcError::with_data(
+        CONTRACT_FAILED,
+        "contract failed",
+        json!({ "id": id, "error": format!("{:?}", error), "gas_used": gas_used }),
+      ),
+      ChainError::InvalidBatch(id) => {
+        RpcError::with_data(INVALID_BATCH, "invalid batch", json!({ "id": id }))
+      }
+      ChainError::InsufficientBalance(account) => RpcError::with_data(
+        INSUFFICIENT_BALANCE,
+        "insufficient balance",
+        json!({ "account": account }),
+      ),
+      ChainError::InvalidAsset(symbol) => {
+        RpcError::with_data(INVALID_ASSET, "invalid asset", json!({ "symbol": symbol }))
+      }
+      ChainError::AssetAlreadyExists(symbol) => RpcError::with_data(
+        ASSET_ALREADY_EXISTS,
+        "asset already exists",
+        json!({ "symbol": symbol }),
+      ),
+      ChainError::AssetNotFound(symbol) => RpcError::with_data(
+        ASSET_NOT_FOUND,
+        "asset not found",
+        json!({ "symbol": symbol }),
+      ),
+      ChainError::InsufficientAssetBalance { account, symbol } => RpcError::with_data(
+        INSUFFICIENT_ASSET_BALANCE,
+        "insufficient asset balance",
+        json!({ "account": account, "symbol": symbol }),
+      ),
+      ChainError::InsufficientStake(validator) => RpcError::with_data(
+        INSUFFICIENT_STAKE,
+        "insufficient stake",
+        json!({ "validator": validator }),
+      ),
+      ChainError::InvalidVote(signed_vote) => RpcError::with_data(
+        INVALID_VOTE,
+        "invalid vote",
+        json!({ "vote": signed_vote.vote }),
+      ),
+      ChainError::InvalidEvidence(id) => {
+        RpcError::with_data(INVALID_EVIDENCE, "invalid evidence", json!({ "id": id }))
+      }
+      ChainError::ForgerJailed(block) => RpcError::with_data(
+        FORGER_JAILED,
+        "forger jailed",
+        json!({ "hash": block.hash(), "forger": block.block.forger() }),
+      ),
+      ChainError::ValidatorJailed(validator) => RpcError::with_data(
+        VALIDATOR_JAILED,
+        "validator jailed",
+        json!({ "validator": validator }),
+      ),
+      ChainError::UnexpectedForger(block) => RpcError::with_data(
+        UNEXPECTED_FORGER,
+        "unexpected forger",
+        json!({ "hash": block.hash(), "forger": block.block.forger() }),
+      ),
+      ChainError::ReorgPastFinalized(block) => RpcError::with_data(
+        REORG_PAST_FINALIZED,
+        "reorg past finalized",
+        json!({ "hash": block.hash(), "block_count": block.block_count() }),
+      ),
+      ChainError::TransactionLocked { id, unlock } => RpcError::with_data(
+        TRANSACTION_LOCKED,
+        "transaction locked",
+        json!({ "id": id, "unlock": unlock }),
+      ),
+      ChainError::ForkNotPreferred(block) => RpcError::with_data(
+        FORK_NOT_PREFERRED,
+        "fork not preferred",
+        json!({ "hash": block.hash(), "block_count": block.block_count() }),
+      ),
+    }
+  }
+}
+
+impl From<NodeError> for RpcError {
+  fn from(error: NodeError) -> Self {
+    match error {
+      NodeError::InvalidSignature {
+        public_key,
+        signed_transaction,
+      } => RpcError::with_data(
+        INVALID_SIGNATURE,
+        "invalid signature",
+        json!({
+          "public_key": public_key,
+          "transaction": signed_transaction.transaction.id(),
+        }),
+      ),
+      NodeError::InvalidBlockSignature(block) => RpcError::with_data(
+        INVALID_BLOCK_SIGNATURE,
+        "invalid block signature",
+        json!({ "hash": block.hash() }),
+      ),
+      NodeError::Chain(error) => error.into(),
+      NodeError::PeerBanned(peer) => {
+        RpcError::with_data(PEER_BANNED, "peer banned", json!({ "peer": peer }))
+      }
+      NodeError::RateLimited(peer) => {
+        RpcError::with_data(RATE_LIMITED, "rate limited", json!({ "peer": peer }))
+      }
+      NodeError::NotForger(forger) => {
+        RpcError::with_data(NOT_FORGER, "not forger", json!({ "forger": forger }))
+      }
+      NodeError::NotExecutable(error) => RpcError::with_data(
+        NOT_EXECUTABLE,
+        "not executable",
+        json!({ "error": RpcError::from(error) }),
+      ),
+    }
+  }
+}
+
+/// Any kind of transaction, already signed, e.g. a cosigned multisig transfer.
+#[derive(Debug, Deserialize)]
+struct SignedTransactionParams {
+  public_key: PublicKey,
+  signed_transaction: SignedTransaction,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReceiptParams {
+  id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockByHeightParams {
+  height: u128,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockByHashParams {
+  hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountParams {
+  account: String,
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+  serde_json::from_value(params).map_err(|error| {
+    RpcError::with_data(
+      INVALID_PARAMS,
+      "invalid params",
+      json!({ "error": error.to_string() }),
+    )
+  })
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, RpcError> {
+  serde_json::to_value(value).map_err(|error| {
+    RpcError::with_data(
+      INTERNAL_ERROR,
+      "internal error",
+      json!({ "error": error.to_string() }),
+    )
+  })
+}
+
+fn call(node: &mut Node, method: &str, params_value: Value) -> Result<Value, RpcError> {
+  match method {
+    "send_transaction" => {
+      let input: viewmodel::AddTransactionInput = params(params_value)?;
+
+      let public_key = input.public_key.clone();
+
+      let signed_transaction: SignedTransaction = input.into();
+
+      let id = signed_transaction.transaction.id().clone();
+
+      node.transaction(&public_key, signed_transaction)?;
+
+      to_value(id)
+    }
+    "send_signed_transaction" => {
+      let SignedTransactionParams {
+        public_key,
+        signed_transaction,
+      } = params(params_value)?;
+
+      let id = signed_transaction.transaction.id().clone();
+
+      node.transaction(&public_key, signed_transaction)?;
+
+      to_value(id)
+    }
+    "get_block_by_height" => {
+      let BlockByHeightParams { height } = params(params_value)?;
+
+      match node.chain().block_by_height(height) {
+        None => Err(RpcError::with_data(
+          BLOCK_NOT_FOUND,
+          "block not found",
+          json!({ "height": height }),
+        )),
+        Some(block) => to_value(viewmodel::BlockOutput::from(block)),
+      }
+    }
+    "get_block_by_hash" => {
+      let BlockByHashParams { hash } = params(params_value)?;
+
+      match node.chain().block_by_hash(&hash) {
+        None => Err(RpcError::with_data(
+          BLOCK_NOT_FOUND,
+          "block not found",
+          json!({ "hash": hash }),
+        )),
+        Some(block) => to_value(viewmodel::BlockOutput::from(block)),
+      }
+    }
+    "get_receipt" => {
+      let ReceiptParams { id } = params(params_value)?;
+
+      match node.chain().receipt(&id) {
+        None => Err(RpcError::with_data(
+          RECEIPT_NOT_FOUND,
+          "receipt not found",
+          json!({ "id": id }),
+        )),
+        Some(receipt) => to_value(receipt),
+      }
+    }
+    "get_balance" => {
+      let AccountParams { account } = params(params_value)?;
+
+      match node.chain().account().balance(&account) {
+        None => Err(ChainError::AccountNotFound(account).into()),
+        Some(balance) => to_value(balance),
+      }
+    }
+    // Counts executed transactions, it isn't used for replay protection.
+    "get_nonce" => {
+      let AccountParams { account } = params(params_value)?;
+
+      to_value(node.chain().account().nonce(&account))
+    }
+    "get_pending_transactions" => {
+      let mut transactions: Vec<&SignedTransaction> = node.transactions().iter().collect();
+
+      transactions.sort_by_key(|signed_transaction| signed_transaction.transaction.hash());
+
+      to_value(transactions)
+    }
+    "get_supply" => to_value(viewmodel::SupplyOutput::from(&*node)),
+    "get_node_info" => to_value(viewmodel::NodeInfoOutput::from(&*node)),
+    "get_finality" => to_value(viewmodel::FinalityOutput::from(&*node)),
+    "get_epoch" => to_value(viewmodel::EpochOutput::from(&*node)),
+    _ => Err(RpcError::with_data(
+      METHOD_NOT_FOUND,
+      "method not found",
+      json!({ "method": method }),
+    )),
+  }
+}
+
+fn single(node: &mut Node, request: Value) -> Option<Response> {
+  let request: Request = match serde_json::from_value(request) {
+    Err(error) => {
+      return Some(Response {
+        jsonrpc: "2.0",
+        result: None,
+        error: Some(RpcError::with_data(
+          INVALID_REQUEST,
+          "invalid request",
+          json!({ "error": error.to_string() }),
+        )),
+        id: Value::Null,
+      })
+    }
+    Ok(request) => request,
+  };
+
+  let result = if request.jsonrpc != "2.0" {
+    Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))
+  } else {
+    call(node, &request.method, request.params)
+  };
+
+  // Requests without an id are notifications and get no response.
+  let id = request.id?;
+
+  Some(match result {
+    Ok(result) => Response {
+      jsonrpc: "2.0",
+      result: Some(result),
+      error: None,
+      id,
+    },
+    Err(error) => Response {
+      jsonrpc: "2.0",
+      result: None,
+      error: Some(error),
+      id,
+    },
+  })
+}
+
+/// Handles a request or a batch of requests, returns nothing if
+/// there is nothing to respond with.
+pub fn handle(node: &mut Node, body: &[u8]) -> Option<Value> {
+  let request: Value = match serde_json::from_slice(body) {
+    Err(error) => {
+      let response = Response {
+        jsonrpc: "2.0",
+        result: None,
+        error: Some(RpcError::with_data(
+          PARSE_ERROR,
+          "parse error",
+          json!({ "error": error.to_string() }),
+        )),
+        id: Value::Null,
+      };
+      return Some(serde_json::to_value(response).unwrap());
+    }
+    Ok(request) => request,
+  };
+
+  match request {
+    Value::Array(requests) if requests.is_empty() => Some(
+      serde_json::to_value(Response {
+        jsonrpc: "2.0",
+        result: None,
+        error: Some(RpcError::new(INVALID_REQUEST, "empty batch")),
+        id: Value::Null,
+      })
+      .unwrap(),
+    ),
+    Value::Array(requests) => {
+      let responses: Vec<Response> = requests
+        .into_iter()
+        .filter_map(|request| single(node, request))
+        .collect();
+
+      if responses.is_empty() {
+        None
+      } else {
+        Some(serde_json::to_value(responses).unwrap())
+      }
+    }
+    request => single(node, request).map(|response| serde_json::to_value(response).unwrap()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::transaction::Transaction;
+  use crate::wallet::Wallet;
+  use serde_json::json;
+
+  fn request(node: &mut Node, request: Value) -> Value {
+    handle(node, request.to_string().as_bytes()).unwrap()
+  }
+
+  #[test]
+  fn returns_parse_error_for_invalid_json() {
+    let mut node = Node::new();
+
+    let response = handle(&mut node, b"{").unwrap();
+
+    assert_eq!(json!(PARSE_ERROR), response["error"]["code"]);
+    assert_eq!(Value::Null, response["id"]);
+  }
+
+  #[test]
+  fn returns_method_not_found_for_unknown_methods() {
+    let mut node = Node::new();
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "unknown", "id": 1}),
+    );
+
+    assert_eq!(json!(METHOD_NOT_FOUND), response["error"]["code"]);
+    assert_eq!(json!({ "method": "unknown" }), response["error"]["data"]);
+    assert_eq!(json!(1), response["id"]);
+  }
+
+  #[test]
+  fn returns_invalid_params_when_params_are_missing() {
+    let mut node = Node::new();
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "get_block_by_height", "id": 1}),
+    );
+
+    assert_eq!(json!(INVALID_PARAMS), response["error"]["code"]);
+  }
+
+  #[test]
+  fn returns_blocks_by_height() {
+    let mut node = Node::new();
+
+    let block = node.forge().unwrap();
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "get_block_by_height", "params": {"height": 1}, "id": "a"}),
+    );
+
+    assert_eq!(json!(block.hash()), response["result"]["hash"]);
+    assert_eq!(json!("a"), response["id"]);
+  }
+
+  #[test]
+  fn returns_block_not_found_for_unknown_hashes() {
+    let mut node = Node::new();
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "get_block_by_hash", "params": {"hash": "unknown"}, "id": 1}),
+    );
+
+    assert_eq!(json!(BLOCK_NOT_FOUND), response["error"]["code"]);
+    assert_eq!(json!({ "hash": "unknown" }), response["error"]["data"]);
+  }
+
+  #[test]
+  fn maps_node_errors_to_error_codes() {
+    let mut node = Node::new();
+
+    let wallet = Wallet::new();
+
+    let transaction = Transaction::transfer(
+      String::from("sender_public_key"),
+      String::from("receiver_public_key"),
+      10,
+    );
+
+    let signed_transaction = wallet.sign_transaction(transaction);
+
+    let (id, timestamp, amount) = match &signed_transaction.transaction {
+      Transaction::Transfer {
+        id,
+        timestamp,
+        amount,
+        ..
+      } => (id.clone(), *timestamp, *amount),
+      _ => unreachable!(),
+    };
+
+    let public_key = Wallet::new().public_key();
+
+    let params = json!({
+      "public_key": public_key,
+      "signature": signed_transaction.signature,
+      "id": id,
+      "sender": "sender_public_key",
+      "receiver": "receiver_public_key",
+      "amount": amount,
+      "timestamp": timestamp,
+    });
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "send_transaction", "params": params, "id": 1}),
+    );
+
+    assert_eq!(json!(INVALID_SIGNATURE), response["error"]["code"]);
+    assert_eq!(
+      json!({ "public_key": public_key, "transaction": id }),
+      response["error"]["data"]
+    );
+    assert_eq!(
+      RpcError::from(ChainError::AccountNotFound(String::from("a"))).code,
+      ACCOUNT_NOT_FOUND
+    );
+  }
+
+  #[test]
+  fn error_data_has_named_fields() {
+    let error = RpcError::from(ChainError::NotEnoughSignatures {
+      account: String::from("a"),
+      required: 2,
+      valid: 1,
+    });
+
+    assert_eq!(
+      Some(json!({ "account": "a", "required": 2, "valid": 1 })),
+      error.data
+    );
+
+    let error = RpcError::from(ChainError::TransactionsFailed(vec![
+      ChainError::AccountNotFound(String::from("a")),
+      ChainError::InsufficientBalance(String::from("b")),
+    ]));
+
+    assert_eq!(
+      Some(json!({
+        "errors": [
+          { "code": ACCOUNT_NOT_FOUND, "message": "account not found", "data": { "account": "a" } },
+          { "code": INSUFFICIENT_BALANCE, "message": "insufficient balance", "data": { "account": "b" } },
+        ]
+      })),
+      error.data
+    );
+  }
+
+  #[test]
+  fn notifications_get_no_response() {
+    let mut node = Node::new();
+
+    let body = json!({"jsonrpc": "2.0", "method": "get_node_info"}).to_string();
+
+    assert_eq!(None, handle(&mut node, body.as_bytes()));
+  }
+
+  #[test]
+  fn answers_every_request_in_a_batch() {
+    let mut node = Node::new();
+
+    let response = request(
+      &mut node,
+      json!([
+        {"jsonrpc": "2.0", "method": "get_nonce", "params": {"account": "a"}, "id": 1},
+        {"jsonrpc": "2.0", "method": "get_pending_transactions", "id": 2},
+      ]),
+    );
+
+    assert_eq!(json!(0), response[0]["result"]);
+    assert_eq!(json!([]), response[1]["result"]);
+  }
+
+  #[test]
+  fn returns_the_circulating_supply() {
+    let mut node = Node::new();
+
+    node.forge().unwrap();
+
+    let response = request(
+      &mut node,
+      json!({"jsonrpc": "2.0", "method": "get_supply", "id": 1}),
+    );
+
+    assert_eq!(json!(50), response["result"]["circulating"]);
+  }
+}
@@ -0,0 +1,360 @@
+//! Runs several nodes in one process, connected by an in-memory transport,
+//! so consensus and gossip can be tested without real sockets.
+
+use crate::chain::Chain;
+use crate::clock::ManualClock;
+use crate::node::{Message, Node, NodeError, Peer, Recipient};
+use crate::transaction::PublicKey;
+use crate::wallet::{SignedTransaction, Wallet};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Upper bound on deliveries before we assume the nodes will never settle.
+const MAX_DELIVERIES: usize = 100_000;
+
+/// Delivers messages between nodes in the order they were sent.
+///
+/// Every node belongs to a group and messages are only delivered
+/// between nodes in the same group.
+#[derive(Debug)]
+struct MemoryTransport {
+  in_flight: VecDeque<(usize, usize, Message)>,
+  groups: Vec<usize>,
+}
+
+impl MemoryTransport {
+  fn new(size: usize) -> Self {
+    Self {
+      in_flight: VecDeque::new(),
+      groups: vec![0; size],
+    }
+  }
+
+  fn connected(&self, from: usize, to: usize) -> bool {
+    from != to && self.groups[from] == self.groups[to]
+  }
+
+  fn send(&mut self, from: usize, to: usize, message: Message) {
+    if self.connected(from, to) {
+      self.in_flight.push_back((from, to, message));
+    }
+  }
+
+  fn receive(&mut self) -> Option<(usize, usize, Message)> {
+    while let Some((from, to, message)) = self.in_flight.pop_front() {
+      // Messages sent before a partition was created are lost.
+      if self.connected(from, to) {
+        return Some((from, to, message));
+      }
+    }
+
+    None
+  }
+}
+
+#[derive(Debug)]
+pub struct Simulation {
+  pub nodes: Vec<Node>,
+  pub clock: ManualClock,
+  transport: MemoryTransport,
+}
+
+impl Simulation {
+  pub fn new(size: usize, allocations: Vec<(PublicKey, i64)>) -> Self {
+    let clock = ManualClock::new();
+
+    let nodes = (0..size)
+      .map(|_| {
+        Node::from_parts(
+          Wallet::new(),
+          Chain::with_allocations(allocations.clone()),
+          Arc::new(clock.clone()),
+        )
+      })
+      .collect();
+
+    Self {
+      nodes,
+      clock,
+      transport: MemoryTransport::new(size),
+    }
+  }
+
+  fn peer(index: usize) -> Peer {
+    format!("node-{}", index)
+  }
+
+  fn index(peer: &Peer) -> usize {
+    peer.trim_start_matches("node-").parse().unwrap()
+  }
+
+  pub fn transaction(
+    &mut self,
+    node: usize,
+    public_key: &PublicKey,
+    signed_transaction: SignedTransaction,
+  ) -> Result<(), NodeError> {
+    self.nodes[node].transaction(public_key, signed_transaction)?;
+    self.run_until_idle();
+    Ok(())
+  }
+
+  pub fn forge(&mut self, node: usize) -> Result<(), NodeError> {
+    self.nodes[node].forge()?;
+    self.run_until_idle();
+    Ok(())
+  }
+
+  /// Moves the clock forward and lets every node react to it.
+  pub fn advance(&mut self, duration: Duration) {
+    self.clock.advance(duration);
+
+    for node in self.nodes.iter_mut() {
+      node.tick();
+    }
+
+    self.run_until_idle();
+  }
+
+  /// Splits the network so only nodes in the same group can talk to each other.
+  /// Nodes that are not in any group are isolated.
+  pub fn partition(&mut self, groups: &[&[usize]]) {
+    let isolated = groups.len();
+
+    for (index, group) in self.transport.groups.iter_mut().enumerate() {
+      *group = isolated + index;
+    }
+
+    for (group, nodes) in groups.iter().enumerate() {
+      for node in nodes.iter() {
+        self.transport.groups[*node] = group;
+      }
+    }
+  }
+
+  pub fn heal(&mut self) {
+    for group in self.transport.groups.iter_mut() {
+      *group = 0;
+    }
+  }
+
+  fn flush(&mut self) {
+    for from in 0..self.nodes.len() {
+      for outgoing in self.nodes[from].outgoing() {
+        match outgoing.recipient {
+          Recipient::All => {
+            for to in 0..self.nodes.len() {
+              self.transport.send(from, to, outgoing.message.clone());
+            }
+          }
+          Recipient::Peer(peer) => {
+            self
+              .transport
+              .send(from, Simulation::index(&peer), outgoing.message)
+          }
+        }
+      }
+    }
+  }
+
+  /// Delivers messages until no node has anything left to say.
+  pub fn run_until_idle(&mut self) {
+    self.flush();
+
+    let mut deliveries = 0;
+
+    while let Some((from, to, message)) = self.transport.receive() {
+      deliveries += 1;
+
+      assert!(
+        deliveries < MAX_DELIVERIES,
+        "nodes did not settle after {} deliveries",
+        MAX_DELIVERIES
+      );
+
+      // Peers reject invalid messages, that's not a reason to stop the simulation.
+      let _ = self.nodes[to].message(&Simulation::peer(from), message);
+
+      self.flush();
+    }
+  }
+
+  pub fn assert_converged(&self) {
+    let first = &self.nodes[0];
+
+    for (index, node) in self.nodes.iter().enumerate().skip(1) {
+      assert_eq!(
+        first.chain().tip(),
+        node.chain().tip(),
+        "node {} has a different tip than node 0",
+        index
+      );
+      assert_eq!(
+        first.chain().account(),
+        node.chain().account(),
+        "node {} has different balances than node 0",
+        index
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::transaction::Transaction;
+
+  fn users() -> (Wallet, Wallet, Vec<(PublicKey, i64)>) {
+    let alice = Wallet::new();
+
+    let bob = Wallet::new();
+
+    let allocations = vec![(alice.public_key(), 100), (bob.public_key(), 100)];
+
+    (alice, bob, allocations)
+  }
+
+  fn transfer(from: &Wallet, to: &Wallet, amount: i64) -> SignedTransaction {
+    from.sign_transaction(Transaction::transfer(
+      from.public_key(),
+      to.public_key(),
+      amount,
+    ))
+  }
+
+  #[test]
+  fn transactions_are_gossiped_to_every_node() {
+    let (alice, bob, allocations) = users();
+
+    let mut simulation = Simulation::new(3, allocations);
+
+    let transaction = transfer(&alice, &bob, 10);
+
+    simulation
+      .transaction(0, &alice.public_key(), transaction.clone())
+      .unwrap();
+
+    for node in simulation.nodes.iter() {
+      assert!(node.transactions().contains(&transaction));
+    }
+
+    simulation.assert_converged();
+  }
+
+  #[test]
+  fn forged_blocks_are_applied_by_every_node() {
+    let (alice, bob, allocations) = users();
+
+    let mut simulation = Simulation::new(3, allocations);
+
+    simulation
+      .transaction(1, &alice.public_key(), transfer(&alice, &bob, 10))
+      .unwrap();
+
+    simulation.clock.advance(Duration::from_secs(1));
+
+    simulation.forge(2).unwrap();
+
+    simulation.assert_converged();
+
+    let chain = simulation.nodes[0].chain();
+
+    assert_eq!(1, chain.tip().block_count());
+    assert_eq!(1_000_000, chain.tip().block.timestamp());
+    assert_eq!(Some(90), chain.account().balance(&alice.public_key()));
+    assert_eq!(Some(110), chain.account().balance(&bob.public_key()));
+  }
+
+  #[test]
+  fn messages_are_not_delivered_across_partitions() {
+    let (alice, bob, allocations) = users();
+
+    let mut simulation = Simulation::new(3, allocations);
+
+    simulation.partition(&[&[0, 1], &[2]]);
+
+    let transaction = transfer(&alice, &bob, 10);
+
+    simulation
+      .transaction(0, &alice.public_key(), transaction.clone())
+      .unwrap();
+
+    assert!(simulation.nodes[1].transactions().contains(&transaction));
+    assert!(!simulation.nodes[2].transactions().contains(&transaction));
+  }
+
+  #[test]
+  fn nodes_converge_on_the_longest_chain_after_a_partition_heals() {
+    let (alice, bob, allocations) = users();
+
+    let mut simulation = Simulation::new(4, allocations);
+
+    simulation.partition(&[&[0, 1], &[2, 3]]);
+
+    simulation
+      .transaction(0, &alice.public_key(), transfer(&alice, &bob, 10))
+      .unwrap();
+    simulation.forge(1).unwrap();
+    simulation.advance(Duration::from_secs(1));
+    simulation.forge(0).unwrap();
+
+    let orphaned = transfer(&bob, &alice, 30);
+
+    simulation
+      .transaction(2, &bob.public_key(), orphaned.clone())
+      .unwrap();
+    simulation.forge(3).unwrap();
+
+    simulation.heal();
+    simulation.advance(Duration::from_secs(1));
+
+    simulation.assert_converged();
+
+    let node = &simulation.nodes[3];
+
+    assert_eq!(2, node.chain().tip().block_count());
+    assert_eq!(
+      Some(90),
+      node.chain().account().balance(&alice.public_key())
+    );
+    assert_eq!(Some(110), node.chain().account().balance(&bob.public_key()));
+
+    // The transaction from the losing fork goes back to the pool.
+    assert!(node.transactions().contains(&orphaned));
+
+    simulation.forge(2).unwrap();
+
+    simulation.assert_converged();
+
+    let node = &simulation.nodes[0];
+
+    assert_eq!(
+      Some(120),
+      node.chain().account().balance(&alice.public_key())
+    );
+    assert_eq!(Some(80), node.chain().account().balance(&bob.public_key()));
+  }
+
+  #[test]
+  fn forks_of_the_same_length_are_resolved_the_same_way_by_every_node() {
+    let (_, _, allocations) = users();
+
+    let mut simulation = Simulation::new(2, allocations);
+
+    simulation.partition(&[&[0], &[1]]);
+
+    simulation.forge(0).unwrap();
+    simulation.forge(1).unwrap();
+
+    assert_ne!(
+      simulation.nodes[0].chain().tip(),
+      simulation.nodes[1].chain().tip()
+    );
+
+    simulation.heal();
+    simulation.advance(Duration::from_secs(1));
+
+    simulation.assert_converged();
+  }
+}
@@ -0,0 +1,405 @@
+use crate::chain::Chain;
+use crate::clock::SystemClock;
+use crate::finality::Finality;
+use crate::journal::FileJournal;
+use crate::node::Node;
+use crate::wallet::{SignedBlock, SignedTransaction, Wallet};
+use libp2p::identity::{ed25519, Keypair};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Where the node saves its chain as it changes, so accepted blocks
+/// survive a crash and not only a clean shutdown.
+pub trait ChainStore: Debug + Send {
+  fn chain_changed(&mut self, chain: &Chain) -> io::Result<()>;
+}
+
+/// Node state kept in the data directory so it survives restarts.
+#[derive(Debug, Clone)]
+pub struct Storage {
+  dir: PathBuf,
+}
+
+impl Storage {
+  /// Creates the data directory if it doesn't exist yet.
+  pub fn open(dir: PathBuf) -> io::Result<Self> {
+    fs::create_dir_all(&dir)?;
+
+    Ok(Self { dir })
+  }
+
+  /// Blocks after genesis, oldest first.
+  pub fn load_blocks(&self) -> io::Result<Vec<SignedBlock>> {
+    self.load("chain.json")
+  }
+
+  pub fn save_blocks(&self, blocks: &[SignedBlock]) -> io::Result<()> {
+    self.save("chain.json", &blocks)
+  }
+
+  /// Votes and the final checkpoint, so we never reorg past it after a restart.
+  pub fn load_finality(&self) -> io::Result<Finality> {
+    self.load("finality.json")
+  }
+
+  pub fn save_finality(&self, finality: &Finality) -> io::Result<()> {
+    self.save("finality.json", finality)
+  }
+
+  /// The node's key, created the first time so validators keep their
+  /// identity and stake across restarts.
+  pub fn load_wallet(&self) -> io::Result<Wallet> {
+    match fs::read(self.dir.join("wallet.pem")) {
+      Ok(pem) => Wallet::from_private_key_pem(&pem)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        let wallet = Wallet::new();
+
+        self.save_secret("wallet.pem", &wallet.private_key_pem())?;
+
+        Ok(wallet)
+      }
+      Err(error) => Err(error),
+    }
+  }
+
+  /// The node's network identity, created the first time so peers
+  /// remembered by id can still be dialed after a restart.
+  pub fn load_identity(&self) -> io::Result<Keypair> {
+    match fs::read(self.dir.join("identity.key")) {
+      Ok(mut bytes) => ed25519::Keypair::decode(&mut bytes)
+        .map(Keypair::Ed25519)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        let keypair = ed25519::Keypair::generate();
+
+        self.save_secret("identity.key", &keypair.encode())?;
+
+        Ok(Keypair::Ed25519(keypair))
+      }
+      Err(error) => Err(error),
+    }
+  }
+
+  /// Like `save`, but only the owner may read the file.
+  fn save_secret(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+    let temporary_path = self.dir.join(format!("{}.tmp", name));
+
+    fs::write(&temporary_path, contents)?;
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      fs::set_permissions(&temporary_path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(temporary_path, self.dir.join(name))
+  }
+
+  fn journal_path(&self) -> PathBuf {
+    self.dir.join("mempool.log")
+  }
+
+  /// Rebuilds the node from the saved chain and mempool journal, the node
+  /// keeps saving its chain and journaling pool changes from then on.
+  pub fn load_node(&self, mut chain: Chain) -> io::Result<Node> {
+    chain
+      .restore(self.load_blocks()?)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", error)))?;
+
+    chain.restore_finality(self.load_finality()?);
+
+    let mut node = Node::from_parts(self.load_wallet()?, chain, Arc::new(SystemClock));
+
+    node.restore_transactions(FileJournal::replay(&self.journal_path())?);
+
+    let mut transactions: Vec<SignedTransaction> = node.transactions().iter().cloned().collect();
+
+    transactions.sort_by_key(|signed_transaction| signed_transaction.transaction.created_at());
+
+    node.set_journal(Box::new(FileJournal::create(
+      self.journal_path(),
+      &transactions,
+    )?));
+
+    node.set_chain_store(Box::new(self.clone()));
+
+    Ok(node)
+  }
+
+  /// Saves the chain and its finality, the mempool is already journaled as it changes.
+  pub fn save_node(&self, node: &Node) -> io::Result<()> {
+    self.save_chain(node.chain())
+  }
+
+  fn save_chain(&self, chain: &Chain) -> io::Result<()> {
+    self.save_blocks(&chain.blocks()[1..])?;
+    self.save_finality(chain.finality())
+  }
+
+  /// A missing file means nothing was saved yet.
+  fn load<T: DeserializeOwned + Default>(&self, name: &str) -> io::Result<T> {
+    match fs::read(self.dir.join(name)) {
+      Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+      Err(error) => Err(error),
+    }
+  }
+
+  /// Writes to a temporary file first so a crash never leaves a half written file behind.
+  fn save<T: Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+    let path = self.dir.join(name);
+
+    let temporary_path = self.dir.join(format!("{}.tmp", name));
+
+    fs::write(&temporary_path, serde_json::to_vec(value)?)?;
+
+    fs::rename(temporary_path, path)
+  }
+}
+
+impl ChainStore for Storage {
+  fn chain_changed(&mut self, chain: &Chain) -> io::Result<()> {
+    self.save_chain(chain)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::Block;
+  use crate::consensus::ConsensusParams;
+  use crate::transaction::Transaction;
+  use crate::wallet::Wallet;
+  use uuid::Uuid;
+
+  fn storage() -> Storage {
+    Storage::open(std::env::temp_dir().join(Uuid::new_v4().to_string())).unwrap()
+  }
+
+  #[test]
+  fn nothing_is_loaded_when_nothing_was_saved() {
+    let storage = storage();
+
+    let node = storage.load_node(Chain::new()).unwrap();
+
+    assert_eq!(Vec::<SignedBlock>::new(), storage.load_blocks().unwrap());
+    assert_eq!(0, node.chain().tip().block_count());
+    assert!(node.transactions().is_empty());
+  }
+
+  #[test]
+  fn saved_blocks_are_loaded_again() {
+    let storage = storage();
+
+    let wallet = Wallet::new();
+
+    let blocks = vec![wallet.sign_block(Block::new(
+      Vec::new(),
+      SignedBlock::genesis().hash(),
+      wallet.public_key(),
+      1,
+    ))];
+
+    storage.save_blocks(&blocks).unwrap();
+
+    let storage = Storage::open(storage.dir.clone()).unwrap();
+
+    assert_eq!(blocks, storage.load_blocks().unwrap());
+  }
+
+  #[test]
+  fn saved_nodes_are_loaded_again_with_their_pending_transactions() {
+    let storage = storage();
+
+    let wallet = Wallet::new();
+
+    let sender = wallet.public_key();
+
+    let receiver = String::from("receiver_public_key");
+
+    let allocations = vec![(sender.clone(), 100), (receiver.clone(), 0)];
+
+    let mut node = storage
+      .load_node(Chain::with_allocations(allocations.clone()))
+      .unwrap();
+
+    node
+      .transaction(
+        &wallet.public_key(),
+        wallet.sign_transaction(Transaction::transfer(sender.clone(), receiver.clone(), 10)),
+      )
+      .unwrap();
+
+    node.forge().unwrap();
+
+    let pending = wallet.sign_transaction(Transaction::transfer(sender, receiver, 20));
+
+    node
+      .transaction(&wallet.public_key(), pending.clone())
+      .unwrap();
+
+    storage.save_node(&node).unwrap();
+
+    let loaded = storage
+      .load_node(Chain::with_allocations(allocations))
+      .unwrap();
+
+    assert_eq!(node.chain().tip(), loaded.chain().tip());
+    assert_eq!(
+      vec![&pending],
+      loaded.transactions().iter().collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn pending_transactions_survive_without_a_clean_shutdown() {
+    let storage = storage();
+
+    let wallet = Wallet::new();
+
+    let allocations = vec![
+      (wallet.public_key(), 100),
+      (String::from("receiver_public_key"), 0),
+    ];
+
+    let mut node = storage
+      .load_node(Chain::with_allocations(allocations.clone()))
+      .unwrap();
+
+    let pending = wallet.sign_transaction(Transaction::transfer(
+      wallet.public_key(),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    node
+      .transaction(&wallet.public_key(), pending.clone())
+      .unwrap();
+
+    drop(node);
+
+    let loaded = storage
+      .load_node(Chain::with_allocations(allocations))
+      .unwrap();
+
+    assert!(loaded.transactions().contains(&pending));
+  }
+
+  #[test]
+  fn blocks_survive_without_a_clean_shutdown() {
+    let storage = storage();
+
+    let mut node = storage.load_node(Chain::new()).unwrap();
+
+    node.forge().unwrap();
+
+    let tip = node.chain().tip().clone();
+
+    drop(node);
+
+    let loaded = storage.load_node(Chain::new()).unwrap();
+
+    assert_eq!(tip, *loaded.chain().tip());
+  }
+
+  #[test]
+  fn drops_pending_transactions_that_cant_be_executed_anymore() {
+    let storage = storage();
+
+    let wallet = Wallet::new();
+
+    let mut node = storage
+      .load_node(Chain::with_allocations(vec![
+        (wallet.public_key(), 100),
+        (String::from("receiver_public_key"), 0),
+      ]))
+      .unwrap();
+
+    node
+      .transaction(
+        &wallet.public_key(),
+        wallet.sign_transaction(Transaction::transfer(
+          wallet.public_key(),
+          String::from("receiver_public_key"),
+          10,
+        )),
+      )
+      .unwrap();
+
+    drop(node);
+
+    let loaded = storage.load_node(Chain::new()).unwrap();
+
+    assert!(loaded.transactions().is_empty());
+  }
+
+  #[test]
+  fn final_checkpoints_are_still_final_after_a_restart() {
+    let storage = storage();
+
+    let validator = Wallet::new();
+
+    let chain = || {
+      Chain::with_params(
+        vec![(validator.public_key(), 10)],
+        ConsensusParams {
+          checkpoint_interval: 2,
+          ..ConsensusParams::default()
+        },
+        Arc::new(SystemClock),
+      )
+    };
+
+    let mut node = storage.load_node(chain()).unwrap();
+
+    node
+      .transaction(
+        &validator.public_key(),
+        validator.sign_transaction(Transaction::stake(validator.public_key(), 10)),
+      )
+      .unwrap();
+
+    node.forge().unwrap();
+    node.forge().unwrap();
+
+    node
+      .vote(validator.sign_vote(node.chain().last_checkpoint().unwrap()))
+      .unwrap();
+
+    assert_eq!(2, node.chain().finality().finalized_height());
+
+    storage.save_node(&node).unwrap();
+
+    let loaded = storage.load_node(chain()).unwrap();
+
+    assert_eq!(2, loaded.chain().finality().finalized_height());
+  }
+
+  #[test]
+  fn the_node_keeps_its_key_across_restarts() {
+    let storage = storage();
+
+    let node = storage.load_node(Chain::new()).unwrap();
+
+    let loaded = storage.load_node(Chain::new()).unwrap();
+
+    assert_eq!(node.public_key(), loaded.public_key());
+  }
+
+  #[test]
+  fn the_network_identity_is_kept_across_restarts() {
+    let storage = storage();
+
+    let identity = storage.load_identity().unwrap();
+
+    assert_eq!(
+      identity.public().into_peer_id(),
+      storage.load_identity().unwrap().public().into_peer_id()
+    );
+  }
+}
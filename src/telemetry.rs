@@ -0,0 +1,19 @@
+//! Sets up where traces go.
+//!
+//! The library only emits `tracing` events, nothing is printed
+//! unless the binary installs a subscriber with `init`.
+
+use crate::config::{Config, LogFormat};
+use tracing_subscriber::EnvFilter;
+
+/// `Config::parse` already rejected invalid log levels.
+pub fn init(config: &Config) {
+  let filter = EnvFilter::new(&config.log_level);
+
+  let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+  match config.log_format {
+    LogFormat::Text => subscriber.init(),
+    LogFormat::Json => subscriber.json().init(),
+  }
+}
@@ -1,18 +1,148 @@
+use crate::vm::Instruction;
+use crate::wallet::SignedBlock;
+use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use std::time::SystemTime;
 use uuid::Uuid;
 
 pub type PublicKey = String;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Transaction {
   Transfer {
     id: String,
     sender: PublicKey,
     receiver: PublicKey,
     amount: i64,
+    /// Free text such as a payment reference, covered by the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
     timestamp: u128,
   },
+  /// Transfer that can't be included before `unlock`.
+  LockedTransfer {
+    id: String,
+    sender: PublicKey,
+    receiver: PublicKey,
+    amount: i64,
+    unlock: Lock,
+    /// Free text such as a payment reference, covered by the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    timestamp: u128,
+  },
+  /// Pays every output from the sender's balance, all or nothing.
+  BatchTransfer {
+    id: String,
+    sender: PublicKey,
+    outputs: Vec<Output>,
+    /// Free text such as a payment reference, covered by the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    timestamp: u128,
+  },
+  /// Creates a new asset whose whole supply goes to the issuer.
+  IssueAsset {
+    id: String,
+    issuer: PublicKey,
+    symbol: String,
+    decimals: u8,
+    supply: i64,
+    timestamp: u128,
+  },
+  /// Moves `amount` units of the asset named by `symbol`.
+  AssetTransfer {
+    id: String,
+    sender: PublicKey,
+    receiver: PublicKey,
+    symbol: String,
+    amount: i64,
+    /// Free text such as a payment reference, covered by the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    timestamp: u128,
+  },
+  /// Creates the contract at `contract` running `code`.
+  DeployContract {
+    id: String,
+    deployer: PublicKey,
+    contract: PublicKey,
+    code: Vec<Instruction>,
+    timestamp: u128,
+  },
+  /// Runs the contract's code with `args`, using at most `gas_limit` gas.
+  CallContract {
+    id: String,
+    caller: PublicKey,
+    contract: PublicKey,
+    args: Vec<i64>,
+    gas_limit: u64,
+    timestamp: u128,
+  },
+  /// Bonds `amount` of the validator's balance as stake.
+  Stake {
+    id: String,
+    validator: PublicKey,
+    amount: i64,
+    timestamp: u128,
+  },
+  /// Returns `amount` of the validator's stake to its balance, once it
+  /// stayed unbonding for an epoch.
+  Unstake {
+    id: String,
+    validator: PublicKey,
+    amount: i64,
+    timestamp: u128,
+  },
+  /// Proof that a forger signed two different blocks at the same height.
+  /// Anyone may submit it, the forger's stake is slashed and the forger jailed.
+  Evidence {
+    id: String,
+    reporter: PublicKey,
+    first: Box<SignedBlock>,
+    second: Box<SignedBlock>,
+    timestamp: u128,
+  },
+  /// Registers `account` as spendable only with signatures from
+  /// at least `threshold` of `keys`.
+  CreateMultisig {
+    id: String,
+    account: PublicKey,
+    keys: Vec<PublicKey>,
+    threshold: usize,
+    timestamp: u128,
+  },
+  /// Transfer out of a multisig account, authorized by the cosignatures
+  /// of the signed transaction.
+  MultisigTransfer {
+    id: String,
+    sender: PublicKey,
+    receiver: PublicKey,
+    amount: i64,
+    /// Free text such as a payment reference, covered by the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    timestamp: u128,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Output {
+  pub receiver: PublicKey,
+  pub amount: i64,
+}
+
+/// When a locked transfer becomes valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lock {
+  /// Can be included in blocks with at least this block count.
+  Height(u128),
+  /// Can be included once the parent block's timestamp, in microseconds, reaches this.
+  ///
+  /// The parent is used instead of the block itself so every node,
+  /// the mempool included, agrees on whether the lock expired.
+  Time(u128),
 }
 
 impl Transaction {
@@ -23,16 +153,294 @@ impl Transaction {
       .as_micros()
   }
 
+  /// When the transaction was created, in microseconds.
+  pub fn created_at(&self) -> u128 {
+    match self {
+      Transaction::Transfer { timestamp, .. }
+      | Transaction::LockedTransfer { timestamp, .. }
+      | Transaction::BatchTransfer { timestamp, .. }
+      | Transaction::IssueAsset { timestamp, .. }
+      | Transaction::AssetTransfer { timestamp, .. }
+      | Transaction::DeployContract { timestamp, .. }
+      | Transaction::CallContract { timestamp, .. }
+      | Transaction::Stake { timestamp, .. }
+      | Transaction::Unstake { timestamp, .. }
+      | Transaction::Evidence { timestamp, .. }
+      | Transaction::CreateMultisig { timestamp, .. }
+      | Transaction::MultisigTransfer { timestamp, .. } => *timestamp,
+    }
+  }
+
   pub fn transfer(sender: PublicKey, receiver: PublicKey, amount: i64) -> Self {
     Transaction::Transfer {
       id: Uuid::new_v4().to_string(),
       sender,
       receiver,
       amount,
+      memo: None,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn locked_transfer(
+    sender: PublicKey,
+    receiver: PublicKey,
+    amount: i64,
+    unlock: Lock,
+  ) -> Self {
+    Transaction::LockedTransfer {
+      id: Uuid::new_v4().to_string(),
+      sender,
+      receiver,
+      amount,
+      unlock,
+      memo: None,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn batch_transfer(sender: PublicKey, outputs: Vec<Output>) -> Self {
+    Transaction::BatchTransfer {
+      id: Uuid::new_v4().to_string(),
+      sender,
+      outputs,
+      memo: None,
       timestamp: Transaction::timestamp(),
     }
   }
 
+  pub fn issue_asset(issuer: PublicKey, symbol: String, decimals: u8, supply: i64) -> Self {
+    Transaction::IssueAsset {
+      id: Uuid::new_v4().to_string(),
+      issuer,
+      symbol,
+      decimals,
+      supply,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn asset_transfer(
+    sender: PublicKey,
+    receiver: PublicKey,
+    symbol: String,
+    amount: i64,
+  ) -> Self {
+    Transaction::AssetTransfer {
+      id: Uuid::new_v4().to_string(),
+      sender,
+      receiver,
+      symbol,
+      amount,
+      memo: None,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn deploy_contract(deployer: PublicKey, code: Vec<Instruction>) -> Self {
+    let id = Uuid::new_v4().to_string();
+
+    Transaction::DeployContract {
+      contract: Transaction::contract_account(&id),
+      id,
+      deployer,
+      code,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn call_contract(
+    caller: PublicKey,
+    contract: PublicKey,
+    args: Vec<i64>,
+    gas_limit: u64,
+  ) -> Self {
+    Transaction::CallContract {
+      id: Uuid::new_v4().to_string(),
+      caller,
+      contract,
+      args,
+      gas_limit,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  /// The address of the contract created by a `DeployContract` transaction.
+  pub fn contract_account(id: &str) -> PublicKey {
+    format!("contract_{:x}", sha2::Sha256::digest(id.as_bytes()))
+  }
+
+  pub fn stake(validator: PublicKey, amount: i64) -> Self {
+    Transaction::Stake {
+      id: Uuid::new_v4().to_string(),
+      validator,
+      amount,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn unstake(validator: PublicKey, amount: i64) -> Self {
+    Transaction::Unstake {
+      id: Uuid::new_v4().to_string(),
+      validator,
+      amount,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn evidence(reporter: PublicKey, first: SignedBlock, second: SignedBlock) -> Self {
+    Transaction::Evidence {
+      id: Uuid::new_v4().to_string(),
+      reporter,
+      first: Box::new(first),
+      second: Box::new(second),
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn create_multisig(keys: Vec<PublicKey>, threshold: usize) -> Self {
+    let id = Uuid::new_v4().to_string();
+
+    Transaction::CreateMultisig {
+      account: Transaction::multisig_account(&id, &keys, threshold),
+      id,
+      keys,
+      threshold,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  pub fn multisig_transfer(sender: PublicKey, receiver: PublicKey, amount: i64) -> Self {
+    Transaction::MultisigTransfer {
+      id: Uuid::new_v4().to_string(),
+      sender,
+      receiver,
+      amount,
+      memo: None,
+      timestamp: Transaction::timestamp(),
+    }
+  }
+
+  /// The address of the account created by a `CreateMultisig` transaction.
+  ///
+  /// Derived from the transaction so nobody can claim an existing address.
+  pub fn multisig_account(id: &str, keys: &[PublicKey], threshold: usize) -> PublicKey {
+    let as_string = format!("{}{:?}{}", id, keys, threshold);
+
+    format!("multisig_{:x}", sha2::Sha256::digest(as_string.as_bytes()))
+  }
+
+  /// Attaches a memo to payments, other transactions don't carry one.
+  pub fn with_memo(mut self, text: String) -> Self {
+    match &mut self {
+      Transaction::Transfer { memo, .. }
+      | Transaction::LockedTransfer { memo, .. }
+      | Transaction::BatchTransfer { memo, .. }
+      | Transaction::AssetTransfer { memo, .. }
+      | Transaction::MultisigTransfer { memo, .. } => *memo = Some(text),
+      Transaction::IssueAsset { .. }
+      | Transaction::DeployContract { .. }
+      | Transaction::CallContract { .. }
+      | Transaction::Stake { .. }
+      | Transaction::Unstake { .. }
+      | Transaction::Evidence { .. }
+      | Transaction::CreateMultisig { .. } => {}
+    }
+    self
+  }
+
+  pub fn memo(&self) -> Option<&String> {
+    match self {
+      Transaction::Transfer { memo, .. }
+      | Transaction::LockedTransfer { memo, .. }
+      | Transaction::BatchTransfer { memo, .. }
+      | Transaction::AssetTransfer { memo, .. }
+      | Transaction::MultisigTransfer { memo, .. } => memo.as_ref(),
+      Transaction::IssueAsset { .. }
+      | Transaction::DeployContract { .. }
+      | Transaction::CallContract { .. }
+      | Transaction::Stake { .. }
+      | Transaction::Unstake { .. }
+      | Transaction::Evidence { .. }
+      | Transaction::CreateMultisig { .. } => None,
+    }
+  }
+
+  pub fn id(&self) -> &String {
+    match self {
+      Transaction::Transfer { id, .. }
+      | Transaction::LockedTransfer { id, .. }
+      | Transaction::BatchTransfer { id, .. }
+      | Transaction::IssueAsset { id, .. }
+      | Transaction::AssetTransfer { id, .. }
+      | Transaction::DeployContract { id, .. }
+      | Transaction::CallContract { id, .. }
+      | Transaction::Stake { id, .. }
+      | Transaction::Unstake { id, .. }
+      | Transaction::Evidence { id, .. }
+      | Transaction::CreateMultisig { id, .. }
+      | Transaction::MultisigTransfer { id, .. } => id,
+    }
+  }
+
+  /// Accounts whose balance may change when the transaction is executed.
+  pub fn accounts(&self) -> Vec<&PublicKey> {
+    match self {
+      Transaction::Transfer {
+        sender, receiver, ..
+      }
+      | Transaction::LockedTransfer {
+        sender, receiver, ..
+      }
+      | Transaction::AssetTransfer {
+        sender, receiver, ..
+      }
+      | Transaction::MultisigTransfer {
+        sender, receiver, ..
+      } => vec![sender, receiver],
+      Transaction::BatchTransfer {
+        sender, outputs, ..
+      } => std::iter::once(sender)
+        .chain(outputs.iter().map(|output| &output.receiver))
+        .collect(),
+      Transaction::DeployContract {
+        deployer, contract, ..
+      } => vec![deployer, contract],
+      Transaction::CallContract {
+        caller, contract, ..
+      } => vec![caller, contract],
+      Transaction::Stake { validator, .. } | Transaction::Unstake { validator, .. } => {
+        vec![validator]
+      }
+      Transaction::Evidence {
+        reporter, first, ..
+      } => vec![reporter, first.block.forger()],
+      Transaction::CreateMultisig { account, .. } => vec![account],
+      Transaction::IssueAsset { issuer, .. } => vec![issuer],
+    }
+  }
+
+  /// The key that must sign the transaction.
+  ///
+  /// None for multisig transfers, which are authorized by their cosignatures,
+  /// and for creating a multisig account, whose address comes from its keys.
+  pub fn signer(&self) -> Option<&PublicKey> {
+    match self {
+      Transaction::Transfer { sender, .. }
+      | Transaction::LockedTransfer { sender, .. }
+      | Transaction::BatchTransfer { sender, .. }
+      | Transaction::AssetTransfer { sender, .. } => Some(sender),
+      Transaction::IssueAsset { issuer, .. } => Some(issuer),
+      Transaction::DeployContract { deployer, .. } => Some(deployer),
+      Transaction::CallContract { caller, .. } => Some(caller),
+      Transaction::Stake { validator, .. } | Transaction::Unstake { validator, .. } => {
+        Some(validator)
+      }
+      Transaction::Evidence { reporter, .. } => Some(reporter),
+      Transaction::CreateMultisig { .. } | Transaction::MultisigTransfer { .. } => None,
+    }
+  }
+
   pub fn hash(&self) -> String {
     let as_string = format!("{:?}", self);
 
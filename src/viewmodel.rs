@@ -1,12 +1,277 @@
+use crate::account::Asset;
+use crate::events::Subscription;
+use crate::finality::Checkpoint;
+use crate::index::Location;
+use crate::node::{Node, Peer};
+use crate::peer_manager::PeerRecord;
 use crate::transaction::{PublicKey, Transaction};
-use crate::wallet::SignedTransaction;
+use crate::wallet::{SignedBlock, SignedTransaction};
 use serde::{Deserialize, Serialize};
-use std::convert::Into;
+use std::collections::BTreeMap;
+use std::convert::From;
 #[derive(Serialize)]
 pub struct Message {
   pub message: String,
 }
 
+/// Sent by websocket clients, e.g. `{"subscribe": "balances", "accounts": ["..."]}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "subscribe", rename_all = "snake_case")]
+pub enum SubscribeInput {
+  Blocks,
+  Transactions,
+  TransactionStatus { id: String },
+  Balances { accounts: Vec<PublicKey> },
+}
+
+impl From<SubscribeInput> for Subscription {
+  fn from(input: SubscribeInput) -> Self {
+    match input {
+      SubscribeInput::Blocks => Subscription::Blocks,
+      SubscribeInput::Transactions => Subscription::Transactions,
+      SubscribeInput::TransactionStatus { id } => Subscription::TransactionStatus { id },
+      SubscribeInput::Balances { accounts } => Subscription::Balances { accounts },
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockOutput {
+  pub hash: String,
+  pub last_hash: String,
+  pub block_count: u128,
+  pub forger: PublicKey,
+  pub timestamp: u128,
+  pub signature: String,
+  pub transactions: Vec<SignedTransaction>,
+}
+
+impl From<&SignedBlock> for BlockOutput {
+  fn from(signed_block: &SignedBlock) -> Self {
+    BlockOutput {
+      hash: signed_block.hash(),
+      last_hash: signed_block.last_hash(),
+      block_count: signed_block.block_count(),
+      forger: signed_block.block.forger().clone(),
+      timestamp: signed_block.block.timestamp(),
+      signature: signed_block.signature.clone(),
+      transactions: signed_block.block.transactions.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfoOutput {
+  pub version: String,
+  pub chain_id: String,
+  pub peer_id: Option<Peer>,
+  pub listen_addresses: Vec<String>,
+  pub public_key: PublicKey,
+  pub block_count: u128,
+  pub hash: String,
+  pub finalized_height: u128,
+  pub pending_transactions: usize,
+  pub peers: usize,
+}
+
+impl From<&Node> for NodeInfoOutput {
+  fn from(node: &Node) -> Self {
+    let tip = node.chain().tip();
+
+    NodeInfoOutput {
+      version: env!("CARGO_PKG_VERSION").to_owned(),
+      chain_id: node.chain().id(),
+      peer_id: node.network().peer_id.clone(),
+      listen_addresses: node.network().listen_addresses.clone(),
+      public_key: node.public_key(),
+      block_count: tip.block_count(),
+      hash: tip.hash(),
+      finalized_height: node.chain().finality().finalized_height(),
+      pending_transactions: node.transactions().len(),
+      peers: node.peers().peers().len(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FinalityOutput {
+  /// 0 until a checkpoint becomes final, genesis can't be reverted.
+  pub finalized_height: u128,
+  pub finalized: Option<Checkpoint>,
+  /// What validators should be voting on now.
+  pub last_checkpoint: Option<Checkpoint>,
+  pub total_stake: i64,
+}
+
+impl From<&Node> for FinalityOutput {
+  fn from(node: &Node) -> Self {
+    let chain = node.chain();
+
+    FinalityOutput {
+      finalized_height: chain.finality().finalized_height(),
+      finalized: chain.finality().finalized().cloned(),
+      last_checkpoint: chain.last_checkpoint(),
+      total_stake: chain.account().total_stake(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EpochOutput {
+  pub number: u128,
+  pub start: u128,
+  /// Block count of the last block in the epoch.
+  pub end: u128,
+  /// Stake of each validator when the epoch started.
+  pub validators: BTreeMap<PublicKey, i64>,
+  pub total_stake: i64,
+  /// None while the epoch has no validators, anyone may forge then.
+  pub next_forger: Option<PublicKey>,
+}
+
+impl From<&Node> for EpochOutput {
+  fn from(node: &Node) -> Self {
+    let chain = node.chain();
+
+    let epoch = chain.epoch();
+
+    EpochOutput {
+      number: epoch.number,
+      start: epoch.start,
+      end: epoch.start + chain.params().epoch_length.max(1) - 1,
+      validators: epoch.validators.clone(),
+      total_stake: epoch.total_stake(),
+      next_forger: node.next_forger(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionOutput {
+  pub block_count: u128,
+  pub position: usize,
+  pub signature: String,
+  pub transaction: Transaction,
+}
+
+impl From<(Location, &SignedTransaction)> for TransactionOutput {
+  fn from((location, signed_transaction): (Location, &SignedTransaction)) -> Self {
+    TransactionOutput {
+      block_count: location.block_count,
+      position: location.position,
+      signature: signed_transaction.signature.clone(),
+      transaction: signed_transaction.transaction.clone(),
+    }
+  }
+}
+
+/// e.g. `?memo=invoice-42`
+#[derive(Debug, Deserialize)]
+pub struct MemoInput {
+  pub memo: String,
+}
+
+/// Query string of paginated endpoints, e.g. `?offset=20&limit=10`.
+#[derive(Debug, Deserialize)]
+pub struct PageInput {
+  #[serde(default)]
+  pub offset: usize,
+  #[serde(default = "PageInput::default_limit")]
+  pub limit: usize,
+}
+
+impl PageInput {
+  pub const MAX_LIMIT: usize = 100;
+
+  fn default_limit() -> usize {
+    20
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageOutput<T> {
+  pub items: Vec<T>,
+  pub offset: usize,
+  pub limit: usize,
+  pub total: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupplyOutput {
+  pub circulating: i64,
+  pub max: i64,
+  pub next_block_reward: i64,
+}
+
+impl From<&Node> for SupplyOutput {
+  fn from(node: &Node) -> Self {
+    SupplyOutput {
+      circulating: node.chain().supply(),
+      max: node.chain().params().max_supply,
+      next_block_reward: node.chain().next_block_reward(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetOutput {
+  pub symbol: String,
+  pub decimals: u8,
+  pub issuer: PublicKey,
+  pub supply: i64,
+}
+
+impl From<&Asset> for AssetOutput {
+  fn from(asset: &Asset) -> Self {
+    AssetOutput {
+      symbol: asset.symbol.clone(),
+      decimals: asset.decimals,
+      issuer: asset.issuer.clone(),
+      supply: asset.supply,
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetBalanceOutput {
+  pub symbol: String,
+  pub balance: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessOutput {
+  pub ready: bool,
+  pub synced: bool,
+  pub storage_open: bool,
+  pub listening: bool,
+  pub sync_lag: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeerOutput {
+  pub peer: Peer,
+  pub score: i64,
+  pub messages: u64,
+  pub invalid_messages: u64,
+  pub failed_syncs: u64,
+  pub spam_offenses: u64,
+  pub banned_until: Option<u128>,
+}
+
+impl From<(&Peer, &PeerRecord)> for PeerOutput {
+  fn from((peer, record): (&Peer, &PeerRecord)) -> Self {
+    PeerOutput {
+      peer: peer.clone(),
+      score: record.score,
+      messages: record.messages,
+      invalid_messages: record.invalid_messages,
+      failed_syncs: record.failed_syncs,
+      spam_offenses: record.spam_offenses,
+      banned_until: record.banned_until,
+    }
+  }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AddTransactionInput {
   pub public_key: PublicKey,
@@ -15,20 +280,24 @@ pub struct AddTransactionInput {
   pub sender: PublicKey,
   pub receiver: PublicKey,
   pub amount: i64,
+  #[serde(default)]
+  pub memo: Option<String>,
   pub timestamp: u128,
 }
 
-impl Into<SignedTransaction> for AddTransactionInput {
-  fn into(self) -> SignedTransaction {
+impl From<AddTransactionInput> for SignedTransaction {
+  fn from(input: AddTransactionInput) -> Self {
     SignedTransaction {
-      signature: self.signature,
+      signature: input.signature,
       transaction: Transaction::Transfer {
-        id: self.id,
-        sender: self.sender,
-        receiver: self.receiver,
-        amount: self.amount,
-        timestamp: self.timestamp,
+        id: input.id,
+        sender: input.sender,
+        receiver: input.receiver,
+        amount: input.amount,
+        memo: input.memo,
+        timestamp: input.timestamp,
       },
+      cosignatures: Vec::new(),
     }
   }
 }
@@ -0,0 +1,334 @@
+//! A small deterministic stack machine that runs contract code.
+//!
+//! Values are `i64`s, arithmetic is checked and storage is ordered, so every
+//! node gets the same result from the same code, arguments and storage.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Values the stack may hold at once.
+pub const MAX_STACK_SIZE: usize = 1024;
+
+pub type Storage = BTreeMap<i64, i64>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Instruction {
+  Push(i64),
+  Pop,
+  Dup,
+  Swap,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+  /// Pushes 1 if the two values on top are equal, 0 otherwise.
+  Eq,
+  /// Pushes 1 if the value below the top is less than the top, 0 otherwise.
+  Lt,
+  Gt,
+  /// Pushes 1 if the top is 0, 0 otherwise.
+  Not,
+  Jump(usize),
+  /// Pops a value and jumps if it isn't 0.
+  JumpIf(usize),
+  /// Pushes the nth argument of the call.
+  Arg(usize),
+  /// Pops a key and pushes the value stored under it, 0 if there's none.
+  Load,
+  /// Pops a value and then a key and stores the value under the key.
+  Store,
+  /// Pops a value and appends it to the logs of the call.
+  Log,
+  Stop,
+  /// Stops and discards every change made by the call.
+  Revert,
+}
+
+impl Instruction {
+  fn gas(&self) -> u64 {
+    match self {
+      Instruction::Store => 20,
+      Instruction::Load | Instruction::Log => 5,
+      _ => 1,
+    }
+  }
+}
+
+/// A deployed contract.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Contract {
+  pub code: Vec<Instruction>,
+  pub storage: Storage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+  OutOfGas,
+  StackUnderflow,
+  StackOverflow,
+  InvalidJump(usize),
+  MissingArgument(usize),
+  Overflow,
+  DivisionByZero,
+  Reverted,
+}
+
+/// What a call left behind, failed calls keep no logs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContractReceipt {
+  pub gas_used: u64,
+  pub logs: Vec<i64>,
+}
+
+/// A call that failed still used the gas up to where it stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmFailure {
+  pub error: VmError,
+  pub gas_used: u64,
+}
+
+#[derive(Debug)]
+struct Machine<'a> {
+  args: &'a [i64],
+  stack: Vec<i64>,
+  storage: Storage,
+  logs: Vec<i64>,
+  gas_used: u64,
+}
+
+impl<'a> Machine<'a> {
+  fn pop(&mut self) -> Result<i64, VmError> {
+    self.stack.pop().ok_or(VmError::StackUnderflow)
+  }
+
+  fn push(&mut self, value: i64) -> Result<(), VmError> {
+    if self.stack.len() == MAX_STACK_SIZE {
+      return Err(VmError::StackOverflow);
+    }
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn binary(&mut self, f: impl Fn(i64, i64) -> Result<i64, VmError>) -> Result<(), VmError> {
+    let b = self.pop()?;
+    let a = self.pop()?;
+    self.push(f(a, b)?)
+  }
+}
+
+/// Runs `code` on a copy of `storage`.
+///
+/// Returns the new storage, changes are only kept if the call succeeds.
+/// Running out of gas uses all of `gas_limit`.
+pub fn execute(
+  code: &[Instruction],
+  args: &[i64],
+  storage: &Storage,
+  gas_limit: u64,
+) -> Result<(Storage, ContractReceipt), VmFailure> {
+  let mut machine = Machine {
+    args,
+    stack: Vec::new(),
+    storage: storage.clone(),
+    logs: Vec::new(),
+    gas_used: 0,
+  };
+
+  if let Err(error) = run(&mut machine, code, gas_limit) {
+    return Err(VmFailure {
+      error,
+      gas_used: machine.gas_used.min(gas_limit),
+    });
+  }
+
+  Ok((
+    machine.storage,
+    ContractReceipt {
+      gas_used: machine.gas_used,
+      logs: machine.logs,
+    },
+  ))
+}
+
+fn run(machine: &mut Machine, code: &[Instruction], gas_limit: u64) -> Result<(), VmError> {
+  let mut pc = 0;
+
+  while let Some(instruction) = code.get(pc) {
+    machine.gas_used += instruction.gas();
+
+    if machine.gas_used > gas_limit {
+      return Err(VmError::OutOfGas);
+    }
+
+    pc += 1;
+
+    match instruction {
+      Instruction::Push(value) => machine.push(*value)?,
+      Instruction::Pop => {
+        machine.pop()?;
+      }
+      Instruction::Dup => {
+        let value = machine.pop()?;
+        machine.push(value)?;
+        machine.push(value)?;
+      }
+      Instruction::Swap => {
+        let b = machine.pop()?;
+        let a = machine.pop()?;
+        machine.push(b)?;
+        machine.push(a)?;
+      }
+      Instruction::Add => machine.binary(|a, b| a.checked_add(b).ok_or(VmError::Overflow))?,
+      Instruction::Sub => machine.binary(|a, b| a.checked_sub(b).ok_or(VmError::Overflow))?,
+      Instruction::Mul => machine.binary(|a, b| a.checked_mul(b).ok_or(VmError::Overflow))?,
+      Instruction::Div | Instruction::Rem => {
+        let divide = *instruction == Instruction::Div;
+        machine.binary(|a, b| {
+          if b == 0 {
+            return Err(VmError::DivisionByZero);
+          }
+          let result = if divide {
+            a.checked_div(b)
+          } else {
+            a.checked_rem(b)
+          };
+          result.ok_or(VmError::Overflow)
+        })?
+      }
+      Instruction::Eq => machine.binary(|a, b| Ok((a == b) as i64))?,
+      Instruction::Lt => machine.binary(|a, b| Ok((a < b) as i64))?,
+      Instruction::Gt => machine.binary(|a, b| Ok((a > b) as i64))?,
+      Instruction::Not => {
+        let value = machine.pop()?;
+        machine.push((value == 0) as i64)?;
+      }
+      Instruction::Jump(target) => pc = jump(code, *target)?,
+      Instruction::JumpIf(target) => {
+        if machine.pop()? != 0 {
+          pc = jump(code, *target)?;
+        }
+      }
+      Instruction::Arg(n) => {
+        let value = *machine.args.get(*n).ok_or(VmError::MissingArgument(*n))?;
+        machine.push(value)?;
+      }
+      Instruction::Load => {
+        let key = machine.pop()?;
+        let value = machine.storage.get(&key).copied().unwrap_or(0);
+        machine.push(value)?;
+      }
+      Instruction::Store => {
+        let value = machine.pop()?;
+        let key = machine.pop()?;
+        machine.storage.insert(key, value);
+      }
+      Instruction::Log => {
+        let value = machine.pop()?;
+        machine.logs.push(value);
+      }
+      Instruction::Stop => break,
+      Instruction::Revert => return Err(VmError::Reverted),
+    }
+  }
+
+  Ok(())
+}
+
+fn jump(code: &[Instruction], target: usize) -> Result<usize, VmError> {
+  if target < code.len() {
+    Ok(target)
+  } else {
+    Err(VmError::InvalidJump(target))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use Instruction::*;
+
+  /// Adds the first argument to the counter stored under key 0 and logs the total.
+  fn counter() -> Vec<Instruction> {
+    vec![Push(0), Push(0), Load, Arg(0), Add, Dup, Log, Store]
+  }
+
+  #[test]
+  fn runs_code_against_storage() {
+    let (storage, receipt) = execute(&counter(), &[5], &Storage::new(), 100).unwrap();
+
+    let (storage, receipt_2) = execute(&counter(), &[3], &storage, 100).unwrap();
+
+    assert_eq!(Some(&8), storage.get(&0));
+    assert_eq!(vec![5], receipt.logs);
+    assert_eq!(vec![8], receipt_2.logs);
+    assert_eq!(1 + 1 + 5 + 1 + 1 + 1 + 5 + 20, receipt.gas_used);
+  }
+
+  #[test]
+  fn stops_when_gas_runs_out() {
+    let infinite_loop = vec![Jump(0)];
+
+    assert_eq!(
+      Err(VmFailure {
+        error: VmError::OutOfGas,
+        gas_used: 1000,
+      }),
+      execute(&infinite_loop, &[], &Storage::new(), 1000)
+    );
+  }
+
+  #[test]
+  fn failed_calls_report_the_gas_they_used() {
+    let code = vec![Push(0), Push(1), Store, Revert];
+
+    assert_eq!(
+      Err(VmFailure {
+        error: VmError::Reverted,
+        gas_used: 1 + 1 + 20 + 1,
+      }),
+      execute(&code, &[], &Storage::new(), 1000)
+    );
+  }
+
+  #[test]
+  fn reports_invalid_programs() {
+    let cases = vec![
+      (vec![Add], VmError::StackUnderflow),
+      (vec![Push(1), Push(0), Div], VmError::DivisionByZero),
+      (vec![Push(i64::MAX), Push(1), Add], VmError::Overflow),
+      (vec![Jump(5)], VmError::InvalidJump(5)),
+      (vec![Arg(0)], VmError::MissingArgument(0)),
+      (vec![Push(0), Push(1), Store, Revert], VmError::Reverted),
+    ];
+
+    for (code, error) in cases {
+      assert_eq!(
+        Err(error),
+        execute(&code, &[], &Storage::new(), 1000).map_err(|failure| failure.error)
+      );
+    }
+  }
+
+  #[test]
+  fn jumps_only_when_the_condition_holds() {
+    // Logs 1 if the argument is positive, 0 otherwise.
+    let code = vec![
+      Arg(0),
+      Push(0),
+      Gt,
+      JumpIf(7),
+      Push(0),
+      Log,
+      Stop,
+      Push(1),
+      Log,
+    ];
+
+    let logs = |arg| execute(&code, &[arg], &Storage::new(), 100).unwrap().1.logs;
+
+    assert_eq!(vec![1], logs(7));
+    assert_eq!(vec![0], logs(-7));
+  }
+}
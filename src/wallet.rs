@@ -1,25 +1,37 @@
 use crate::block::Block;
+use crate::finality::{Checkpoint, SignedVote, Vote};
 use crate::transaction::{PublicKey, Transaction};
 use hex;
 use openssl::{
+  error::ErrorStack,
   hash::MessageDigest,
   pkey::{PKey, Private},
   rsa::Rsa,
   sign::{Signer, Verifier},
 };
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub struct Wallet {
   key_pair: Rsa<Private>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SignedTransaction {
   pub signature: String,
   pub transaction: Transaction,
+  /// Extra signatures, used to authorize transfers out of multisig accounts.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub cosignatures: Vec<Cosignature>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cosignature {
+  pub public_key: PublicKey,
+  pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SignedBlock {
   pub signature: String,
   pub block: Block,
@@ -41,11 +53,22 @@ impl SignedBlock {
     self.block.last_hash()
   }
 
+  /// How many bytes the block takes when sent to other nodes.
+  pub fn size(&self) -> usize {
+    serde_json::to_vec(self).unwrap().len()
+  }
+
   pub fn block_count(&self) -> u128 {
     self.block.block_count()
   }
 }
 
+impl Default for Wallet {
+  fn default() -> Self {
+    Wallet::new()
+  }
+}
+
 impl Wallet {
   pub fn new() -> Self {
     Wallet {
@@ -53,34 +76,89 @@ impl Wallet {
     }
   }
 
+  /// Loads a wallet saved with `private_key_pem`.
+  pub fn from_private_key_pem(pem: &[u8]) -> Result<Self, ErrorStack> {
+    Ok(Wallet {
+      key_pair: Rsa::private_key_from_pem(pem)?,
+    })
+  }
+
+  pub fn private_key_pem(&self) -> Vec<u8> {
+    self.key_pair.private_key_to_pem().unwrap()
+  }
+
   pub fn public_key(&self) -> PublicKey {
     hex::encode(self.key_pair.public_key_to_pem().unwrap())
   }
 
   pub fn sign_transaction(&self, transaction: Transaction) -> SignedTransaction {
-    let key_pair = PKey::from_rsa(self.key_pair.clone()).unwrap();
-
-    let mut signer = Signer::new(MessageDigest::sha256(), &key_pair).unwrap();
-
-    signer.update(transaction.hash().as_bytes()).unwrap();
-
-    let mut buffer = vec![0; signer.len().unwrap()];
-
-    signer.sign(&mut buffer).unwrap();
-
     SignedTransaction {
-      signature: hex::encode(buffer),
+      signature: self.sign(&transaction.hash()),
       transaction,
+      cosignatures: Vec::new(),
     }
   }
 
+  /// Adds this wallet's signature to the transaction's cosignatures.
+  pub fn cosign(&self, signed_transaction: &mut SignedTransaction) {
+    let signature = self.sign(&signed_transaction.transaction.hash());
+
+    signed_transaction.cosignatures.push(Cosignature {
+      public_key: self.public_key(),
+      signature,
+    });
+  }
+
   pub fn verify_transaction(
     hex_encoded_public_key: &PublicKey,
     SignedTransaction {
       signature,
       transaction,
+      ..
     }: &SignedTransaction,
   ) -> bool {
+    Wallet::verify(hex_encoded_public_key, &transaction.hash(), signature)
+  }
+
+  /// Checks that the transaction was signed by the account it acts for
+  /// and that every cosignature is genuine.
+  ///
+  /// Whether there are enough cosignatures depends on the multisig account,
+  /// that is up to the chain.
+  pub fn verify_signer(signed_transaction: &SignedTransaction) -> bool {
+    let signed_by_the_signer = match signed_transaction.transaction.signer() {
+      None => true,
+      Some(signer) => Wallet::verify_transaction(signer, signed_transaction),
+    };
+
+    signed_by_the_signer
+      && signed_transaction
+        .cosignatures
+        .iter()
+        .all(|cosignature| Wallet::verify_cosignature(cosignature, &signed_transaction.transaction))
+  }
+
+  pub fn verify_cosignature(
+    Cosignature {
+      public_key,
+      signature,
+    }: &Cosignature,
+    transaction: &Transaction,
+  ) -> bool {
+    Wallet::verify(public_key, &transaction.hash(), signature)
+  }
+
+  /// Checks that the block was signed by its forger.
+  pub fn verify_block_forger(SignedBlock { signature, block }: &SignedBlock) -> bool {
+    Wallet::verify(block.forger(), &block.hash(), signature)
+  }
+
+  fn verify(hex_encoded_public_key: &PublicKey, hash: &str, signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+      Err(_) => return false,
+      Ok(signature) => signature,
+    };
+
     let public_key = match hex::decode(hex_encoded_public_key) {
       Err(_) => return false,
       Ok(key) => key,
@@ -101,27 +179,48 @@ impl Wallet {
       Ok(verifier) => verifier,
     };
 
-    match verifier.update(transaction.hash().as_bytes()) {
+    match verifier.update(hash.as_bytes()) {
       Err(_) => false,
-      Ok(_) => verifier.verify(&hex::decode(signature).unwrap()).unwrap(),
+      Ok(_) => verifier.verify(&signature).unwrap_or(false),
     }
   }
 
   pub fn sign_block(&self, block: Block) -> SignedBlock {
+    SignedBlock {
+      signature: self.sign(&block.hash()),
+      block,
+    }
+  }
+
+  /// Votes for the checkpoint as the validator holding this wallet's key.
+  pub fn sign_vote(&self, checkpoint: Checkpoint) -> SignedVote {
+    let vote = Vote {
+      validator: self.public_key(),
+      checkpoint,
+    };
+
+    SignedVote {
+      signature: self.sign(&vote.hash()),
+      vote,
+    }
+  }
+
+  pub fn verify_vote(SignedVote { signature, vote }: &SignedVote) -> bool {
+    Wallet::verify(&vote.validator, &vote.hash(), signature)
+  }
+
+  fn sign(&self, hash: &str) -> String {
     let key_pair = PKey::from_rsa(self.key_pair.clone()).unwrap();
 
     let mut signer = Signer::new(MessageDigest::sha256(), &key_pair).unwrap();
 
-    signer.update(block.hash().as_bytes()).unwrap();
+    signer.update(hash.as_bytes()).unwrap();
 
     let mut buffer = vec![0; signer.len().unwrap()];
 
     signer.sign(&mut buffer).unwrap();
 
-    SignedBlock {
-      signature: hex::encode(buffer),
-      block,
-    }
+    hex::encode(buffer)
   }
 
   pub fn verify_block(&self, SignedBlock { signature, block }: &SignedBlock) -> bool {
@@ -177,6 +276,29 @@ mod tests {
     )
   }
 
+  #[test]
+  fn memos_are_covered_by_the_signature() {
+    let wallet = Wallet::new();
+
+    let mut signed_transaction = wallet.sign_transaction(
+      Transaction::transfer(
+        String::from("sender_public_key"),
+        String::from("receiver_public_key"),
+        10,
+      )
+      .with_memo(String::from("invoice-1")),
+    );
+
+    signed_transaction.transaction = signed_transaction
+      .transaction
+      .with_memo(String::from("invoice-2"));
+
+    assert!(!Wallet::verify_transaction(
+      &wallet.public_key(),
+      &signed_transaction
+    ));
+  }
+
   #[test]
   fn verifies_blocks_signed_by_same_wallet() {
     let wallet = Wallet::new();
@@ -211,6 +333,54 @@ mod tests {
     assert_eq!(wallet_b.verify_block(&block_signed_by_wallet_a), false)
   }
 
+  #[test]
+  fn verifies_blocks_signed_by_their_forger() {
+    let wallet = Wallet::new();
+
+    let block = Block::new(
+      Vec::new(),
+      String::from("last_hash"),
+      wallet.public_key(),
+      1,
+    );
+
+    assert!(Wallet::verify_block_forger(&wallet.sign_block(block)));
+  }
+
+  #[test]
+  fn verifies_blocks_signed_by_someone_other_than_their_forger() {
+    let forger = Wallet::new();
+
+    let block = Block::new(
+      Vec::new(),
+      String::from("last_hash"),
+      forger.public_key(),
+      1,
+    );
+
+    let signed_block = Wallet::new().sign_block(block);
+
+    assert!(!Wallet::verify_block_forger(&signed_block));
+  }
+
+  #[test]
+  fn signatures_that_are_not_hex_encoded_are_invalid() {
+    let wallet = Wallet::new();
+
+    let mut signed_transaction = wallet.sign_transaction(Transaction::transfer(
+      String::from("sender_public_key"),
+      String::from("receiver_public_key"),
+      10,
+    ));
+
+    signed_transaction.signature = String::from("not_hex");
+
+    assert!(!Wallet::verify_transaction(
+      &wallet.public_key(),
+      &signed_transaction
+    ));
+  }
+
   #[test]
   fn genesis_block_has_default_signature() {
     let expected = SignedBlock {
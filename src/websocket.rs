@@ -0,0 +1,68 @@
+use crate::events::{Event, Subscriptions};
+use crate::viewmodel;
+use actix_ws::{Message, MessageStream, Session};
+use futures::StreamExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Forwards the events a websocket client subscribed to until it goes away.
+pub async fn session(
+  mut session: Session,
+  mut messages: MessageStream,
+  mut events: broadcast::Receiver<Event>,
+) {
+  let mut subscriptions = Subscriptions::new();
+
+  loop {
+    let reply = tokio::select! {
+      message = messages.next() => match message {
+        Some(Ok(Message::Text(text))) => {
+          match serde_json::from_str::<viewmodel::SubscribeInput>(&text) {
+            Ok(input) => {
+              subscriptions.add(input.into());
+              None
+            }
+            Err(error) => Some(viewmodel::Message {
+              message: format!("invalid subscription: {}", error),
+            }),
+          }
+        }
+        Some(Ok(Message::Ping(bytes))) => {
+          if session.pong(&bytes).await.is_err() {
+            return;
+          }
+          None
+        }
+        Some(Ok(Message::Close(reason))) => {
+          let _ = session.close(reason).await;
+          return;
+        }
+        Some(Ok(_)) => None,
+        Some(Err(_)) | None => return,
+      },
+      event = events.recv() => match event {
+        Ok(event) => {
+          if subscriptions.matches(&event)
+            && session.text(serde_json::to_string(&event).unwrap()).await.is_err()
+          {
+            return;
+          }
+          None
+        }
+        Err(RecvError::Lagged(missed)) => Some(viewmodel::Message {
+          message: format!("missed {} events", missed),
+        }),
+        Err(RecvError::Closed) => return,
+      },
+    };
+
+    if let Some(reply) = reply {
+      if session
+        .text(serde_json::to_string(&reply).unwrap())
+        .await
+        .is_err()
+      {
+        return;
+      }
+    }
+  }
+}